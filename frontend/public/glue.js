@@ -1,4 +1,5 @@
 const invoke = window.__TAURI__ !== undefined ? window.__TAURI__.core.invoke : (e) => {throw new Error(`Tauri not available`);};
+const listen = window.__TAURI__ !== undefined ? window.__TAURI__.event.listen : (e) => {throw new Error(`Tauri not available`);};
 
 /**
  * Tauri binding for the `get_sites` function.
@@ -17,6 +18,22 @@ export async function getDepartures(site) {
     return await invoke("get_departures", {siteId: site});
 }
 
+/**
+ * Tauri binding for the `get_deviations` function.
+ * @returns {Promise<>}
+ */
+export async function getDeviations() {
+    return await invoke("get_deviations");
+}
+
+/**
+ * Tauri binding for the `get_routes` function.
+ * @returns {Promise<>}
+ */
+export async function getRoutes() {
+    return await invoke("get_routes");
+}
+
 /**
  * Tauri binding for the `get_currency` function.
  * @returns {Promise<>}
@@ -25,6 +42,30 @@ export async function getCurrency() {
     return await invoke("get_currency");
 }
 
+/**
+ * Tauri binding for the `get_currency_history` function.
+ * @returns {Promise<>}
+ */
+export async function getCurrencyHistory() {
+    return await invoke("get_currency_history");
+}
+
+/**
+ * Tauri binding for the `get_currency_alerts` function.
+ * @returns {Promise<>}
+ */
+export async function getCurrencyAlerts() {
+    return await invoke("get_currency_alerts");
+}
+
+/**
+ * Tauri binding for the `get_quotes` function.
+ * @returns {Promise<>}
+ */
+export async function getQuotes() {
+    return await invoke("get_quotes");
+}
+
 /**
  * Tauri binding for the `get_weather` function.
  * @returns {Promise<>}
@@ -32,3 +73,12 @@ export async function getCurrency() {
 export async function getWeather() {
     return await invoke("get_weather");
 }
+
+/**
+ * Reloads the page when the settings file changes, so that every panel
+ * fetches its data with the new settings.
+ * @returns {Promise<>}
+ */
+export async function reloadOnSettingsChange() {
+    return await listen("settings-changed", () => window.location.reload());
+}
@@ -1,12 +1,15 @@
 use chrono::prelude::{DateTime, Local};
 use futures::StreamExt;
+use gloo_console::log;
 use yew::{html, Component, Context, Html, Properties};
 
 use super::services::{refresh_currency, start_currency_job, stream_time};
-use homedisplay::models::currency::Conversion;
+use homedisplay::models::currency::{Conversion, ConversionHistory, TriggeredAlert};
 
 pub struct CurrencyComponent {
-    currency: Option<Conversion>,
+    currency: Option<Vec<Conversion>>,
+    history: Vec<ConversionHistory>,
+    alerts: Vec<TriggeredAlert>,
     loading: bool,
     error: Option<String>,
     current_date: DateTime<Local>,
@@ -15,7 +18,57 @@ pub struct CurrencyComponent {
 pub enum Msg {
     ClockUpdate(DateTime<Local>),
     LoadCurrencyData,
-    CurrencyDataReceived(Result<Conversion, String>),
+    CurrencyDataReceived(Result<Vec<Conversion>, String>),
+    HistoryDataReceived(Result<Vec<ConversionHistory>, String>),
+    AlertsDataReceived(Result<Vec<TriggeredAlert>, String>),
+}
+
+/// Renders the history of a conversion as a small line chart, with its 7, 30 and 90 days change
+fn history_chart(history: &ConversionHistory) -> Html {
+    let rates = history.rates();
+    if rates.len() < 2 {
+        return html! {};
+    }
+
+    let min = rates.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = rates.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let step = 100.0 / (rates.len() - 1) as f32;
+    let points = rates
+        .iter()
+        .enumerate()
+        .map(|(index, rate)| {
+            let y = if max > min {
+                20.0 - (rate - min) / (max - min) * 20.0
+            } else {
+                10.0
+            };
+            format!("{:.1},{:.1}", index as f32 * step, y)
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    let changes = [7, 30, 90]
+        .iter()
+        .filter_map(|days| {
+            history.change(*days).map(|change| {
+                let color = if change >= 0.0 { "#4CAF50" } else { "#e05252" };
+                html! {
+                    <span class="currency-change" style={format!("color: {color}")}>
+                        { format!("{days}d {change:+.1}%") }
+                    </span>
+                }
+            })
+        })
+        .collect::<Html>();
+
+    html! {
+        <div class="currency-history">
+            <svg viewBox="0 0 100 20" preserveAspectRatio="none" class="currency-chart">
+                <polyline points={points} fill="none" stroke="#4b9cdb" stroke-width="1" />
+            </svg>
+            <div>{ changes }</div>
+        </div>
+    }
 }
 
 #[derive(Properties, PartialEq)]
@@ -29,13 +82,17 @@ impl Component for CurrencyComponent {
 
     fn create(ctx: &Context<Self>) -> Self {
         let currency_ready_cb = ctx.link().callback(Msg::CurrencyDataReceived);
-        start_currency_job(currency_ready_cb);
+        let history_ready_cb = ctx.link().callback(Msg::HistoryDataReceived);
+        let alerts_ready_cb = ctx.link().callback(Msg::AlertsDataReceived);
+        start_currency_job(currency_ready_cb, history_ready_cb, alerts_ready_cb);
 
         let time_stream = stream_time();
         ctx.link().send_stream(time_stream.map(Msg::ClockUpdate));
 
         Self {
             currency: None,
+            history: Vec::new(),
+            alerts: Vec::new(),
             loading: true,
             error: None,
             current_date: Local::now(),
@@ -72,6 +129,26 @@ impl Component for CurrencyComponent {
                 self.loading = false;
                 true
             }
+            Msg::HistoryDataReceived(result) => match result {
+                Ok(history) => {
+                    self.history = history;
+                    true
+                }
+                Err(e) => {
+                    log!(format!("Unable to load currency history: {e}"));
+                    false
+                }
+            },
+            Msg::AlertsDataReceived(result) => match result {
+                Ok(alerts) => {
+                    self.alerts = alerts;
+                    true
+                }
+                Err(e) => {
+                    log!(format!("Unable to check currency alerts: {e}"));
+                    false
+                }
+            },
         }
     }
 
@@ -81,11 +158,16 @@ impl Component for CurrencyComponent {
         let current_time: String = self.current_date.format("%H:%M").to_string();
 
         let title_node = html! {
-            <div style="width: 100%;text-align: center;">
-            <p class="time-text">{ current_time }</p>
-                <p class="date-text">{ current_day }</p>
-                <p class="date-text">{ current_date }</p>
-            </div>
+            <>
+                <div style="width: 100%;text-align: center;">
+                <p class="time-text">{ current_time }</p>
+                    <p class="date-text">{ current_day }</p>
+                    <p class="date-text">{ current_date }</p>
+                </div>
+                { self.alerts.iter().map(|alert| html! {
+                    <div class="currency-alert">{ format!("⚠ {}", alert.message) }</div>
+                }).collect::<Html>() }
+            </>
         };
 
         if let Some(error) = &self.error {
@@ -112,18 +194,9 @@ impl Component for CurrencyComponent {
                     </div>
                 </div>
             }
-        } else if let Some(conversion) = self.currency.as_ref() {
-            let from_currency = format!(
-                "{:.02} {}",
-                conversion.from_currency_amount, conversion.from_currency
-            );
-            let to_currency = format!(
-                "{:.02} {}",
-                conversion.to_currency_amount, conversion.to_currency
-            );
-
+        } else if let Some(conversions) = self.currency.as_ref().filter(|c| !c.is_empty()) {
             let refresh_date = {
-                let date_fetched = DateTime::from_timestamp(conversion.timestamp, 0)
+                let date_fetched = DateTime::from_timestamp(conversions[0].timestamp, 0)
                     .unwrap()
                     .with_timezone(&Local);
                 let date = format!("{}", date_fetched.format("%d/%m/%Y"));
@@ -135,16 +208,36 @@ impl Component for CurrencyComponent {
                 <div class="panel">
                     { title_node }
                     <div class="panel-div">
-                        <div>
-                            <div>
-                                <p class="currency-text">
-                                    { from_currency }
-                                </p>
-                                <p class="currency-text">
-                                    { to_currency }
-                                </p>
-                            </div>
-                        </div>
+                        <table style="width: 100%;">
+                            { conversions.iter().map(|conversion| {
+                                let from_currency = format!(
+                                    "{:.02} {}",
+                                    conversion.from_currency_amount, conversion.from_currency
+                                );
+                                let to_currency = format!(
+                                    "{:.02} {}",
+                                    conversion.to_currency_amount, conversion.to_currency
+                                );
+                                let history = self.history.iter().find(|history| {
+                                    history.from_currency == conversion.from_currency
+                                        && history.to_currency == conversion.to_currency
+                                });
+                                html! {
+                                    <>
+                                        <tr>
+                                            <td><p class="currency-text">{ from_currency }</p></td>
+                                            <td><p class="currency-text">{ "=" }</p></td>
+                                            <td><p class="currency-text">{ to_currency }</p></td>
+                                        </tr>
+                                        if let Some(history) = history {
+                                            <tr>
+                                                <td colspan="3">{ history_chart(history) }</td>
+                                            </tr>
+                                        }
+                                    </>
+                                }
+                            }).collect::<Html>() }
+                        </table>
                     </div>
                     <small class="refresh-text">
                         { refresh_date }
@@ -5,17 +5,17 @@ use wasm_bindgen_futures::spawn_local;
 use yew::platform::time::{interval, sleep};
 use yew::Callback;
 
-use crate::glue::get_currency;
-use homedisplay::models::currency::Conversion;
+use crate::glue::{get_currency, get_currency_alerts, get_currency_history};
+use homedisplay::models::currency::{Conversion, ConversionHistory, TriggeredAlert};
 
 const ONE_SEC: Duration = Duration::from_secs(1);
 const CONVERSION_REFRESH_INTERVAL: Duration = Duration::from_secs(3600);
 
-pub fn refresh_currency(callback: Callback<Result<Conversion, String>>) {
+pub fn refresh_currency(callback: Callback<Result<Vec<Conversion>, String>>) {
     spawn_local(async move {
         match get_currency().await {
             Ok(response) => {
-                let currency: Result<Conversion, String> =
+                let currency: Result<Vec<Conversion>, String> =
                     serde_wasm_bindgen::from_value(response).map_err(|e| e.to_string());
                 callback.emit(currency);
             }
@@ -26,10 +26,46 @@ pub fn refresh_currency(callback: Callback<Result<Conversion, String>>) {
     });
 }
 
-pub fn start_currency_job(callback: Callback<Result<Conversion, String>>) {
+pub fn refresh_currency_history(callback: Callback<Result<Vec<ConversionHistory>, String>>) {
+    spawn_local(async move {
+        match get_currency_history().await {
+            Ok(response) => {
+                let history: Result<Vec<ConversionHistory>, String> =
+                    serde_wasm_bindgen::from_value(response).map_err(|e| e.to_string());
+                callback.emit(history);
+            }
+            Err(e) => {
+                callback.emit(serde_wasm_bindgen::from_value(e).map_err(|e| e.to_string()));
+            }
+        }
+    });
+}
+
+pub fn refresh_currency_alerts(callback: Callback<Result<Vec<TriggeredAlert>, String>>) {
+    spawn_local(async move {
+        match get_currency_alerts().await {
+            Ok(response) => {
+                let alerts: Result<Vec<TriggeredAlert>, String> =
+                    serde_wasm_bindgen::from_value(response).map_err(|e| e.to_string());
+                callback.emit(alerts);
+            }
+            Err(e) => {
+                callback.emit(serde_wasm_bindgen::from_value(e).map_err(|e| e.to_string()));
+            }
+        }
+    });
+}
+
+pub fn start_currency_job(
+    callback: Callback<Result<Vec<Conversion>, String>>,
+    history_callback: Callback<Result<Vec<ConversionHistory>, String>>,
+    alerts_callback: Callback<Result<Vec<TriggeredAlert>, String>>,
+) {
     spawn_local(async move {
         loop {
             refresh_currency(callback.clone());
+            refresh_currency_history(history_callback.clone());
+            refresh_currency_alerts(alerts_callback.clone());
             sleep(CONVERSION_REFRESH_INTERVAL).await;
         }
     });
@@ -8,9 +8,27 @@ extern "C" {
     #[wasm_bindgen(js_name = getDepartures, catch)]
     pub async fn get_departures(site: String) -> Result<JsValue, JsValue>;
 
+    #[wasm_bindgen(js_name = getDeviations, catch)]
+    pub async fn get_deviations() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_name = getRoutes, catch)]
+    pub async fn get_routes() -> Result<JsValue, JsValue>;
+
     #[wasm_bindgen(js_name = getCurrency, catch)]
     pub async fn get_currency() -> Result<JsValue, JsValue>;
 
+    #[wasm_bindgen(js_name = getCurrencyHistory, catch)]
+    pub async fn get_currency_history() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_name = getCurrencyAlerts, catch)]
+    pub async fn get_currency_alerts() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_name = getQuotes, catch)]
+    pub async fn get_quotes() -> Result<JsValue, JsValue>;
+
     #[wasm_bindgen(js_name = getWeather, catch)]
     pub async fn get_weather() -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_name = reloadOnSettingsChange, catch)]
+    pub async fn reload_on_settings_change() -> Result<JsValue, JsValue>;
 }
@@ -1,15 +1,23 @@
+use gloo_console::log;
 use yew::prelude::*;
 
 mod currency;
 mod glue;
+mod quotes;
 mod transports;
 mod weather;
 
 use currency::component::CurrencyComponent;
+use quotes::component::QuotesComponent;
 use transports::components::transport::TransportsComponent;
 use weather::component::WeatherComponent;
 
 fn main() {
+    wasm_bindgen_futures::spawn_local(async {
+        if let Err(e) = glue::reload_on_settings_change().await {
+            log!("Unable to listen to the settings changes:", e);
+        }
+    });
     yew::Renderer::<App>::new().render();
 }
 
@@ -21,6 +29,7 @@ pub fn app() -> Html {
             <WeatherComponent must_refresh=false />
             <CurrencyComponent must_refresh=false />
             <TransportsComponent must_refresh=false />
+            <QuotesComponent />
         </div>
     }
 }
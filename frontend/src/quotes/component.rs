@@ -0,0 +1,79 @@
+use gloo_console::log;
+use yew::{html, Component, Context, Html};
+
+use super::services::start_quotes_job;
+use homedisplay::models::quotes::{Quote, QuoteTable};
+
+/// Ticker scrolling the cryptocurrency and commodity quotes along the bottom of the screen
+pub struct QuotesComponent {
+    quotes: QuoteTable,
+}
+
+pub enum Msg {
+    QuotesDataReceived(Result<QuoteTable, String>),
+}
+
+/// Renders a single quote of the ticker, e.g. `BTC 67000.00 USD ▲1.20%`
+fn quote_item(quote: &Quote) -> Html {
+    let change = quote.change_24h.map(|change| {
+        let (arrow, class) = if change >= 0.0 {
+            ("▲", "quote-up")
+        } else {
+            ("▼", "quote-down")
+        };
+        html! { <span class={class}>{ format!(" {arrow}{:.2}%", change.abs()) }</span> }
+    });
+
+    html! {
+        <span class="quote">
+            <b>{ format!("{} ", quote.symbol) }</b>
+            { format!("{:.2} {}", quote.price, quote.currency) }
+            { change.unwrap_or_default() }
+        </span>
+    }
+}
+
+impl Component for QuotesComponent {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        start_quotes_job(ctx.link().callback(Msg::QuotesDataReceived));
+
+        Self {
+            quotes: QuoteTable::default(),
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::QuotesDataReceived(result) => match result {
+                Ok(quotes) => {
+                    self.quotes = quotes;
+                    true
+                }
+                Err(e) => {
+                    log!(format!("Unable to load quotes: {e}"));
+                    false
+                }
+            },
+        }
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        if self.quotes.quotes.is_empty() {
+            return html! {};
+        }
+
+        // The quotes are rendered twice so the scrolling animation loops without a gap
+        let items = self.quotes.quotes.iter().map(quote_item).collect::<Html>();
+        html! {
+            <div class="ticker">
+                <div class="ticker-track">
+                    { items.clone() }
+                    { items }
+                </div>
+            </div>
+        }
+    }
+}
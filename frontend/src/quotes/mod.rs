@@ -0,0 +1,2 @@
+pub mod component;
+mod services;
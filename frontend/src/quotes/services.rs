@@ -0,0 +1,33 @@
+use std::time::Duration;
+use wasm_bindgen_futures::spawn_local;
+use yew::platform::time::sleep;
+use yew::Callback;
+
+use crate::glue::get_quotes;
+use homedisplay::models::quotes::QuoteTable;
+
+const QUOTES_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+pub fn refresh_quotes(callback: Callback<Result<QuoteTable, String>>) {
+    spawn_local(async move {
+        match get_quotes().await {
+            Ok(response) => {
+                let quotes: Result<QuoteTable, String> =
+                    serde_wasm_bindgen::from_value(response).map_err(|e| e.to_string());
+                callback.emit(quotes);
+            }
+            Err(e) => {
+                callback.emit(serde_wasm_bindgen::from_value(e).map_err(|e| e.to_string()));
+            }
+        }
+    });
+}
+
+pub fn start_quotes_job(callback: Callback<Result<QuoteTable, String>>) {
+    spawn_local(async move {
+        loop {
+            refresh_quotes(callback.clone());
+            sleep(QUOTES_REFRESH_INTERVAL).await;
+        }
+    });
+}
@@ -1,2 +1,3 @@
+pub mod routes;
 pub mod timing;
 pub mod transport;
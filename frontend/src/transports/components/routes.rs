@@ -0,0 +1,55 @@
+use chrono::NaiveDateTime;
+use homedisplay::models::transports::{Itinerary, RoutePlan};
+use yew::{html, Component, Context, Html, Properties};
+
+pub struct Routes;
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub plans: Vec<RoutePlan>,
+    pub now: NaiveDateTime, // Stockholm time the departed itineraries are hidden against
+}
+
+/// Renders an itinerary with its times and transfers, and its legs below
+fn itinerary_row(itinerary: &Itinerary) -> Html {
+    html! {
+        <div class="itinerary">
+            <div>{ itinerary.summary() }</div>
+            <div class="itinerary-legs">
+                { itinerary.legs.iter().map(|leg| html! {
+                    <span class={if leg.line.is_some() { "itinerary-leg" } else { "itinerary-leg itinerary-walk" }}
+                        title={format!("{} {} → {} {}", leg.departure.format("%H:%M"), leg.origin, leg.arrival.format("%H:%M"), leg.destination)}>
+                        { leg.describe() }
+                    </span>
+                }).collect::<Html>() }
+            </div>
+        </div>
+    }
+}
+
+impl Component for Routes {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Routes
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, _msg: Self::Message) -> bool {
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let now = ctx.props().now;
+        html! {
+            <div class="routes">
+                { ctx.props().plans.iter().map(|plan| html! {
+                    <div>
+                        <div class="route-headline">{ plan.headline(now) }</div>
+                        { plan.upcoming(now).into_iter().map(itinerary_row).collect::<Html>() }
+                    </div>
+                }).collect::<Html>() }
+            </div>
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use homedisplay::models::transports::Departure;
+use chrono::NaiveDateTime;
+use homedisplay::models::transports::{leave_headline, next_catchable, Departure, Reachability};
 use yew::{html, Component, Context, Html, Properties};
 
 pub struct Timing;
@@ -6,17 +7,57 @@ pub struct Timing;
 #[derive(Properties)]
 pub struct Props {
     pub departures: Vec<Departure>,
+    pub now: NaiveDateTime, // Stockholm time the countdowns are computed against
 }
 
 // Implement partial equality for Props
 impl PartialEq for Props {
     fn eq(&self, other: &Self) -> bool {
-        self.departures.len() == other.departures.len()
+        self.now == other.now
+            && self.departures.len() == other.departures.len()
             && self
                 .departures
                 .iter()
                 .zip(other.departures.iter())
-                .all(|(a, b)| a.display == b.display)
+                .all(|(a, b)| {
+                    a.display == b.display
+                        && a.expected == b.expected
+                        && a.is_cancelled() == b.is_cancelled()
+                })
+    }
+}
+
+/// Renders a departure with its line, destination, platform and countdown, highlighting
+/// its delay or cancellation, and whether one has to run to catch it
+fn departure_row(departure: &Departure, now: NaiveDateTime) -> Html {
+    let dep_display = format!(
+        "{} - {} - {}",
+        departure.line.name(),
+        departure.destination,
+        departure.countdown(now)
+    );
+    let platform = departure
+        .platform()
+        .map(|platform| html! { <span class="departure-platform">{ platform }</span> });
+    let status = if departure.is_cancelled() {
+        html! { <span class="departure-cancelled-text">{ "cancelled" }</span> }
+    } else if let Some(delay) = departure.delay_minutes().filter(|delay| *delay > 0) {
+        html! { <span class="departure-delay">{ format!("+{delay}'") }</span> }
+    } else {
+        html! {}
+    };
+    let run = (departure.reachability(now) == Some(Reachability::Run))
+        .then(|| html! { <span class="departure-run">{ "run!" }</span> });
+
+    html! {
+        <div>
+            <span class={if departure.is_cancelled() { "departure-cancelled" } else { "" }}>
+                { dep_display }
+            </span>
+            { platform.unwrap_or_default() }
+            { status }
+            { run.unwrap_or_default() }
+        </div>
     }
 }
 
@@ -33,31 +74,41 @@ impl Component for Timing {
     }
 
     fn view(&self, ctx: &Context<Self>) -> yew::Html {
+        let now = ctx.props().now;
+        let upcoming: Vec<&Departure> = ctx
+            .props()
+            .departures
+            .iter()
+            .filter(|departure| {
+                !departure.has_departed(now)
+                    && departure.reachability(now) != Some(Reachability::Missed)
+            })
+            .collect();
+        let headline = next_catchable(upcoming.iter().copied(), now)
+            .and_then(|departure| leave_headline(departure, now))
+            .map(|headline| html! { <div class="leave-headline">{ headline }</div> });
+
         let mut transport_modes: Vec<String> = Vec::new();
-        for departure in &ctx.props().departures {
+        for departure in &upcoming {
             if !transport_modes.contains(&departure.line.transport_mode) {
                 transport_modes.push(departure.line.transport_mode.clone());
             }
         }
 
         html! {
+            <>
+            { headline.unwrap_or_default() }
             { transport_modes.iter().map(|mode| {
-                let departures = &ctx.props().departures.iter().filter(|dep| &dep.line.transport_mode == mode).collect::<Vec<&Departure>>();
+                let departures = upcoming.iter().filter(|dep| &dep.line.transport_mode == mode).collect::<Vec<_>>();
 
                 html! {
                     <div>
                         <h4>{ mode }</h4>
-                        { departures.iter().map(|departure| {
-                            let dep_display = format!("{} - {} - {}", departure.line.id, departure.destination, departure.display);
-                            html! {
-                                <div>
-                                    { dep_display }
-                                </div>
-                            }
-                        }).collect::<Html>() }
+                        { departures.iter().map(|departure| departure_row(departure, now)).collect::<Html>() }
                     </div>
                 }
             }).collect::<Html>() }
+            </>
         }
     }
 }
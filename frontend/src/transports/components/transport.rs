@@ -1,20 +1,30 @@
-use chrono::prelude::Local;
+use chrono::prelude::{Local, NaiveDateTime};
 use futures::stream::StreamExt;
 use gloo_console::log;
-use homedisplay::models::transports::{Departure, Site};
+use homedisplay::models::transports::{
+    stockholm_now, Departure, RoutePlan, Site, TrafficDeviation, DEPARTURES_STALE_AFTER,
+};
 use std::collections::HashMap;
 use yew::{html, Component, Context, Html, Properties};
 
+use crate::transports::components::routes::Routes;
 use crate::transports::components::timing::Timing;
 
-use super::super::services::{fetch_departures, fetch_sites, stream_time};
+use super::super::services::{
+    fetch_departures, fetch_deviations, fetch_routes, fetch_sites, stream_time,
+};
 
 pub struct TransportsComponent {
     sites: Vec<Site>,
     departures: HashMap<String, Vec<Departure>>,
     site_errors: HashMap<String, String>,
+    deviations: Vec<TrafficDeviation>,
+    routes: Vec<RoutePlan>,
+    route_error: Option<String>,
     last_update: i64,
     time_since_last_update: i64,
+    last_received: i64, // Last time departures were received
+    now: NaiveDateTime, // Stockholm time the countdowns are computed against
     loading: bool,
     refreshing: bool,
     error: Option<String>,
@@ -27,6 +37,8 @@ pub enum Msg {
     SitesDataReceived(Result<Vec<Site>, String>),
     LoadDepartures(String),
     DeparturesDataReceived(Result<(String, Vec<Departure>), (String, String)>),
+    DeviationsDataReceived(Result<Vec<TrafficDeviation>, String>),
+    RoutesDataReceived(Result<Vec<RoutePlan>, String>),
 }
 
 #[derive(Properties, PartialEq)]
@@ -50,8 +62,13 @@ impl Component for TransportsComponent {
             sites: Vec::new(),
             departures: HashMap::new(),
             site_errors: HashMap::new(),
+            deviations: Vec::new(),
+            routes: Vec::new(),
+            route_error: None,
             last_update: Local::now().timestamp(),
             time_since_last_update: 0,
+            last_received: Local::now().timestamp(),
+            now: stockholm_now(),
             loading: true,
             refreshing: false,
             error: None,
@@ -67,6 +84,7 @@ impl Component for TransportsComponent {
             Msg::ClockUpdate => {
                 // Update timestamps
                 let now = Local::now().timestamp();
+                self.now = stockholm_now();
                 self.time_since_last_update = now - self.last_update;
                 if self.time_since_last_update > 60 {
                     ctx.link().send_message(Msg::LoadAllDepartures);
@@ -112,6 +130,7 @@ impl Component for TransportsComponent {
                             log!("Site {} is now working again", &site_id);
                         }
                         self.departures.insert(site_id, departures);
+                        self.last_received = Local::now().timestamp();
                     }
                     Err((site_id, error)) => {
                         log!("Error loading departures for site {}: {}", &site_id, &error);
@@ -121,12 +140,37 @@ impl Component for TransportsComponent {
                 self.refreshing = false;
                 true
             }
+            Msg::DeviationsDataReceived(result) => match result {
+                Ok(deviations) => {
+                    self.deviations = deviations;
+                    true
+                }
+                Err(e) => {
+                    log!("Error loading deviations: {}", &e);
+                    false
+                }
+            },
+            Msg::RoutesDataReceived(result) => {
+                match result {
+                    Ok(routes) => {
+                        self.routes = routes;
+                        self.route_error = None;
+                    }
+                    Err(e) => {
+                        log!("Error loading routes: {}", &e);
+                        self.route_error = Some(e);
+                    }
+                }
+                true
+            }
             Msg::LoadAllDepartures => {
                 // Load all departures
                 for site in &self.sites {
                     ctx.link()
                         .send_message(Msg::LoadDepartures(site.id.clone()));
                 }
+                fetch_deviations(ctx.link().callback(Msg::DeviationsDataReceived));
+                fetch_routes(ctx.link().callback(Msg::RoutesDataReceived));
                 self.last_update = Local::now().timestamp();
                 true
             }
@@ -151,7 +195,10 @@ impl Component for TransportsComponent {
                 </div>
             }
         } else {
-            let last_update = if self.refreshing {
+            let since_received = Local::now().timestamp() - self.last_received;
+            let last_update = if since_received > DEPARTURES_STALE_AFTER as i64 {
+                format!("stale, last received {} seconds ago.", since_received)
+            } else if self.refreshing {
                 format!(
                     "{} seconds ago. (refreshing...)",
                     self.time_since_last_update
@@ -162,6 +209,19 @@ impl Component for TransportsComponent {
 
             html! {
                 <div class="panel panel-div">
+                    if !self.deviations.is_empty() {
+                        <div class="deviation-banner">
+                            { self.deviations.iter().map(|deviation| html! {
+                                <p title={deviation.details.clone()}>{ format!("⚠ {}", deviation.summary()) }</p>
+                            }).collect::<Html>() }
+                        </div>
+                    }
+                    if let Some(error) = &self.route_error {
+                        <div style="color: red">{ error }</div>
+                    }
+                    if !self.routes.is_empty() {
+                        <Routes plans={self.routes.clone()} now={self.now} />
+                    }
                     { self.sites.iter().map(|site| {
                         let site_name = format!("🚂 {}", &site.name);
                         if let Some(error) = self.site_errors.get(&site.id) {
@@ -175,7 +235,7 @@ impl Component for TransportsComponent {
                             html! {
                                 <div>
                                     <h3>{ site_name }</h3>
-                                    <Timing departures={departures.clone()} />
+                                    <Timing departures={departures.clone()} now={self.now} />
                                 </div>
                             }
                         } else {
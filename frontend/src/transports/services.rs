@@ -1,12 +1,12 @@
 use chrono::{DateTime, Local};
 use futures::stream::{Stream, StreamExt};
-use homedisplay::models::transports::{Departure, Site};
+use homedisplay::models::transports::{Departure, RoutePlan, Site, TrafficDeviation};
 use std::time::Duration;
 use wasm_bindgen_futures::spawn_local;
 use yew::platform::time::interval;
 use yew::Callback;
 
-use crate::glue::{get_departures, get_sites};
+use crate::glue::{get_departures, get_deviations, get_routes, get_sites};
 
 const ONE_SEC: Duration = Duration::from_secs(1);
 
@@ -46,6 +46,36 @@ pub fn fetch_departures(
     })
 }
 
+pub fn fetch_deviations(callback: Callback<Result<Vec<TrafficDeviation>, String>>) {
+    spawn_local(async move {
+        match get_deviations().await {
+            Ok(response) => {
+                let deviations: Result<Vec<TrafficDeviation>, String> =
+                    serde_wasm_bindgen::from_value(response).map_err(|e| e.to_string());
+                callback.emit(deviations);
+            }
+            Err(e) => {
+                callback.emit(serde_wasm_bindgen::from_value(e).map_err(|e| e.to_string()));
+            }
+        }
+    })
+}
+
+pub fn fetch_routes(callback: Callback<Result<Vec<RoutePlan>, String>>) {
+    spawn_local(async move {
+        match get_routes().await {
+            Ok(response) => {
+                let routes: Result<Vec<RoutePlan>, String> =
+                    serde_wasm_bindgen::from_value(response).map_err(|e| e.to_string());
+                callback.emit(routes);
+            }
+            Err(e) => {
+                callback.emit(serde_wasm_bindgen::from_value(e).map_err(|e| e.to_string()));
+            }
+        }
+    })
+}
+
 /// Returns a stream that emits the current time every second
 pub fn stream_time() -> impl Stream<Item = DateTime<Local>> {
     interval(ONE_SEC).map(|_| Local::now())
@@ -0,0 +1,13 @@
+/// Prints the JSON Schema of the settings, published as `settings.schema.json` at the root of
+/// the repository for the editors completing and checking the settings files.
+///
+/// cargo run -p homedisplay --features schema --example settings_schema > settings.schema.json
+use homedisplay::settings::Settings;
+
+fn main() {
+    let schema = schemars::schema_for!(Settings);
+    match serde_json::to_string_pretty(&schema) {
+        Ok(schema) => println!("{schema}"),
+        Err(e) => eprintln!("Unable to serialize the schema: {e}"),
+    }
+}
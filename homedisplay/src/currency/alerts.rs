@@ -0,0 +1,306 @@
+/// Evaluates the currency alert rules from the settings against the latest rates,
+/// and notifies the configured webhook when new alerts are triggered.
+use log::{error, info, warn};
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::database;
+use crate::models::currency::{AlertKind, RateHistory, RateTable, TriggeredAlert};
+use crate::settings;
+
+use super::database::{fetch_rate_history, fetch_rate_table};
+
+#[derive(Serialize, Deserialize, Default)]
+struct AlertsDatabase {
+    alerts: Vec<TriggeredAlert>,
+    freshness: u64,
+}
+
+/// Returns the alerts triggered by the rules, given the latest rate table and the rate history
+pub fn evaluate(
+    rules: &[settings::CurrencyAlert],
+    rates: &RateTable,
+    history: &RateHistory,
+) -> Vec<TriggeredAlert> {
+    let mut alerts: Vec<TriggeredAlert> = vec![];
+
+    for rule in rules {
+        let rate = match rates.convert(&rule.from, &rule.to) {
+            Ok(conversion) => conversion.to_currency_amount,
+            Err(e) => {
+                warn!("Unable to evaluate alert on {}/{}: {e}", rule.from, rule.to);
+                continue;
+            }
+        };
+        let alert = |kind: AlertKind, message: String| TriggeredAlert {
+            from_currency: rule.from.clone(),
+            to_currency: rule.to.clone(),
+            kind,
+            rate,
+            message,
+        };
+
+        if let Some(above) = rule.above.filter(|above| rate >= *above) {
+            alerts.push(alert(
+                AlertKind::Above(above),
+                format!("{}/{} is above {above:.4} ({rate:.4})", rule.from, rule.to),
+            ));
+        }
+        if let Some(below) = rule.below.filter(|below| rate <= *below) {
+            alerts.push(alert(
+                AlertKind::Below(below),
+                format!("{}/{} is below {below:.4} ({rate:.4})", rule.from, rule.to),
+            ));
+        }
+        if let Some(threshold) = rule.daily_change {
+            let change = history.conversion(&rule.from, &rule.to).change(1);
+            if let Some(change) = change.filter(|change| change.abs() >= threshold) {
+                alerts.push(alert(
+                    AlertKind::DailyChange(threshold),
+                    format!(
+                        "{}/{} moved {change:+.2}% since yesterday ({rate:.4})",
+                        rule.from, rule.to
+                    ),
+                ));
+            }
+        }
+    }
+
+    alerts
+}
+
+/// Returns the alerts that were not triggered during the previous evaluation. An alert
+/// staying triggered is only notified once, it is notified again once its rate went back
+/// within the threshold and crossed it anew.
+fn new_alerts(alerts: &[TriggeredAlert], previous: &[TriggeredAlert]) -> Vec<TriggeredAlert> {
+    alerts
+        .iter()
+        .filter(|alert| !previous.iter().any(|previous| previous.same_rule(alert)))
+        .cloned()
+        .collect()
+}
+
+/// Loads the alerts that were triggered and notified during the previous evaluation
+async fn load_alerts(redis_data: &settings::Redis) -> AlertsDatabase {
+    match database::get_redis_key("homedisplay:currency:alerts".to_string(), redis_data).await {
+        Ok(serialized) => serde_json::from_str(serialized.as_str()).unwrap_or_else(|error| {
+            warn!("Could not deserialize the currency alerts: {error}");
+            AlertsDatabase::default()
+        }),
+        Err(_) => AlertsDatabase::default(),
+    }
+}
+
+/// Saves the triggered alerts that were notified in redis
+fn store_alerts(alerts: &[TriggeredAlert], redis_data: &settings::Redis) -> Result<(), String> {
+    let alerts = AlertsDatabase {
+        alerts: alerts.to_vec(),
+        freshness: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+
+    let serialized_alerts: String = match serde_json::to_string(&alerts) {
+        Ok(serialized) => serialized,
+        Err(error) => {
+            return Err(format!(
+                "An error occured while serializing the data: {error}",
+            ))
+        }
+    };
+
+    let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
+
+    match con.set::<String, String, redis::Value>(
+        "homedisplay:currency:alerts".to_string(),
+        serialized_alerts,
+    ) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!(
+            "Could not save serialized data into redis: {error}",
+        )),
+    }
+}
+
+#[derive(Serialize)]
+/// The body POSTed to the webhook
+struct WebhookPayload<'a> {
+    alerts: &'a [TriggeredAlert],
+}
+
+/// POSTs the given alerts to the webhook as JSON
+async fn notify_webhook(url: &str, alerts: &[TriggeredAlert]) -> Result<(), String> {
+    let result = reqwest::Client::new()
+        .post(url)
+        .json(&WebhookPayload { alerts })
+        .send()
+        .await
+        .map_err(|error| format!("Unable to reach the alert webhook: {error}"))?;
+
+    if result.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("Alert webhook answered with {}", result.status()))
+    }
+}
+
+/// Evaluates the alert rules against the current rates and returns the triggered alerts.
+/// Alerts that were not triggered during the previous evaluation are sent to the webhook,
+/// and sent again on the next evaluation if the webhook could not be notified.
+pub async fn check_alerts(
+    currency_settings: settings::Currency,
+    redis_data: &settings::Redis,
+) -> Result<Vec<TriggeredAlert>, String> {
+    if currency_settings.alerts.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let rates = fetch_rate_table(currency_settings.clone(), redis_data).await?;
+    let history = fetch_rate_history(currency_settings.clone(), redis_data).await?;
+    let alerts = evaluate(&currency_settings.alerts, &rates, &history);
+
+    let previous = load_alerts(redis_data).await;
+    let new_alerts = new_alerts(&alerts, &previous.alerts);
+
+    let mut notified = alerts.clone();
+    if !new_alerts.is_empty() {
+        info!("{} new currency alerts triggered", new_alerts.len());
+        if let Some(url) = &currency_settings.webhook {
+            if let Err(e) = notify_webhook(url, &new_alerts).await {
+                error!("Could not notify the alert webhook, retrying on the next check: {e}");
+                // The new alerts are left out of the stored ones, so that they are new
+                // again on the next check
+                notified.retain(|alert| {
+                    !new_alerts
+                        .iter()
+                        .any(|new_alert| new_alert.same_rule(alert))
+                });
+            }
+        }
+    }
+
+    store_alerts(&notified, redis_data)?;
+    Ok(alerts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+
+    /// A rate table where one EUR is worth the given amount of SEK
+    fn rates(eur_sek: f32) -> RateTable {
+        RateTable {
+            base: "EUR".to_string(),
+            timestamp: 0,
+            rates: HashMap::from([("SEK".to_string(), eur_sek)]),
+        }
+    }
+
+    /// A history of the EUR/SEK rate over consecutive days
+    fn history(eur_sek: &[f32]) -> RateHistory {
+        let mut history = RateHistory::default();
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        for (day, rate) in eur_sek.iter().enumerate() {
+            history.insert(start + chrono::Days::new(day as u64), rates(*rate));
+        }
+        history
+    }
+
+    fn rule(
+        above: Option<f32>,
+        below: Option<f32>,
+        daily_change: Option<f32>,
+    ) -> settings::CurrencyAlert {
+        settings::CurrencyAlert {
+            from: "EUR".to_string(),
+            to: "SEK".to_string(),
+            above,
+            below,
+            daily_change,
+        }
+    }
+
+    fn kinds(alerts: &[TriggeredAlert]) -> Vec<AlertKind> {
+        alerts.iter().map(|alert| alert.kind.clone()).collect()
+    }
+
+    #[test]
+    fn triggers_when_the_rate_crosses_a_threshold() {
+        let rules = [rule(Some(11.5), Some(11.0), None)];
+
+        let above = evaluate(&rules, &rates(11.6), &RateHistory::default());
+        assert_eq!(kinds(&above), vec![AlertKind::Above(11.5)]);
+        assert_eq!(above[0].rate, 11.6);
+        assert_eq!(above[0].message, "EUR/SEK is above 11.5000 (11.6000)");
+
+        let below = evaluate(&rules, &rates(10.9), &RateHistory::default());
+        assert_eq!(kinds(&below), vec![AlertKind::Below(11.0)]);
+
+        // The thresholds themselves trigger
+        let at = evaluate(&rules, &rates(11.5), &RateHistory::default());
+        assert_eq!(kinds(&at), vec![AlertKind::Above(11.5)]);
+    }
+
+    #[test]
+    fn stays_quiet_within_the_band() {
+        let rules = [rule(Some(11.5), Some(11.0), Some(2.0))];
+        let history = history(&[11.2, 11.3]);
+
+        assert!(evaluate(&rules, &rates(11.3), &history).is_empty());
+        // Without any threshold, a rule never triggers
+        assert!(evaluate(&[rule(None, None, None)], &rates(11.3), &history).is_empty());
+    }
+
+    #[test]
+    fn triggers_on_large_daily_changes_in_either_direction() {
+        let rules = [rule(None, None, Some(2.0))];
+
+        let up = evaluate(&rules, &rates(11.5), &history(&[11.0, 11.5]));
+        assert_eq!(kinds(&up), vec![AlertKind::DailyChange(2.0)]);
+        assert!(up[0].message.contains("+4.55%"), "{}", up[0].message);
+
+        let down = evaluate(&rules, &rates(10.5), &history(&[11.0, 10.5]));
+        assert_eq!(kinds(&down), vec![AlertKind::DailyChange(2.0)]);
+
+        // A single day of history has no change
+        assert!(evaluate(&rules, &rates(11.5), &history(&[11.5])).is_empty());
+    }
+
+    #[test]
+    fn skips_the_rules_on_unknown_currencies() {
+        let rules = [settings::CurrencyAlert {
+            to: "XYZ".to_string(),
+            ..rule(Some(0.0), None, None)
+        }];
+        assert!(evaluate(&rules, &rates(11.3), &RateHistory::default()).is_empty());
+    }
+
+    #[test]
+    fn notifies_an_alert_again_only_after_it_cleared() {
+        let rules = [rule(Some(11.5), None, None)];
+        let history = RateHistory::default();
+
+        // Crossing: notified
+        let first = evaluate(&rules, &rates(11.6), &history);
+        assert_eq!(new_alerts(&first, &[]).len(), 1);
+
+        // Still above, at another rate: not notified again
+        let second = evaluate(&rules, &rates(11.7), &history);
+        assert!(new_alerts(&second, &first).is_empty());
+
+        // Back within the threshold: nothing to notify, the alert clears
+        let third = evaluate(&rules, &rates(11.4), &history);
+        assert!(new_alerts(&third, &second).is_empty());
+
+        // Crossing again: notified again
+        let fourth = evaluate(&rules, &rates(11.6), &history);
+        assert_eq!(
+            kinds(&new_alerts(&fourth, &third)),
+            vec![AlertKind::Above(11.5)]
+        );
+    }
+}
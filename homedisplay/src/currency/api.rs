@@ -1,81 +1,167 @@
 use async_trait::async_trait;
+use chrono::NaiveDate;
 use reqwest::Url;
 use serde_derive::Deserialize;
 use std::collections::HashMap;
 
-use crate::models::currency::Conversion;
+use crate::models::currency::{CurrencyList, RateHistory, RateTable};
 use crate::traits::Api;
 
-use crate::settings::Currency as CurrencySettings;
+use crate::settings::{Currency as CurrencySettings, CurrencyProvider};
+
+use super::{ecb, riksbank};
+
+fn default_base() -> String {
+    "USD".to_string()
+}
 
 #[derive(Deserialize, Debug)]
 /// An API response from Open Exchange Rates
 struct APIResponse {
+    #[serde(default = "default_base")]
+    pub base: String,
     pub timestamp: i64,
     pub rates: HashMap<String, f32>,
 }
 
-trait ConversionFromAPI {
-    fn from_base(
-        api_response: APIResponse,
-        from_currency: String,
-        to_currency: String,
-    ) -> Conversion;
+impl From<APIResponse> for RateTable {
+    fn from(data: APIResponse) -> RateTable {
+        RateTable {
+            base: data.base,
+            timestamp: data.timestamp,
+            rates: data.rates,
+        }
+    }
 }
 
-impl ConversionFromAPI for Conversion {
-    /// Convert an API response from Open Exchange Rates to a Conversion structure.
-    fn from_base(data: APIResponse, from_currency: String, to_currency: String) -> Conversion {
-        let from: f32 = match data.rates.get(&from_currency) {
-            Some(value) => *value,
-            None => 0.0,
-        };
+/// Fetches the latest rate table from Open Exchange Rates.
+async fn fetch_open_exchange_rates(api_key: &str) -> Result<RateTable, String> {
+    let url: Url = Url::parse(
+        format!("https://openexchangerates.org/api/latest.json?app_id={api_key}").as_str(),
+    )
+    .map_err(|err| format!("Could not parse URL: {err}"))?;
 
-        let to: f32 = match data.rates.get(&to_currency) {
-            Some(value) => *value,
-            None => 0.0,
-        };
+    let result = reqwest::get(url)
+        .await
+        .map_err(|error| format!("Error while fetching OpenExchangeRates API: {error}",))?;
 
-        Conversion {
-            from_currency,
-            from_currency_amount: 1.0,
-            to_currency,
-            to_currency_amount: (to / from),
-            timestamp: data.timestamp,
+    match result.status() {
+        reqwest::StatusCode::OK => match result.json::<APIResponse>().await {
+            Ok(data) => Ok(data.into()),
+            Err(err) => Err(format!("Error while converting rates data: {err}",)),
+        },
+        reqwest::StatusCode::UNAUTHORIZED => Err("Openexchangerates token is invalid".to_string()),
+        _ => Err(format!("Unexpected error ({})", result.status())),
+    }
+}
+
+/// Fetches the rate table of the given day from Open Exchange Rates.
+async fn fetch_open_exchange_rates_historical(
+    api_key: &str,
+    date: NaiveDate,
+) -> Result<RateTable, String> {
+    let url: Url = Url::parse(
+        format!(
+            "https://openexchangerates.org/api/historical/{}.json?app_id={api_key}",
+            date.format("%Y-%m-%d")
+        )
+        .as_str(),
+    )
+    .map_err(|err| format!("Could not parse URL: {err}"))?;
+
+    let result = reqwest::get(url)
+        .await
+        .map_err(|error| format!("Error while fetching OpenExchangeRates API: {error}",))?;
+
+    match result.status() {
+        reqwest::StatusCode::OK => match result.json::<APIResponse>().await {
+            Ok(data) => Ok(data.into()),
+            Err(err) => Err(format!(
+                "Error while converting historical rates data: {err}",
+            )),
+        },
+        reqwest::StatusCode::UNAUTHORIZED => Err("Openexchangerates token is invalid".to_string()),
+        _ => Err(format!("Unexpected error ({})", result.status())),
+    }
+}
+
+#[async_trait]
+impl Api<CurrencySettings, RateTable> for RateTable {
+    /// Fetches the latest rate table from the configured provider.
+    async fn api_get(currency_settings: CurrencySettings) -> Result<RateTable, String> {
+        match currency_settings.provider {
+            CurrencyProvider::OpenExchangeRates => {
+                fetch_open_exchange_rates(currency_settings.api_key.expose()).await
+            }
+            CurrencyProvider::Ecb => ecb::fetch_rates().await,
+            CurrencyProvider::Riksbank => riksbank::fetch_rates().await,
         }
     }
 }
 
 #[async_trait]
-impl Api<CurrencySettings, Conversion> for Conversion {
-    /// Create a conversion structure based on the API response of Open Exchange Rates.
-    async fn api_get(currency_settings: CurrencySettings) -> Result<Conversion, String> {
-        let url: Url = Url::parse(
-            format!(
-                "https://openexchangerates.org/api/latest.json?app_id={}",
-                currency_settings.api_key
-            )
-            .as_str(),
-        )
-        .map_err(|err| format!("Could not parse URL: {err}"))?;
+impl Api<(), CurrencyList> for CurrencyList {
+    /// Fetches the list of currencies supported by Open Exchange Rates.
+    async fn api_get(_: ()) -> Result<CurrencyList, String> {
+        let url: Url = Url::parse("https://openexchangerates.org/api/currencies.json")
+            .map_err(|err| format!("Could not parse URL: {err}"))?;
 
         let result = reqwest::get(url)
             .await
             .map_err(|error| format!("Error while fetching OpenExchangeRates API: {error}",))?;
 
         match result.status() {
-            reqwest::StatusCode::OK => match result.json::<APIResponse>().await {
-                Ok(data) => Ok(Conversion::from_base(
-                    data,
-                    currency_settings.currency_from,
-                    currency_settings.currency_to,
-                )),
-                Err(err) => Err(format!("Error while converting Conversion data: {err}",)),
+            reqwest::StatusCode::OK => match result.json::<HashMap<String, String>>().await {
+                Ok(data) => Ok(CurrencyList(data)),
+                Err(err) => Err(format!("Error while converting currencies data: {err}",)),
             },
-            reqwest::StatusCode::UNAUTHORIZED => {
-                Err("Openexchangerates token is invalid".to_string())
-            }
             _ => Err(format!("Unexpected error ({})", result.status())),
         }
     }
 }
+
+#[async_trait]
+impl Api<(CurrencySettings, Vec<NaiveDate>), RateHistory> for RateHistory {
+    /// Fetches the rate tables of the given days from the configured provider. Providers
+    /// publishing their history as a whole may return more days than requested.
+    async fn api_get(
+        (currency_settings, dates): (CurrencySettings, Vec<NaiveDate>),
+    ) -> Result<RateHistory, String> {
+        let mut history = RateHistory::default();
+        let (Some(first), Some(last)) = (dates.iter().min(), dates.iter().max()) else {
+            return Ok(history);
+        };
+
+        let tables = match currency_settings.provider {
+            CurrencyProvider::OpenExchangeRates => {
+                // Stored on the requested day, as the timestamp of a historical table
+                // may fall on the day after
+                for date in dates.iter() {
+                    let table = fetch_open_exchange_rates_historical(
+                        currency_settings.api_key.expose(),
+                        *date,
+                    )
+                    .await?;
+                    history.insert(*date, table);
+                }
+                return Ok(history);
+            }
+            CurrencyProvider::Ecb => ecb::fetch_history().await?,
+            CurrencyProvider::Riksbank => {
+                let mut currencies: Vec<String> = currency_settings
+                    .pairs()
+                    .into_iter()
+                    .flat_map(|pair| [pair.from, pair.to])
+                    .collect();
+                currencies.sort();
+                currencies.dedup();
+                riksbank::fetch_history(&currencies, *first, *last).await?
+            }
+        };
+
+        for table in tables {
+            history.insert_table(table);
+        }
+        Ok(history)
+    }
+}
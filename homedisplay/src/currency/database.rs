@@ -1,3 +1,4 @@
+use chrono::{Days, NaiveDate, Utc};
 use log::{error, info, warn};
 use redis::Commands;
 use serde::{Deserialize, Serialize};
@@ -5,28 +6,58 @@ use serde_json;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::database;
-use crate::models::currency::Conversion;
+use crate::models::currency::{
+    Conversion, ConversionHistory, CurrencyList, RateHistory, RateTable,
+};
 use crate::traits::Api;
 
 use crate::settings;
 
 #[derive(Serialize, Deserialize)]
-struct ConversionDatabase {
-    conversion: Conversion,
+struct RateTableDatabase {
+    rates: RateTable,
     freshness: u64,
+    /// Configured currencies the table missed when it was fetched, unknown to the provider
+    #[serde(default)]
+    missing: Vec<String>,
 }
 
-/// Saves the conversion in redis, wrapping it in a struct that includes the freshness of the data
-fn store_conversion(conversion: &Conversion, redis_data: &settings::Redis) -> Result<(), String> {
-    let conversion: ConversionDatabase = ConversionDatabase {
-        conversion: conversion.clone(),
+#[derive(Serialize, Deserialize, Default)]
+struct RateHistoryDatabase {
+    history: RateHistory,
+    freshness: u64,
+    /// Time before which a failed refresh of the history is not attempted again
+    #[serde(default)]
+    retry_after: u64,
+}
+
+/// Delay before fetching the missing days of the rate history again after a failure
+const HISTORY_RETRY_DELAY: u64 = 6 * 3600;
+
+/// Most days of history fetched from Open Exchange Rates on each daily refresh, each day
+/// costing a request of the free tier quota
+const OER_HISTORY_DAYS_PER_REFRESH: usize = 7;
+
+#[derive(Serialize, Deserialize)]
+struct CurrencyListDatabase {
+    currencies: CurrencyList,
+    freshness: u64,
+}
+
+/// Saves the currency list in redis, wrapping it in a struct that includes the freshness of the data
+fn store_currency_list(
+    currencies: &CurrencyList,
+    redis_data: &settings::Redis,
+) -> Result<(), String> {
+    let currencies: CurrencyListDatabase = CurrencyListDatabase {
+        currencies: currencies.clone(),
         freshness: SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs(),
     };
 
-    let serialized_conversion: String = match serde_json::to_string(&conversion) {
+    let serialized_currencies: String = match serde_json::to_string(&currencies) {
         Ok(serialized) => serialized,
         Err(error) => {
             return Err(format!(
@@ -38,8 +69,8 @@ fn store_conversion(conversion: &Conversion, redis_data: &settings::Redis) -> Re
     let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
 
     match con.set::<String, String, redis::Value>(
-        "homedisplay:conversion".to_string(),
-        serialized_conversion,
+        "homedisplay:currencies".to_string(),
+        serialized_currencies,
     ) {
         Ok(_) => Ok(()),
         Err(error) => Err(format!(
@@ -48,18 +79,204 @@ fn store_conversion(conversion: &Conversion, redis_data: &settings::Redis) -> Re
     }
 }
 
-/// Fetches the current conversion from the database, if it is older than a day, data will be refreshed before being returned
-pub async fn fetch_current_conversion(
+/// Fetches the list of currencies known by Open Exchange Rates from the database, if it is
+/// older than 30 days, data will be refreshed before being returned
+async fn fetch_oer_currency_list(redis_data: &settings::Redis) -> Result<CurrencyList, String> {
+    if let Ok(serialized) =
+        database::get_redis_key("homedisplay:currencies".to_string(), redis_data).await
+    {
+        match serde_json::from_str::<CurrencyListDatabase>(serialized.as_str()) {
+            Ok(currencies)
+                if SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+                    - currencies.freshness
+                    <= 30 * 86400 =>
+            {
+                return Ok(currencies.currencies);
+            }
+            Ok(_) => info!("Currency list is older than 30 days, fetching new data"),
+            Err(error) => warn!("Could not deserialize the currency list: {error}"),
+        }
+    }
+
+    info!("Fetching currency list from API");
+    let currencies = CurrencyList::api_get(()).await?;
+    store_currency_list(&currencies, redis_data)?;
+    Ok(currencies)
+}
+
+/// Returns the list of currencies known by the configured provider. Providers without a
+/// currency list only know the currencies of their rate table.
+pub async fn fetch_currency_list(
+    currency_settings: &settings::Currency,
+    redis_data: &settings::Redis,
+) -> Result<CurrencyList, String> {
+    match currency_settings.provider {
+        settings::CurrencyProvider::OpenExchangeRates => fetch_oer_currency_list(redis_data).await,
+        _ => {
+            let rates = fetch_rate_table(currency_settings.clone(), redis_data).await?;
+            Ok(CurrencyList(
+                rates
+                    .rates
+                    .keys()
+                    .chain(std::iter::once(&rates.base))
+                    .map(|code| (code.clone(), code.clone()))
+                    .collect(),
+            ))
+        }
+    }
+}
+
+/// Returns one error message per currency code of the configured pairs that is
+/// unknown to the provider
+fn unknown_currencies(
+    currencies: &CurrencyList,
+    currency_settings: &settings::Currency,
+) -> Vec<String> {
+    let mut errors: Vec<String> = vec![];
+    for pair in currency_settings.pairs() {
+        for code in [&pair.from, &pair.to] {
+            if let Err(e) = currencies.validate(code) {
+                let message = e.to_string();
+                if !errors.contains(&message) {
+                    errors.push(message);
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Checks every currency code of the configured pairs against the provider's currency list.
+/// Returns one error message per unknown code.
+pub async fn validate_currencies(
+    currency_settings: &settings::Currency,
+    redis_data: &settings::Redis,
+) -> Result<(), Vec<String>> {
+    let currencies = fetch_currency_list(currency_settings, redis_data)
+        .await
+        .map_err(|e| vec![format!("Unable to validate currency codes: {e}")])?;
+
+    let errors = unknown_currencies(&currencies, currency_settings);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Returns the redis key holding the rate table of the given provider
+fn rate_table_key(provider: settings::CurrencyProvider) -> String {
+    format!("homedisplay:rates:{provider}")
+}
+
+/// Saves the rate table in redis, wrapping it in a struct that includes the freshness of the
+/// data and the configured currencies it misses
+fn store_rate_table(
+    rates: &RateTable,
+    missing: Vec<String>,
+    provider: settings::CurrencyProvider,
+    redis_data: &settings::Redis,
+) -> Result<(), String> {
+    let rates: RateTableDatabase = RateTableDatabase {
+        rates: rates.clone(),
+        freshness: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        missing,
+    };
+
+    let serialized_rates: String = match serde_json::to_string(&rates) {
+        Ok(serialized) => serialized,
+        Err(error) => {
+            return Err(format!(
+                "An error occured while serializing the data: {error}",
+            ))
+        }
+    };
+
+    let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
+
+    match con.set::<String, String, redis::Value>(rate_table_key(provider), serialized_rates) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!(
+            "Could not save serialized data into redis: {error}",
+        )),
+    }
+}
+
+/// Returns the currencies of the configured pairs missing from the rate table
+fn missing_currencies(rates: &RateTable, currency_settings: &settings::Currency) -> Vec<String> {
+    let mut missing: Vec<String> = vec![];
+    for pair in currency_settings.pairs() {
+        for code in [pair.from, pair.to] {
+            if !rates.contains(&code) && !missing.contains(&code) {
+                missing.push(code);
+            }
+        }
+    }
+    missing
+}
+
+/// Returns whether the rate table holds every currency used in the configured pairs. The
+/// currencies it already missed when it was fetched are unknown to the provider, fetching
+/// the table again would not find them either.
+fn covers_settings(
+    rates: &RateTable,
+    missing: &[String],
+    currency_settings: &settings::Currency,
+) -> bool {
+    missing_currencies(rates, currency_settings)
+        .iter()
+        .all(|code| missing.contains(code))
+}
+
+/// Fetches a new rate table from the API and stores it in the database
+async fn refresh_rate_table(
     currency_settings: settings::Currency,
     redis_data: &settings::Redis,
-) -> Result<Conversion, String> {
-    match database::get_redis_key("homedisplay:conversion".to_string(), redis_data).await {
-        Ok(serialized) => match serde_json::from_str(serialized.as_str()) {
-            Ok(conversion) => {
-                let ConversionDatabase {
-                    conversion,
-                    freshness,
-                } = conversion;
+) -> Result<RateTable, String> {
+    let provider = currency_settings.provider;
+    match RateTable::api_get(currency_settings.clone()).await {
+        Ok(rates) => {
+            info!("Storing rate table in database");
+            let missing = missing_currencies(&rates, &currency_settings);
+            if !missing.is_empty() {
+                warn!(
+                    "The {provider} rates do not include {}, they are reported as unknown until the next daily refresh",
+                    missing.join(", ")
+                );
+            }
+            store_rate_table(&rates, missing, provider, redis_data)?;
+            if let Err(e) = record_rate_table(&rates, provider, redis_data).await {
+                warn!("Could not add the rate table to the history: {e}");
+            }
+            Ok(rates)
+        }
+        Err(error) => {
+            error!("Could not fetch rate table from API: {}", error);
+            Err(error)
+        }
+    }
+}
+
+/// Fetches the current rate table from the database, if it is older than a day or misses
+/// one of the configured currencies, data will be refreshed before being returned. The
+/// currencies the provider does not publish are only looked for once a day.
+pub async fn fetch_rate_table(
+    currency_settings: settings::Currency,
+    redis_data: &settings::Redis,
+) -> Result<RateTable, String> {
+    match database::get_redis_key(rate_table_key(currency_settings.provider), redis_data).await {
+        Ok(serialized) => match serde_json::from_str::<RateTableDatabase>(serialized.as_str()) {
+            Ok(RateTableDatabase {
+                rates,
+                freshness,
+                missing,
+            }) => {
                 if SystemTime::now()
                     .duration_since(UNIX_EPOCH)
                     .unwrap()
@@ -68,39 +285,248 @@ pub async fn fetch_current_conversion(
                     > 86400
                 {
                     info!("Data is older than a day, fetching new data from API");
-                    match Conversion::api_get(currency_settings).await {
-                        Ok(conversion) => {
-                            store_conversion(&conversion, redis_data)?;
-                            Ok(conversion)
-                        }
-                        Err(error) => Err(error),
-                    }
+                    refresh_rate_table(currency_settings, redis_data).await
+                } else if !covers_settings(&rates, &missing, &currency_settings) {
+                    info!("Stored rates do not cover the configured currencies, fetching new data from API");
+                    refresh_rate_table(currency_settings, redis_data).await
                 } else {
                     info!("Data is fresh enough, returning data from redis");
-                    Ok(conversion)
+                    Ok(rates)
                 }
             }
             Err(error) => {
-                error!("Could not deserialize the conversion: {error}");
-                Err(format!(
-                    "An error occured while deserializing the conversion: {error}"
-                ))
+                error!("Could not deserialize the rate table: {error}. Fetching from API");
+                refresh_rate_table(currency_settings, redis_data).await
             }
         },
         Err(err) => {
-            warn!("Could not fetch conversion from redis: {}", err);
-            info!("Fetching conversion from API");
-            match Conversion::api_get(currency_settings).await {
-                Ok(conversion) => {
-                    info!("Storing conversion in database");
-                    store_conversion(&conversion, redis_data)?;
-                    Ok(conversion)
+            warn!("Could not fetch rate table from redis: {}", err);
+            info!("Fetching rate table from API");
+            refresh_rate_table(currency_settings, redis_data).await
+        }
+    }
+}
+
+/// Computes the conversion of every configured currency pair from the current rate table.
+/// Unknown currency codes are reported before any rate is fetched.
+pub async fn fetch_current_conversions(
+    currency_settings: settings::Currency,
+    redis_data: &settings::Redis,
+) -> Result<Vec<Conversion>, String> {
+    match fetch_currency_list(&currency_settings, redis_data).await {
+        Ok(currencies) => {
+            let errors = unknown_currencies(&currencies, &currency_settings);
+            if !errors.is_empty() {
+                return Err(errors.join("\n"));
+            }
+        }
+        Err(e) => warn!("Unable to validate currency codes: {e}"),
+    }
+
+    let pairs = currency_settings.pairs();
+    let rates = fetch_rate_table(currency_settings, redis_data).await?;
+
+    pairs
+        .iter()
+        .map(|pair| {
+            rates
+                .convert(&pair.from, &pair.to)
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+/// Returns the redis key holding the rate history of the given provider
+fn rate_history_key(provider: settings::CurrencyProvider) -> String {
+    format!("homedisplay:rates:{provider}:history")
+}
+
+/// Saves the rate history in redis, with the given freshness
+fn store_rate_history(
+    history: &RateHistoryDatabase,
+    provider: settings::CurrencyProvider,
+    redis_data: &settings::Redis,
+) -> Result<(), String> {
+    let serialized_history: String = match serde_json::to_string(&history) {
+        Ok(serialized) => serialized,
+        Err(error) => {
+            return Err(format!(
+                "An error occured while serializing the data: {error}",
+            ))
+        }
+    };
+
+    let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
+
+    match con.set::<String, String, redis::Value>(rate_history_key(provider), serialized_history) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!(
+            "Could not save serialized data into redis: {error}",
+        )),
+    }
+}
+
+/// Loads the rate history of the given provider from redis, or an empty history
+async fn load_rate_history(
+    provider: settings::CurrencyProvider,
+    redis_data: &settings::Redis,
+) -> RateHistoryDatabase {
+    match database::get_redis_key(rate_history_key(provider), redis_data).await {
+        Ok(serialized) => serde_json::from_str::<RateHistoryDatabase>(serialized.as_str())
+            .unwrap_or_else(|error| {
+                warn!("Could not deserialize the rate history: {error}");
+                RateHistoryDatabase::default()
+            }),
+        Err(err) => {
+            info!("No rate history in redis: {}", err);
+            RateHistoryDatabase::default()
+        }
+    }
+}
+
+/// Adds a freshly fetched rate table to the stored history of its provider
+async fn record_rate_table(
+    rates: &RateTable,
+    provider: settings::CurrencyProvider,
+    redis_data: &settings::Redis,
+) -> Result<(), String> {
+    let mut stored = load_rate_history(provider, redis_data).await;
+    stored.history.insert_table(rates.clone());
+    store_rate_history(&stored, provider, redis_data)
+}
+
+/// Fetches the daily rate history from the database. Once a day, the days missing from
+/// the configured history window are fetched from the provider and old days are dropped.
+/// Providers charging one request per day are queried day by day, each day being stored
+/// as soon as it is fetched, so that a failure does not lose the days already fetched.
+/// Open Exchange Rates only fetches the `OER_HISTORY_DAYS_PER_REFRESH` most recent missing
+/// days on each refresh, the window filling up over several days.
+/// After a failure, the missing days are fetched again once `HISTORY_RETRY_DELAY` is over.
+pub async fn fetch_rate_history(
+    currency_settings: settings::Currency,
+    redis_data: &settings::Redis,
+) -> Result<RateHistory, String> {
+    let provider = currency_settings.provider;
+    let mut stored = load_rate_history(provider, redis_data).await;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    if now - stored.freshness <= 86400 {
+        info!("Rate history is fresh enough, returning data from redis");
+        return Ok(stored.history);
+    }
+    if now < stored.retry_after {
+        info!("Rate history could not be refreshed recently, returning data from redis");
+        return Ok(stored.history);
+    }
+
+    let today = Utc::now().date_naive();
+    let start = today
+        .checked_sub_days(Days::new(currency_settings.history_days as u64))
+        .unwrap_or(today);
+    let missing: Vec<NaiveDate> = start
+        .iter_days()
+        .take_while(|date| *date < today)
+        .filter(|date| !stored.history.tables.contains_key(date))
+        .collect();
+    stored.history.prune_before(start);
+
+    if !missing.is_empty() {
+        info!("Fetching {} missing days of rate history", missing.len());
+        let batches: Vec<Vec<NaiveDate>> = match provider {
+            settings::CurrencyProvider::OpenExchangeRates => missing
+                .into_iter()
+                .rev()
+                .take(OER_HISTORY_DAYS_PER_REFRESH)
+                .map(|date| vec![date])
+                .collect(),
+            _ => vec![missing],
+        };
+
+        for dates in batches {
+            match RateHistory::api_get((currency_settings.clone(), dates)).await {
+                Ok(history) => {
+                    for (date, table) in history.tables.into_iter().filter(|(d, _)| *d >= start) {
+                        stored.history.insert(date, table);
+                    }
+                    store_rate_history(&stored, provider, redis_data)?;
                 }
-                Err(error) => {
-                    error!("Could not fetch conversion from API: {}", error);
-                    Err(error)
+                Err(e) => {
+                    warn!(
+                        "Could not fetch rate history from API, retrying in {} hours: {e}",
+                        HISTORY_RETRY_DELAY / 3600
+                    );
+                    stored.retry_after = now + HISTORY_RETRY_DELAY;
+                    store_rate_history(&stored, provider, redis_data)?;
+                    return Ok(stored.history);
                 }
             }
         }
     }
+
+    stored.freshness = now;
+    stored.retry_after = 0;
+    store_rate_history(&stored, provider, redis_data)?;
+    Ok(stored.history)
+}
+
+/// Computes the history of every configured currency pair from the stored rate history
+pub async fn fetch_conversion_histories(
+    currency_settings: settings::Currency,
+    redis_data: &settings::Redis,
+) -> Result<Vec<ConversionHistory>, String> {
+    let pairs = currency_settings.pairs();
+    let history = fetch_rate_history(currency_settings, redis_data).await?;
+
+    Ok(pairs
+        .iter()
+        .map(|pair| history.conversion(&pair.from, &pair.to))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn rates() -> RateTable {
+        RateTable {
+            base: "EUR".to_string(),
+            timestamp: 0,
+            rates: HashMap::from([("SEK".to_string(), 11.25), ("USD".to_string(), 1.08)]),
+        }
+    }
+
+    fn currency(pairs: &[(&str, &str)]) -> settings::Currency {
+        settings::Currency {
+            pairs: pairs
+                .iter()
+                .map(|(from, to)| settings::CurrencyPair {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                })
+                .collect(),
+            ..settings::Currency::default()
+        }
+    }
+
+    #[test]
+    fn lists_the_missing_currencies_once() {
+        let settings = currency(&[("SEK", "EUR"), ("SEK", "XYZ"), ("XYZ", "USD")]);
+        assert_eq!(missing_currencies(&rates(), &settings), vec!["XYZ"]);
+    }
+
+    #[test]
+    fn covers_the_currencies_known_to_be_missing() {
+        let settings = currency(&[("SEK", "EUR"), ("SEK", "XYZ")]);
+        // Missed since the table was fetched, e.g. after a change of the settings
+        assert!(!covers_settings(&rates(), &[], &settings));
+        // Already missing from the freshly fetched table
+        assert!(covers_settings(&rates(), &["XYZ".to_string()], &settings));
+
+        let settings = currency(&[("SEK", "USD")]);
+        assert!(covers_settings(&rates(), &[], &settings));
+    }
 }
@@ -0,0 +1,170 @@
+/// Fetches the daily reference rates published by the European Central Bank.
+/// The rates are published as an XML document, all relative to the euro.
+use chrono::NaiveDate;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+
+use crate::models::currency::RateTable;
+
+const ECB_DAILY_URL: &str = "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml";
+const ECB_HISTORY_URL: &str = "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-hist-90d.xml";
+
+/// Parses an ECB XML feed into one rate table per day. The rates are stored in nested
+/// `Cube` elements: `<Cube time="2024-01-31"><Cube currency="USD" rate="1.0837"/>...</Cube>`
+pub fn parse_tables(document: &str) -> Result<Vec<RateTable>, String> {
+    let mut reader = Reader::from_str(document);
+    let mut tables: Vec<RateTable> = vec![];
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) | Ok(Event::Empty(element))
+                if element.local_name().as_ref() == b"Cube" =>
+            {
+                let mut currency: Option<String> = None;
+                let mut rate: Option<f32> = None;
+                for attribute in element.attributes().flatten() {
+                    let value = attribute
+                        .unescape_value()
+                        .map_err(|e| format!("Invalid attribute in ECB rates: {e}"))?;
+                    match attribute.key.as_ref() {
+                        b"time" => {
+                            let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                                .map_err(|e| format!("Invalid date `{value}` in ECB rates: {e}"))?;
+                            tables.push(RateTable {
+                                base: "EUR".to_string(),
+                                timestamp: date
+                                    .and_hms_opt(0, 0, 0)
+                                    .map(|dt| dt.and_utc().timestamp())
+                                    .unwrap_or_default(),
+                                rates: HashMap::new(),
+                            });
+                        }
+                        b"currency" => currency = Some(value.to_string()),
+                        b"rate" => {
+                            rate =
+                                Some(value.parse::<f32>().map_err(|e| {
+                                    format!("Invalid rate `{value}` in ECB rates: {e}")
+                                })?)
+                        }
+                        _ => {}
+                    }
+                }
+                if let (Some(currency), Some(rate), Some(table)) =
+                    (currency, rate, tables.last_mut())
+                {
+                    table.rates.insert(currency, rate);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => return Err(format!("Unable to parse ECB rates: {e}")),
+        }
+    }
+
+    tables.retain(|table| !table.rates.is_empty());
+    if tables.is_empty() {
+        return Err("No rate could be found in the ECB document".to_string());
+    }
+    Ok(tables)
+}
+
+/// Parses the ECB daily XML feed, returning the most recent table of the document
+pub fn parse_rates(document: &str) -> Result<RateTable, String> {
+    parse_tables(document)?
+        .into_iter()
+        .max_by_key(|table| table.timestamp)
+        .ok_or("No rate could be found in the ECB document".to_string())
+}
+
+/// Fetches an ECB feed and returns its body
+async fn fetch_document(url: &str) -> Result<String, String> {
+    let result = reqwest::get(url)
+        .await
+        .map_err(|error| format!("Error while fetching ECB reference rates: {error}"))?;
+
+    match result.status() {
+        reqwest::StatusCode::OK => result
+            .text()
+            .await
+            .map_err(|error| format!("Error while reading ECB reference rates: {error}")),
+        status => Err(format!("Unexpected error ({status})")),
+    }
+}
+
+/// Fetches the reference rates of the last 90 days from the ECB, one table per day
+pub async fn fetch_history() -> Result<Vec<RateTable>, String> {
+    parse_tables(&fetch_document(ECB_HISTORY_URL).await?)
+}
+
+/// Fetches the latest reference rates from the ECB
+pub async fn fetch_rates() -> Result<RateTable, String> {
+    parse_rates(&fetch_document(ECB_DAILY_URL).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    const HISTORY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01" xmlns="http://www.ecb.int/vocabulary/2002-08-01/eurofxref">
+    <gesmes:subject>Reference rates</gesmes:subject>
+    <Cube>
+        <Cube time="2024-01-31">
+            <Cube currency="USD" rate="1.0837"/>
+            <Cube currency="SEK" rate="11.2645"/>
+        </Cube>
+        <Cube time="2024-01-30">
+            <Cube currency="USD" rate="1.0846"/>
+            <Cube currency="SEK" rate="11.3120"/>
+        </Cube>
+    </Cube>
+</gesmes:Envelope>"#;
+
+    #[test]
+    fn parses_one_table_per_day() {
+        let tables = parse_tables(HISTORY).unwrap();
+        assert_eq!(tables.len(), 2);
+        assert!(tables.iter().all(|table| table.base == "EUR"));
+        assert_eq!(tables[1].rates.get("SEK"), Some(&11.312));
+    }
+
+    #[test]
+    fn keeps_the_latest_table() {
+        let table = parse_rates(HISTORY).unwrap();
+        let date = DateTime::from_timestamp(table.timestamp, 0).unwrap();
+        assert_eq!(
+            date.date_naive(),
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+        );
+        assert_eq!(table.rates.get("USD"), Some(&1.0837));
+    }
+
+    #[test]
+    fn converts_through_the_euro() {
+        let table = parse_rates(HISTORY).unwrap();
+
+        let eur_sek = table.convert("EUR", "SEK").unwrap();
+        assert!((eur_sek.to_currency_amount - 11.2645).abs() < 1e-4);
+
+        let sek_eur = table.convert("SEK", "EUR").unwrap();
+        assert!((sek_eur.to_currency_amount - 1.0 / 11.2645).abs() < 1e-6);
+
+        let usd_sek = table.convert("USD", "SEK").unwrap();
+        assert!((usd_sek.to_currency_amount - 11.2645 / 1.0837).abs() < 1e-4);
+    }
+
+    #[test]
+    fn reports_missing_currencies() {
+        let table = parse_rates(HISTORY).unwrap();
+        assert!(!table.contains("JPY"));
+        assert!(table.convert("EUR", "JPY").is_err());
+    }
+
+    #[test]
+    fn rejects_a_document_without_rates() {
+        assert!(parse_tables("<Cube><Cube time=\"2024-01-31\"></Cube></Cube>").is_err());
+        assert!(parse_tables("<Cube><Cube currency=\"USD\" rate=\"abc\"/></Cube>").is_err());
+    }
+}
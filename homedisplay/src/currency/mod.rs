@@ -1,2 +1,5 @@
+pub mod alerts;
 pub mod api;
 pub mod database;
+pub mod ecb;
+pub mod riksbank;
@@ -0,0 +1,307 @@
+/// Fetches the exchange rates published by the Riksbank through its SWEA API.
+/// The rates are expressed in Swedish kronor per unit of foreign currency, some series
+/// (e.g. JPY) being quoted for 100 units as given by the description of the series.
+use chrono::NaiveDate;
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+
+use crate::models::currency::RateTable;
+
+/// Latest observations of the "Currencies against Swedish kronor" group
+const RIKSBANK_LATEST_URL: &str = "https://api.riksbank.se/swea/v1/Observations/Latest/ByGroup/130";
+const RIKSBANK_OBSERVATIONS_URL: &str = "https://api.riksbank.se/swea/v1/Observations";
+const RIKSBANK_SERIES_URL: &str = "https://api.riksbank.se/swea/v1/Series";
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A single observation of a SWEA series, e.g. `SEKEURPMI`
+struct Observation {
+    pub series_id: String,
+    pub date: String,
+    pub value: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+/// A single observation of a known SWEA series
+struct SeriesObservation {
+    pub date: String,
+    pub value: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// The description of a SWEA series, e.g. `100 JPY` for `SEKJPYPMI`
+struct Series {
+    pub series_id: String,
+    #[serde(default)]
+    pub short_description: Option<String>,
+    #[serde(default)]
+    pub mid_description: Option<String>,
+    #[serde(default)]
+    pub long_description: Option<String>,
+}
+
+impl Series {
+    /// Returns the number of units of currency the series is quoted for, 1 unless its
+    /// description states otherwise (e.g. `100 JPY`)
+    fn unit(&self) -> f32 {
+        [
+            &self.short_description,
+            &self.mid_description,
+            &self.long_description,
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|description| description.split_whitespace())
+        .find_map(|word| {
+            word.parse::<u32>()
+                .ok()
+                .filter(|unit| matches!(unit, 10 | 100 | 1000 | 10000))
+        })
+        .map_or(1.0, |unit| unit as f32)
+    }
+}
+
+/// Returns the units the series are quoted for, by series id
+fn to_units(series: Vec<Series>) -> HashMap<String, f32> {
+    series
+        .into_iter()
+        .map(|series| (series.series_id.clone(), series.unit()))
+        .collect()
+}
+
+/// Fetches the units the SWEA series are quoted for
+async fn fetch_units() -> Result<HashMap<String, f32>, String> {
+    let result = reqwest::get(RIKSBANK_SERIES_URL)
+        .await
+        .map_err(|error| format!("Error while fetching the Riksbank series: {error}"))?;
+
+    match result.status() {
+        reqwest::StatusCode::OK => result
+            .json::<Vec<Series>>()
+            .await
+            .map(to_units)
+            .map_err(|err| format!("Error while converting the Riksbank series: {err}")),
+        status => Err(format!(
+            "Unexpected error ({status}) for the Riksbank series"
+        )),
+    }
+}
+
+/// Converts the SWEA observations into a rate table based on the Swedish krona.
+/// Series are named `SEK<currency>PMI`, their value is the price of `unit` units of
+/// `<currency>` in kronor, so the table rate is `unit` over the value.
+fn to_rate_table(
+    observations: Vec<Observation>,
+    units: &HashMap<String, f32>,
+) -> Result<RateTable, String> {
+    let mut rates: HashMap<String, f32> = HashMap::new();
+    let mut timestamp: i64 = 0;
+
+    for observation in observations {
+        let currency = match observation
+            .series_id
+            .strip_prefix("SEK")
+            .and_then(|id| id.strip_suffix("PMI"))
+        {
+            Some(currency) if currency.len() == 3 => currency.to_string(),
+            _ => continue,
+        };
+        let value = match observation.value {
+            Some(value) if value > 0.0 => value,
+            _ => continue,
+        };
+
+        if let Ok(date) = NaiveDate::parse_from_str(&observation.date, "%Y-%m-%d") {
+            if let Some(datetime) = date.and_hms_opt(0, 0, 0) {
+                timestamp = timestamp.max(datetime.and_utc().timestamp());
+            }
+        }
+        let unit = units.get(&observation.series_id).copied().unwrap_or(1.0);
+        rates.insert(currency, unit / value);
+    }
+
+    if rates.is_empty() {
+        return Err("No rate could be found in the Riksbank observations".to_string());
+    }
+
+    Ok(RateTable {
+        base: "SEK".to_string(),
+        timestamp,
+        rates,
+    })
+}
+
+/// Fetches the latest exchange rates from the Riksbank
+pub async fn fetch_rates() -> Result<RateTable, String> {
+    let units = fetch_units().await?;
+    let result = reqwest::get(RIKSBANK_LATEST_URL)
+        .await
+        .map_err(|error| format!("Error while fetching Riksbank rates: {error}"))?;
+
+    match result.status() {
+        reqwest::StatusCode::OK => match result.json::<Vec<Observation>>().await {
+            Ok(observations) => to_rate_table(observations, &units),
+            Err(err) => Err(format!("Error while converting Riksbank rates: {err}")),
+        },
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            Err("Too many requests to the Riksbank API, try again later".to_string())
+        }
+        status => Err(format!("Unexpected error ({status})")),
+    }
+}
+
+/// Fetches the daily rates of the given currencies between two dates, one table per day.
+/// The Riksbank publishes one series per currency, so one request is made per currency.
+pub async fn fetch_history(
+    currencies: &[String],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<RateTable>, String> {
+    let units = fetch_units().await?;
+    let mut observations: Vec<Observation> = vec![];
+
+    for currency in currencies.iter().filter(|currency| *currency != "SEK") {
+        let series_id = format!("SEK{currency}PMI");
+        let url = format!(
+            "{RIKSBANK_OBSERVATIONS_URL}/{series_id}/{}/{}",
+            from.format("%Y-%m-%d"),
+            to.format("%Y-%m-%d")
+        );
+        let result = reqwest::get(url)
+            .await
+            .map_err(|error| format!("Error while fetching Riksbank history: {error}"))?;
+
+        match result.status() {
+            reqwest::StatusCode::OK => {
+                let series = result
+                    .json::<Vec<SeriesObservation>>()
+                    .await
+                    .map_err(|err| format!("Error while converting Riksbank history: {err}"))?;
+                observations.extend(series.into_iter().map(|observation| Observation {
+                    series_id: series_id.clone(),
+                    date: observation.date,
+                    value: observation.value,
+                }));
+            }
+            status => return Err(format!("Unexpected error ({status}) for {series_id}")),
+        }
+    }
+
+    let mut by_date: HashMap<String, Vec<Observation>> = HashMap::new();
+    for observation in observations {
+        by_date
+            .entry(observation.date.clone())
+            .or_default()
+            .push(observation);
+    }
+
+    Ok(by_date
+        .into_values()
+        .filter_map(|observations| to_rate_table(observations, &units).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATEST: &str = r#"[
+        {"seriesId": "SEKEURPMI", "date": "2024-01-31", "value": 11.25},
+        {"seriesId": "SEKUSDPMI", "date": "2024-01-31", "value": 10.4},
+        {"seriesId": "SEKJPYPMI", "date": "2024-01-31", "value": null},
+        {"seriesId": "SEKETT", "date": "2024-01-31", "value": 1.0}
+    ]"#;
+
+    const SERIES: &str = r#"[
+        {"seriesId": "SEKEURPMI", "shortDescription": "EUR", "midDescription": "1 EUR", "longDescription": "Euro"},
+        {"seriesId": "SEKUSDPMI", "shortDescription": "USD", "midDescription": "1 USD", "longDescription": "US dollar"},
+        {"seriesId": "SEKJPYPMI", "shortDescription": "JPY", "midDescription": "100 JPY", "longDescription": "Japanese yen"}
+    ]"#;
+
+    fn units() -> HashMap<String, f32> {
+        to_units(serde_json::from_str(SERIES).unwrap())
+    }
+
+    fn latest() -> RateTable {
+        to_rate_table(serde_json::from_str(LATEST).unwrap(), &units()).unwrap()
+    }
+
+    #[test]
+    fn builds_a_krona_table() {
+        let table = latest();
+        assert_eq!(table.base, "SEK");
+        assert_eq!(table.rates.len(), 2);
+        assert_eq!(
+            table.timestamp,
+            NaiveDate::from_ymd_opt(2024, 1, 31)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp()
+        );
+    }
+
+    #[test]
+    fn converts_through_the_krona() {
+        let table = latest();
+
+        let eur_sek = table.convert("EUR", "SEK").unwrap();
+        assert!((eur_sek.to_currency_amount - 11.25).abs() < 1e-4);
+
+        let sek_usd = table.convert("SEK", "USD").unwrap();
+        assert!((sek_usd.to_currency_amount - 1.0 / 10.4).abs() < 1e-6);
+
+        let eur_usd = table.convert("EUR", "USD").unwrap();
+        assert!((eur_usd.to_currency_amount - 11.25 / 10.4).abs() < 1e-4);
+    }
+
+    #[test]
+    fn reports_missing_currencies() {
+        let table = latest();
+        // Observed without a value
+        assert!(table.convert("SEK", "JPY").is_err());
+        // Not published at all
+        assert!(table.convert("SEK", "GBP").is_err());
+    }
+
+    #[test]
+    fn rejects_observations_without_rates() {
+        let observations = serde_json::from_str(
+            r#"[{"seriesId": "SEKEURPMI", "date": "2024-01-31", "value": null}]"#,
+        )
+        .unwrap();
+        assert!(to_rate_table(observations, &units()).is_err());
+    }
+
+    #[test]
+    fn reads_the_unit_of_the_series() {
+        let units = units();
+        assert_eq!(units["SEKEURPMI"], 1.0);
+        assert_eq!(units["SEKJPYPMI"], 100.0);
+    }
+
+    #[test]
+    fn normalises_the_series_quoted_per_100_units() {
+        let observations = serde_json::from_str(
+            r#"[
+                {"seriesId": "SEKEURPMI", "date": "2024-01-31", "value": 11.25},
+                {"seriesId": "SEKJPYPMI", "date": "2024-01-31", "value": 7.05}
+            ]"#,
+        )
+        .unwrap();
+        let table = to_rate_table(observations, &units()).unwrap();
+
+        let jpy_sek = table.convert("JPY", "SEK").unwrap();
+        assert!((jpy_sek.to_currency_amount - 0.0705).abs() < 1e-6);
+
+        let eur_jpy = table.convert("EUR", "JPY").unwrap();
+        assert!((eur_jpy.to_currency_amount - 11.25 / 0.0705).abs() < 0.01);
+
+        // Series missing from the descriptions are quoted for one unit
+        let table = to_rate_table(serde_json::from_str(LATEST).unwrap(), &HashMap::new()).unwrap();
+        let eur_sek = table.convert("EUR", "SEK").unwrap();
+        assert!((eur_sek.to_currency_amount - 11.25).abs() < 1e-4);
+    }
+}
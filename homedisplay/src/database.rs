@@ -5,11 +5,17 @@ use redis::Commands;
 use crate::settings;
 
 pub fn get_redis_connection(redis_settings: &settings::Redis) -> Result<redis::Connection, String> {
-    let client = redis::Client::open(
-        format!("redis://{}:{}/",
-            redis_settings.host,
-            redis_settings.port,
-        ))
+    let connection_info = redis::ConnectionInfo {
+        addr: redis::ConnectionAddr::Tcp(redis_settings.host.clone(), redis_settings.port),
+        redis: redis::RedisConnectionInfo {
+            password: redis_settings
+                .password
+                .as_ref()
+                .map(|password| password.expose().to_string()),
+            ..Default::default()
+        },
+    };
+    let client = redis::Client::open(connection_info)
         .map_err(|e| format!("Could not connect to redis.\nIs the database running at the given host & port ?\nError: {e}"))?;
 
     client
@@ -13,6 +13,10 @@ pub enum HomeDisplayError {
     FileOperation(String),
     NetworkTimeout(String),
     InvalidConfiguration(String),
+    UnknownCurrency {
+        code: String,
+        suggestions: Vec<String>,
+    },
 }
 
 impl fmt::Display for HomeDisplayError {
@@ -37,6 +41,14 @@ impl fmt::Display for HomeDisplayError {
             HomeDisplayError::InvalidConfiguration(msg) => {
                 write!(f, "Invalid configuration: {msg}")
             }
+            HomeDisplayError::UnknownCurrency { code, suggestions } => {
+                write!(f, "Unknown currency code '{code}'")?;
+                if suggestions.is_empty() {
+                    Ok(())
+                } else {
+                    write!(f, ", did you mean {}?", suggestions.join(", "))
+                }
+            }
         }
     }
 }
@@ -9,6 +9,8 @@ pub mod currency;
 #[cfg(feature = "network")]
 pub mod database;
 #[cfg(feature = "network")]
+pub mod quotes;
+#[cfg(feature = "network")]
 pub mod transports;
 #[cfg(feature = "network")]
 pub mod weather;
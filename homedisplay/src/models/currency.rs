@@ -1,4 +1,8 @@
+use chrono::{DateTime, NaiveDate};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::HomeDisplayError;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 /// A conversion between two currencies.
@@ -9,3 +13,230 @@ pub struct Conversion {
     pub to_currency_amount: f32,
     pub timestamp: i64,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A table of exchange rates, all expressed relatively to the `base` currency.
+pub struct RateTable {
+    pub base: String,
+    pub timestamp: i64,
+    pub rates: HashMap<String, f32>,
+}
+
+impl RateTable {
+    /// Returns the rate of the given currency relatively to the base currency, or an
+    /// error if the table does not hold a usable rate for it
+    fn rate(&self, currency: &str) -> Result<f32, HomeDisplayError> {
+        if currency == self.base {
+            return Ok(1.0);
+        }
+
+        match self.rates.get(currency) {
+            Some(rate) if rate.is_finite() && *rate > 0.0 => Ok(*rate),
+            _ => Err(HomeDisplayError::UnknownCurrency {
+                code: currency.to_string(),
+                suggestions: closest_codes(currency, self.rates.keys()),
+            }),
+        }
+    }
+
+    /// Returns whether the table contains a rate for the given currency
+    pub fn contains(&self, currency: &str) -> bool {
+        currency == self.base || self.rates.contains_key(currency)
+    }
+
+    /// Computes the conversion of one unit of `from_currency` into `to_currency`
+    pub fn convert(
+        &self,
+        from_currency: &str,
+        to_currency: &str,
+    ) -> Result<Conversion, HomeDisplayError> {
+        let from = self.rate(from_currency)?;
+        let to = self.rate(to_currency)?;
+
+        Ok(Conversion {
+            from_currency: from_currency.to_string(),
+            from_currency_amount: 1.0,
+            to_currency: to_currency.to_string(),
+            to_currency_amount: to / from,
+            timestamp: self.timestamp,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+/// The currencies known by a provider, mapping their code to their full name
+/// (e.g. `SEK` to `Swedish Krona`).
+pub struct CurrencyList(pub HashMap<String, String>);
+
+impl CurrencyList {
+    /// Checks that the given code is known by the provider. The error lists the
+    /// closest known codes, as well as the codes whose name contains the given code.
+    pub fn validate(&self, code: &str) -> Result<(), HomeDisplayError> {
+        if self.0.contains_key(code) {
+            return Ok(());
+        }
+
+        let needle = code.to_lowercase();
+        let mut suggestions = closest_codes(code, self.0.keys());
+        let mut by_name: Vec<&String> = self
+            .0
+            .iter()
+            .filter(|(_, name)| !needle.is_empty() && name.to_lowercase().contains(&needle))
+            .map(|(code, _)| code)
+            .collect();
+        by_name.sort();
+        for candidate in by_name {
+            if !suggestions.contains(candidate) {
+                suggestions.push(candidate.clone());
+            }
+        }
+        suggestions.truncate(5);
+
+        Err(HomeDisplayError::UnknownCurrency {
+            code: code.to_string(),
+            suggestions: suggestions
+                .into_iter()
+                .map(|code| match self.0.get(&code) {
+                    Some(name) => format!("{code} ({name})"),
+                    None => code,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Returns the candidates closest to the given currency code, using the
+/// Damerau-Levenshtein distance on the uppercased code
+fn closest_codes<'a>(code: &str, candidates: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let code = code.to_uppercase();
+    let mut matches: Vec<(usize, &String)> = candidates
+        .map(|candidate| (strsim::damerau_levenshtein(&code, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 1)
+        .collect();
+    matches.sort();
+    matches
+        .into_iter()
+        .take(5)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+/// Daily rate tables of a provider, indexed by the date they were published
+pub struct RateHistory {
+    pub tables: BTreeMap<NaiveDate, RateTable>,
+}
+
+impl RateHistory {
+    /// Adds a table to the history, merging its rates with the table already
+    /// stored for the same date if any
+    pub fn insert(&mut self, date: NaiveDate, table: RateTable) {
+        match self.tables.get_mut(&date) {
+            Some(existing) if existing.base == table.base => existing.rates.extend(table.rates),
+            _ => {
+                self.tables.insert(date, table);
+            }
+        }
+    }
+
+    /// Adds a table to the history, on the date of its timestamp
+    pub fn insert_table(&mut self, table: RateTable) {
+        if let Some(date) = DateTime::from_timestamp(table.timestamp, 0).map(|dt| dt.date_naive()) {
+            self.insert(date, table);
+        }
+    }
+
+    /// Removes the tables older than the given date
+    pub fn prune_before(&mut self, date: NaiveDate) {
+        self.tables = self.tables.split_off(&date);
+    }
+
+    /// Returns the history of the conversion between two currencies. Dates for
+    /// which one of the currencies is missing are skipped.
+    pub fn conversion(&self, from_currency: &str, to_currency: &str) -> ConversionHistory {
+        ConversionHistory {
+            from_currency: from_currency.to_string(),
+            to_currency: to_currency.to_string(),
+            points: self
+                .tables
+                .iter()
+                .filter_map(|(date, table)| {
+                    table
+                        .convert(from_currency, to_currency)
+                        .ok()
+                        .map(|conversion| HistoryPoint {
+                            date: *date,
+                            rate: conversion.to_currency_amount,
+                        })
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// The rate of a conversion on a given day
+pub struct HistoryPoint {
+    pub date: NaiveDate,
+    pub rate: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// The daily rates of a conversion between two currencies, oldest first
+pub struct ConversionHistory {
+    pub from_currency: String,
+    pub to_currency: String,
+    pub points: Vec<HistoryPoint>,
+}
+
+impl ConversionHistory {
+    /// Returns the change of the rate, in percent, between the latest point and the
+    /// latest point at least `days` days older. `None` if the history is too short.
+    pub fn change(&self, days: i64) -> Option<f32> {
+        let latest = self.points.last()?;
+        let reference = self
+            .points
+            .iter()
+            .rev()
+            .find(|point| (latest.date - point.date).num_days() >= days)?;
+
+        if reference.rate == 0.0 {
+            None
+        } else {
+            Some((latest.rate - reference.rate) / reference.rate * 100.0)
+        }
+    }
+
+    /// Returns the rates of the history, oldest first
+    pub fn rates(&self) -> Vec<f32> {
+        self.points.iter().map(|point| point.rate).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// The condition of an alert rule, with its configured threshold
+pub enum AlertKind {
+    Above(f32),
+    Below(f32),
+    DailyChange(f32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// An alert rule of the currency settings that is currently triggered
+pub struct TriggeredAlert {
+    pub from_currency: String,
+    pub to_currency: String,
+    pub kind: AlertKind,
+    pub rate: f32,
+    pub message: String,
+}
+
+impl TriggeredAlert {
+    /// Returns whether both alerts were triggered by the same rule
+    pub fn same_rule(&self, other: &TriggeredAlert) -> bool {
+        self.from_currency == other.from_currency
+            && self.to_currency == other.to_currency
+            && self.kind == other.kind
+    }
+}
@@ -1,4 +1,5 @@
 pub mod currency;
+pub mod quotes;
 pub mod transports;
 pub mod weather;
 mod weather_codes;
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// The price of a cryptocurrency or a commodity
+pub struct Quote {
+    pub symbol: String, // As configured, e.g. BTC
+    pub price: f32,
+    pub currency: String,
+    pub change_24h: Option<f32>, // In percent
+    pub timestamp: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+/// The quotes of the configured symbols, in the order of the settings
+pub struct QuoteTable {
+    pub currency: String,
+    pub quotes: Vec<Quote>,
+    /// The configured symbols the provider has no price for, e.g. misspelled tickers
+    #[serde(default)]
+    pub missing: Vec<String>,
+}
+
+impl QuoteTable {
+    /// Returns the quote of the given symbol, if any
+    pub fn get(&self, symbol: &str) -> Option<&Quote> {
+        self.quotes
+            .iter()
+            .find(|quote| quote.symbol.eq_ignore_ascii_case(symbol))
+    }
+
+    /// Returns whether the table holds a quote for every given symbol, in the given currency.
+    /// Symbols the provider was already asked for and has no price for count as covered.
+    pub fn covers(&self, symbols: &[String], currency: &str) -> bool {
+        self.currency.eq_ignore_ascii_case(currency)
+            && symbols.iter().all(|symbol| {
+                self.get(symbol).is_some()
+                    || self
+                        .missing
+                        .iter()
+                        .any(|missing| missing.eq_ignore_ascii_case(symbol))
+            })
+    }
+}
@@ -1,13 +1,50 @@
 /// Simple models for the data we are going to use in the app
 /// These models represent the data that is sent to the frontend
+use chrono::{NaiveDateTime, Utc};
+use chrono_tz::Europe::Stockholm;
 use serde::{Deserialize, Serialize};
 
+/// Departures are refreshed every minute, data older than this many seconds is stale
+pub const DEPARTURES_STALE_AFTER: u64 = 150;
+
+/// Share of the walking time needed to reach a stop when running
+const RUNNING_FACTOR: f32 = 0.6;
+
+/// Mean radius of the Earth, in meters
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Returns the current wall-clock time in Stockholm. SL gives the departure times as
+/// Stockholm local times without an offset, they must be compared with this rather than
+/// with the local time of the host.
+pub fn stockholm_now() -> NaiveDateTime {
+    Utc::now().with_timezone(&Stockholm).naive_local()
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Coordinates {
     pub latitude: f32,
     pub longitude: f32,
 }
 
+impl Coordinates {
+    /// Returns whether the coordinates are known, sites without any default to 0.0
+    pub fn is_known(&self) -> bool {
+        self.latitude != 0.0 || self.longitude != 0.0
+    }
+
+    /// Returns the great-circle distance to the given point in meters, using the haversine
+    /// formula
+    pub fn distance_to(&self, latitude: f64, longitude: f64) -> f64 {
+        let (lat_a, lat_b) = ((self.latitude as f64).to_radians(), latitude.to_radians());
+        let delta_lat = lat_b - lat_a;
+        let delta_lon = (longitude - self.longitude as f64).to_radians();
+
+        let a = (delta_lat / 2.0).sin().powi(2)
+            + lat_a.cos() * lat_b.cos() * (delta_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Site {
     pub name: String,
@@ -18,12 +55,529 @@ pub struct Site {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Line {
     pub id: i32,
+    #[serde(default)]
+    pub designation: String, // The public name of the line, e.g. 43X
     pub transport_mode: String,
+    #[serde(default)]
+    pub group_of_lines: Option<String>, // e.g. Tunnelbanans röda linje
+}
+
+impl Line {
+    /// Returns a stable numeric id for a line known by text only (e.g. `Röd` or `T14X`),
+    /// so that distinct lines don't share an id
+    pub fn hashed_id(key: &str) -> i32 {
+        let hash = key.bytes().fold(17u32, |hash, byte| {
+            hash.wrapping_mul(31).wrapping_add(byte as u32)
+        });
+        (hash & 0x7fff_ffff) as i32
+    }
+
+    /// Returns the public name of the line, falling back to its id
+    pub fn name(&self) -> String {
+        if self.designation.is_empty() {
+            self.id.to_string()
+        } else {
+            self.designation.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+/// The state of a departure, as reported by SL
+pub enum DepartureState {
+    NotExpected,
+    NotCalled,
+    #[default]
+    Expected,
+    Cancelled,
+    InProgress,
+    SlowProgress,
+    NoProgress,
+    OffRoute,
+    Aborted,
+    Assigned,
+    AtStop,
+    Boarding,
+    BoardingClosed,
+    Departed,
+    Passed,
+    Missed,
+    Replaced,
+    AssumedDeparted,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+/// The stop point (platform, track or bus stop position) the departure leaves from
+pub struct StopPoint {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub designation: Option<String>, // e.g. A, 2
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+/// The vehicle journey the departure belongs to
+pub struct Journey {
+    pub id: i64,
+    #[serde(default)]
+    pub state: DepartureState,
+    #[serde(default)]
+    pub prediction_state: Option<String>, // NORMAL, LOSTIN_PROGRESS, UNRELIABLE
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+/// A disruption affecting the departure
+pub struct Deviation {
+    #[serde(default)]
+    pub importance_level: i32,
+    #[serde(default)]
+    pub consequence: Option<String>, // e.g. CANCELLED, INFORMATION
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+/// Whether a departure can be reached, given the walking time to its stop
+pub enum Reachability {
+    Catchable, // Leaving now, the stop is reached walking
+    Run,       // Leaving now, the stop is only reached running
+    Missed,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Departure {
     pub destination: String,
+    #[serde(default)]
+    pub direction_code: Option<i32>,
     pub display: String,
+    #[serde(default)]
+    pub state: DepartureState,
+    #[serde(default)]
+    pub scheduled: Option<NaiveDateTime>, // Local Stockholm time
+    #[serde(default)]
+    pub expected: Option<NaiveDateTime>, // Local Stockholm time
     pub line: Line,
+    #[serde(default)]
+    pub stop_point: Option<StopPoint>,
+    #[serde(default)]
+    pub journey: Option<Journey>,
+    #[serde(default)]
+    pub deviations: Vec<Deviation>,
+    /// Minutes needed to walk to the stop, copied from the settings of the stop
+    #[serde(default)]
+    pub walking_minutes: Option<u32>,
+}
+
+impl Departure {
+    /// Returns the time the departure will leave at, expected if known, scheduled otherwise
+    pub fn departure_time(&self) -> Option<NaiveDateTime> {
+        self.expected.or(self.scheduled)
+    }
+
+    /// Returns the number of whole minutes until the departure, computed against the given
+    /// Stockholm time. `None` when SL did not give any time for the departure.
+    pub fn minutes_until(&self, now: NaiveDateTime) -> Option<i64> {
+        self.departure_time()
+            .map(|time| (time - now).num_seconds().div_euclid(60))
+    }
+
+    /// Returns the delay of the departure in whole minutes, negative when it is early
+    pub fn delay_minutes(&self) -> Option<i64> {
+        match (self.scheduled, self.expected) {
+            (Some(scheduled), Some(expected)) => Some((expected - scheduled).num_minutes()),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the departure, or the journey it belongs to, is cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.state == DepartureState::Cancelled
+            || self
+                .journey
+                .as_ref()
+                .is_some_and(|journey| journey.state == DepartureState::Cancelled)
+    }
+
+    /// Returns the platform letter or number the departure leaves from, if any
+    pub fn platform(&self) -> Option<&str> {
+        self.stop_point
+            .as_ref()
+            .and_then(|stop_point| stop_point.designation.as_deref())
+            .filter(|designation| !designation.is_empty())
+    }
+
+    /// Returns whether the vehicle has left, either as reported by SL or because its
+    /// departure time is past the given Stockholm time
+    pub fn has_departed(&self, now: NaiveDateTime) -> bool {
+        matches!(
+            self.state,
+            DepartureState::Departed
+                | DepartureState::Passed
+                | DepartureState::AssumedDeparted
+                | DepartureState::Missed
+        ) || self.departure_time().is_some_and(|time| time < now)
+    }
+
+    /// Returns whether the departure can be reached from the given Stockholm time. `None`
+    /// when no walking time is configured for its stop or SL did not give any time.
+    pub fn reachability(&self, now: NaiveDateTime) -> Option<Reachability> {
+        let walking = self.walking_minutes? as f32;
+        let seconds_left = (self.departure_time()? - now).num_seconds() as f32;
+
+        Some(if seconds_left >= walking * 60.0 {
+            Reachability::Catchable
+        } else if seconds_left >= walking * RUNNING_FACTOR * 60.0 {
+            Reachability::Run
+        } else {
+            Reachability::Missed
+        })
+    }
+
+    /// Returns the minutes left before leaving for the stop to catch the departure walking
+    pub fn leave_in(&self, now: NaiveDateTime) -> Option<i64> {
+        Some(self.minutes_until(now)? - self.walking_minutes? as i64)
+    }
+
+    /// Returns the time left before the departure as displayed on the boards (e.g. `Now`,
+    /// `4 min`), computed against the given Stockholm time. Falls back on SL's display text
+    /// when no time is known.
+    pub fn countdown(&self, now: NaiveDateTime) -> String {
+        match self.minutes_until(now) {
+            Some(minutes) if minutes <= 0 => "Now".to_string(),
+            Some(minutes) if minutes < 60 => format!("{minutes} min"),
+            _ => self.display.clone(),
+        }
+    }
+}
+
+/// Returns the next departure that can be caught walking to its stop, if any
+pub fn next_catchable<'a>(
+    departures: impl IntoIterator<Item = &'a Departure>,
+    now: NaiveDateTime,
+) -> Option<&'a Departure> {
+    departures
+        .into_iter()
+        .filter(|departure| {
+            !departure.is_cancelled()
+                && departure.reachability(now) == Some(Reachability::Catchable)
+        })
+        .min_by_key(|departure| departure.departure_time())
+}
+
+/// Returns the headline telling when to leave for the given departure, e.g.
+/// `Leave in 3 min for 43 Slussen`
+pub fn leave_headline(departure: &Departure, now: NaiveDateTime) -> Option<String> {
+    let when = match departure.leave_in(now)? {
+        minutes if minutes <= 0 => "Leave now".to_string(),
+        minutes => format!("Leave in {minutes} min"),
+    };
+    Some(format!(
+        "{when} for {} {}",
+        departure.line.name(),
+        departure.destination
+    ))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+/// A disruption of the traffic published by SL, e.g. a closed section of a metro line
+pub struct TrafficDeviation {
+    pub id: i64,
+    pub header: String,
+    pub details: String,
+    pub importance_level: i32, // The higher, the more important
+    pub site_ids: Vec<String>, // Sites affected by the deviation
+    pub line_ids: Vec<i32>,    // Lines affected by the deviation
+    pub lines: Vec<String>,    // Public names of the affected lines
+    pub publish_upto: Option<NaiveDateTime>,
+}
+
+impl TrafficDeviation {
+    /// Returns whether the deviation affects one of the given sites or lines
+    pub fn affects(&self, site_ids: &[String], line_ids: &[i32]) -> bool {
+        self.site_ids.iter().any(|id| site_ids.contains(id))
+            || self.line_ids.iter().any(|id| line_ids.contains(id))
+    }
+
+    /// Returns the header prefixed with the affected lines, e.g. `[13, 14] Closed between X and Y`
+    pub fn summary(&self) -> String {
+        if self.lines.is_empty() {
+            self.header.clone()
+        } else {
+            format!("[{}] {}", self.lines.join(", "), self.header)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+/// A leg of an itinerary, either on board a line or walking between two stops
+pub struct ItineraryLeg {
+    pub origin: String,
+    pub destination: String,
+    pub departure: NaiveDateTime,
+    pub arrival: NaiveDateTime,
+    pub line: Option<String>,           // None when walking
+    pub transport_mode: Option<String>, // e.g. BUS, METRO
+    pub direction: Option<String>,      // Final destination of the line
+}
+
+impl ItineraryLeg {
+    /// Returns the duration of the leg in minutes
+    pub fn duration_minutes(&self) -> i64 {
+        (self.arrival - self.departure).num_minutes()
+    }
+
+    /// Returns a short description of the leg, e.g. `17 → Åkeshov` or `Walk 4 min`
+    pub fn describe(&self) -> String {
+        match (&self.line, &self.direction) {
+            (Some(line), Some(direction)) => format!("{line} → {direction}"),
+            (Some(line), None) => format!("{line} → {}", self.destination),
+            (None, _) => format!("Walk {} min", self.duration_minutes()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+/// One way of travelling a route, from the first departure to the last arrival
+pub struct Itinerary {
+    pub departure: NaiveDateTime,
+    pub arrival: NaiveDateTime,
+    pub legs: Vec<ItineraryLeg>,
+}
+
+impl Itinerary {
+    /// Returns the number of changes between lines, walks excluded
+    pub fn transfers(&self) -> usize {
+        self.legs
+            .iter()
+            .filter(|leg| leg.line.is_some())
+            .count()
+            .saturating_sub(1)
+    }
+
+    /// Returns the total travel time in minutes
+    pub fn duration_minutes(&self) -> i64 {
+        (self.arrival - self.departure).num_minutes()
+    }
+
+    /// Returns the times and duration of the itinerary, e.g. `08:12 → 08:47 (35 min, 1 transfer)`
+    pub fn summary(&self) -> String {
+        let transfers = match self.transfers() {
+            0 => "direct".to_string(),
+            1 => "1 transfer".to_string(),
+            transfers => format!("{transfers} transfers"),
+        };
+        format!(
+            "{} → {} ({} min, {transfers})",
+            self.departure.format("%H:%M"),
+            self.arrival.format("%H:%M"),
+            self.duration_minutes()
+        )
+    }
+
+    /// Returns the legs of the itinerary, e.g. `Walk 3 min › 17 → Åkeshov › 4 → Radiohuset`
+    pub fn legs_summary(&self) -> String {
+        self.legs
+            .iter()
+            .map(ItineraryLeg::describe)
+            .collect::<Vec<String>>()
+            .join(" › ")
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+/// The next itineraries of a route of the settings
+pub struct RoutePlan {
+    pub name: String,
+    pub itineraries: Vec<Itinerary>,
+}
+
+impl RoutePlan {
+    /// Returns the itineraries that have not left yet
+    pub fn upcoming(&self, now: NaiveDateTime) -> Vec<&Itinerary> {
+        self.itineraries
+            .iter()
+            .filter(|itinerary| itinerary.departure >= now)
+            .collect()
+    }
+
+    /// Returns the headline of the route, e.g.
+    /// `Home → Office: next trips leave 08:12, 08:19, arrive 08:47`
+    pub fn headline(&self, now: NaiveDateTime) -> String {
+        let upcoming = self.upcoming(now);
+        match upcoming.first() {
+            Some(first) => format!(
+                "{}: next trips leave {}, arrive {}",
+                self.name,
+                upcoming
+                    .iter()
+                    .map(|itinerary| itinerary.departure.format("%H:%M").to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                first.arrival.format("%H:%M")
+            ),
+            None => format!("{}: no upcoming trips", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinates(latitude: f32, longitude: f32) -> Coordinates {
+        Coordinates {
+            latitude,
+            longitude,
+        }
+    }
+
+    #[test]
+    fn computes_great_circle_distances() {
+        // (from, to, distance in meters)
+        let cases = [
+            ((48.8566, 2.3522), (51.5074, -0.1278), 343_556.0), // Paris to London
+            ((59.3300, 18.0590), (57.7089, 11.9746), 396_429.0), // Stockholm to Gothenburg
+            ((0.0, 0.0), (0.0, 1.0), 111_195.0),                // One degree on the equator
+            ((59.3195, 18.0719), (59.3233, 18.0719), 423.0),    // Slussen to Gamla stan
+        ];
+        for ((lat_a, lon_a), (lat_b, lon_b), expected) in cases {
+            let distance = coordinates(lat_a, lon_a).distance_to(lat_b as f64, lon_b as f64);
+            assert!(
+                (distance - expected).abs() < expected * 1e-4 + 1.0,
+                "{lat_a},{lon_a} to {lat_b},{lon_b}: {distance}"
+            );
+            let back = coordinates(lat_b, lon_b).distance_to(lat_a as f64, lon_a as f64);
+            assert!((distance - back).abs() < 1.0);
+        }
+        assert_eq!(
+            coordinates(59.3, 18.0).distance_to(59.3f32 as f64, 18.0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn knows_the_sites_without_coordinates() {
+        assert!(!coordinates(0.0, 0.0).is_known());
+        assert!(coordinates(59.3, 0.0).is_known());
+    }
+
+    fn time(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    /// A departure of line 43 to Hjulsta, at the given time, from a stop that is the given
+    /// number of minutes away
+    fn departure(at: NaiveDateTime, walking_minutes: Option<u32>) -> Departure {
+        Departure {
+            destination: "Hjulsta".to_string(),
+            direction_code: None,
+            display: at.format("%H:%M").to_string(),
+            state: DepartureState::Expected,
+            scheduled: Some(at),
+            expected: Some(at),
+            line: Line {
+                id: 43,
+                designation: "43".to_string(),
+                transport_mode: "BUS".to_string(),
+                group_of_lines: None,
+            },
+            stop_point: None,
+            journey: None,
+            deviations: vec![],
+            walking_minutes,
+        }
+    }
+
+    #[test]
+    fn tells_whether_a_departure_can_be_reached() {
+        let now = time(12, 8, 0);
+        // (minutes left, reachability with a 5 minutes walk)
+        let cases = [
+            (10, Reachability::Catchable),
+            (5, Reachability::Catchable),
+            (4, Reachability::Run),
+            (3, Reachability::Run),
+            (2, Reachability::Missed),
+            (-1, Reachability::Missed),
+        ];
+        for (minutes, expected) in cases {
+            let departure = departure(now + chrono::Duration::minutes(minutes), Some(5));
+            assert_eq!(departure.reachability(now), Some(expected), "{minutes} min");
+        }
+
+        // Unknown without a walking time or a departure time
+        assert_eq!(departure(time(12, 8, 10), None).reachability(now), None);
+        let unknown = Departure {
+            scheduled: None,
+            expected: None,
+            ..departure(now, Some(5))
+        };
+        assert_eq!(unknown.reachability(now), None);
+    }
+
+    #[test]
+    fn tells_when_to_leave() {
+        let now = time(12, 8, 0);
+
+        let comfortable = departure(time(12, 8, 12), Some(5));
+        assert_eq!(comfortable.leave_in(now), Some(7));
+        assert_eq!(
+            leave_headline(&comfortable, now).as_deref(),
+            Some("Leave in 7 min for 43 Hjulsta")
+        );
+
+        let leave_now = departure(time(12, 8, 5), Some(5));
+        assert_eq!(leave_now.leave_in(now), Some(0));
+        assert_eq!(
+            leave_headline(&leave_now, now).as_deref(),
+            Some("Leave now for 43 Hjulsta")
+        );
+
+        let late = departure(time(12, 8, 4), Some(5));
+        assert_eq!(late.leave_in(now), Some(-1));
+        assert_eq!(
+            leave_headline(&late, now).as_deref(),
+            Some("Leave now for 43 Hjulsta")
+        );
+
+        assert_eq!(departure(time(12, 8, 12), None).leave_in(now), None);
+    }
+
+    #[test]
+    fn counts_down_across_midnight() {
+        let now = time(12, 23, 58);
+        let departure = departure(time(13, 0, 5), Some(5));
+
+        assert_eq!(departure.minutes_until(now), Some(7));
+        assert_eq!(departure.countdown(now), "7 min");
+        assert_eq!(departure.reachability(now), Some(Reachability::Catchable));
+        assert_eq!(departure.leave_in(now), Some(2));
+        assert!(!departure.has_departed(now));
+        assert!(departure.has_departed(time(13, 0, 6)));
+    }
+
+    #[test]
+    fn picks_the_next_departure_caught_walking() {
+        let now = time(12, 23, 55);
+        let missed = departure(time(12, 23, 57), Some(5));
+        let run = departure(time(12, 23, 59), Some(5));
+        let cancelled = Departure {
+            state: DepartureState::Cancelled,
+            ..departure(time(13, 0, 1), Some(5))
+        };
+        let after_midnight = departure(time(13, 0, 3), Some(5));
+        let later = departure(time(13, 0, 20), Some(5));
+
+        let departures = [later, missed, run, cancelled, after_midnight];
+        let next = next_catchable(&departures, now).unwrap();
+        assert_eq!(next.expected, Some(time(13, 0, 3)));
+
+        assert!(next_catchable(&departures[1..3], now).is_none());
+    }
 }
@@ -0,0 +1,228 @@
+/// Fetches cryptocurrency and commodity prices from the CoinGecko simple price API,
+/// or from a local file in the same format.
+use async_trait::async_trait;
+use log::warn;
+use reqwest::Url;
+use std::collections::HashMap;
+
+use crate::models::quotes::{Quote, QuoteTable};
+use crate::settings::{QuoteProvider, Quotes as QuoteSettings};
+use crate::traits::Api;
+
+const COINGECKO_SIMPLE_PRICE_URL: &str = "https://api.coingecko.com/api/v3/simple/price";
+
+/// Returns the CoinGecko coin id of the given symbol. Common tickers are mapped to
+/// their coin, gold and silver to tokens backed by the metal. Other symbols are
+/// expected to be coin ids already.
+pub fn coingecko_id(symbol: &str) -> String {
+    match symbol.to_uppercase().as_str() {
+        "BTC" => "bitcoin",
+        "ETH" => "ethereum",
+        "SOL" => "solana",
+        "ADA" => "cardano",
+        "XRP" => "ripple",
+        "DOGE" => "dogecoin",
+        "LTC" => "litecoin",
+        "DOT" => "polkadot",
+        "XAU" | "GOLD" => "pax-gold",
+        "XAG" | "SILVER" => "kinesis-silver",
+        _ => return symbol.to_lowercase(),
+    }
+    .to_string()
+}
+
+/// Parses a CoinGecko simple price response, e.g.
+/// `{"bitcoin": {"usd": 67000.0, "usd_24h_change": 1.2, "last_updated_at": 1711000000}}`,
+/// into the quotes of the configured symbols. Symbols missing from the response are
+/// recorded as missing.
+pub fn parse_simple_price(body: &str, settings: &QuoteSettings) -> Result<QuoteTable, String> {
+    let prices: HashMap<String, HashMap<String, Option<f64>>> = serde_json::from_str(body)
+        .map_err(|err| format!("Error while converting quotes data: {err}"))?;
+    let currency = settings.vs_currency.to_lowercase();
+
+    let mut table = QuoteTable {
+        currency: settings.vs_currency.to_uppercase(),
+        ..Default::default()
+    };
+    for symbol in settings.symbols.iter() {
+        let fields = prices.get(&coingecko_id(symbol));
+        match fields.and_then(|fields| *fields.get(&currency)?) {
+            Some(price) => table.quotes.push(Quote {
+                symbol: symbol.to_uppercase(),
+                price: price as f32,
+                currency: settings.vs_currency.to_uppercase(),
+                change_24h: fields
+                    .and_then(|fields| fields.get(&format!("{currency}_24h_change")))
+                    .copied()
+                    .flatten()
+                    .map(|change| change as f32),
+                timestamp: fields
+                    .and_then(|fields| fields.get("last_updated_at"))
+                    .copied()
+                    .flatten()
+                    .unwrap_or_default() as i64,
+            }),
+            None => {
+                warn!(
+                    "No {} price for the quote symbol {symbol} (coin id `{}`), is it misspelled ?",
+                    settings.vs_currency.to_uppercase(),
+                    coingecko_id(symbol)
+                );
+                table.missing.push(symbol.to_uppercase());
+            }
+        }
+    }
+
+    Ok(table)
+}
+
+/// Fetches the prices of the configured symbols from CoinGecko
+async fn fetch_coingecko(settings: &QuoteSettings) -> Result<String, String> {
+    let mut ids: Vec<String> = settings.symbols.iter().map(|s| coingecko_id(s)).collect();
+    ids.sort();
+    ids.dedup();
+
+    let url: Url = Url::parse_with_params(
+        COINGECKO_SIMPLE_PRICE_URL,
+        &[
+            ("ids", ids.join(",")),
+            ("vs_currencies", settings.vs_currency.to_lowercase()),
+            ("include_24hr_change", "true".to_string()),
+            ("include_last_updated_at", "true".to_string()),
+        ],
+    )
+    .map_err(|err| format!("Could not parse URL: {err}"))?;
+
+    let mut request = reqwest::Client::new().get(url);
+    if !settings.api_key.is_empty() {
+        request = request.header("x-cg-demo-api-key", settings.api_key.expose());
+    }
+
+    let result = request
+        .send()
+        .await
+        .map_err(|error| format!("Error while fetching CoinGecko API: {error}"))?;
+
+    match result.status() {
+        reqwest::StatusCode::OK => result
+            .text()
+            .await
+            .map_err(|error| format!("Error while reading CoinGecko prices: {error}")),
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            Err("Too many requests to the CoinGecko API, try again later".to_string())
+        }
+        reqwest::StatusCode::UNAUTHORIZED => Err("CoinGecko api key is invalid".to_string()),
+        status => Err(format!("Unexpected error ({status})")),
+    }
+}
+
+/// Reads the prices from the local file, in the CoinGecko simple price format
+fn read_local_file(settings: &QuoteSettings) -> Result<String, String> {
+    let path = settings
+        .local_file
+        .as_ref()
+        .ok_or("The local quote provider requires a `local_file`".to_string())?;
+    std::fs::read_to_string(path).map_err(|err| format!("Unable to read quotes from {path}: {err}"))
+}
+
+#[async_trait]
+impl Api<QuoteSettings, QuoteTable> for QuoteTable {
+    /// Fetches the quotes of the configured symbols from the configured provider.
+    async fn api_get(settings: QuoteSettings) -> Result<QuoteTable, String> {
+        let body = match settings.provider {
+            QuoteProvider::CoinGecko => fetch_coingecko(&settings).await?,
+            QuoteProvider::Local => read_local_file(&settings)?,
+        };
+        parse_simple_price(&body, &settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRICES: &str = r#"{
+        "bitcoin": {"usd": 67000.5, "usd_24h_change": -1.25, "last_updated_at": 1711000000},
+        "pax-gold": {"usd": 2150.0, "usd_24h_change": null, "last_updated_at": 1711000100},
+        "ethereum": {"eur": 3100.0}
+    }"#;
+
+    fn settings(symbols: &[&str]) -> QuoteSettings {
+        QuoteSettings {
+            provider: QuoteProvider::Local,
+            symbols: symbols.iter().map(|symbol| symbol.to_string()).collect(),
+            vs_currency: "usd".to_string(),
+            local_file: Some("quotes.json".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn maps_tickers_to_coin_ids() {
+        assert_eq!(coingecko_id("btc"), "bitcoin");
+        assert_eq!(coingecko_id("GOLD"), "pax-gold");
+        assert_eq!(coingecko_id("Pax-Gold"), "pax-gold");
+    }
+
+    #[test]
+    fn parses_the_configured_symbols() {
+        let table = parse_simple_price(PRICES, &settings(&["btc", "XAU"])).unwrap();
+        assert_eq!(table.currency, "USD");
+        assert!(table.missing.is_empty());
+
+        let bitcoin = table.get("BTC").unwrap();
+        assert_eq!(bitcoin.price, 67000.5);
+        assert_eq!(bitcoin.currency, "USD");
+        assert_eq!(bitcoin.change_24h, Some(-1.25));
+        assert_eq!(bitcoin.timestamp, 1711000000);
+
+        let gold = table.get("xau").unwrap();
+        assert_eq!(gold.price, 2150.0);
+        assert_eq!(gold.change_24h, None);
+    }
+
+    #[test]
+    fn records_the_symbols_without_price() {
+        // Unknown coin, and a coin without a price in the requested currency
+        let table = parse_simple_price(PRICES, &settings(&["BTC", "BTX", "ETH"])).unwrap();
+        assert_eq!(table.quotes.len(), 1);
+        assert_eq!(table.missing, vec!["BTX".to_string(), "ETH".to_string()]);
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(parse_simple_price("not json", &settings(&["BTC"])).is_err());
+    }
+
+    #[test]
+    fn covers_the_symbols_asked_for() {
+        let table = parse_simple_price(PRICES, &settings(&["BTC", "BTX"])).unwrap();
+        let symbols = |symbols: &[&str]| -> Vec<String> {
+            symbols.iter().map(|symbol| symbol.to_string()).collect()
+        };
+
+        assert!(table.covers(&symbols(&["btc", "btx"]), "usd"));
+        assert!(table.covers(&symbols(&["BTC"]), "USD"));
+        // Never asked for, the provider may know it
+        assert!(!table.covers(&symbols(&["BTC", "XAU"]), "USD"));
+        // Asked for in another currency
+        assert!(!table.covers(&symbols(&["BTC"]), "EUR"));
+    }
+
+    #[test]
+    fn reads_a_local_file() {
+        let path =
+            std::env::temp_dir().join(format!("homedisplay-quotes-{}.json", std::process::id()));
+        std::fs::write(&path, PRICES).unwrap();
+        let mut settings = settings(&["BTC"]);
+        settings.local_file = Some(path.to_string_lossy().to_string());
+
+        let body = read_local_file(&settings);
+        std::fs::remove_file(&path).unwrap();
+        let table = parse_simple_price(&body.unwrap(), &settings).unwrap();
+        assert_eq!(table.get("BTC").map(|quote| quote.price), Some(67000.5));
+
+        settings.local_file = None;
+        assert!(read_local_file(&settings).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use log::{error, info, warn};
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::database;
+use crate::models::quotes::QuoteTable;
+use crate::settings;
+use crate::settings::Quotes as QuoteSettings;
+use crate::traits::Api;
+
+#[derive(Serialize, Deserialize)]
+struct QuoteTableDatabase {
+    quotes: QuoteTable,
+    freshness: u64,
+}
+
+/// Returns the redis key holding the quotes of the given provider
+fn quote_table_key(provider: settings::QuoteProvider) -> String {
+    format!("homedisplay:quotes:{provider}")
+}
+
+/// Saves the quotes in redis, wrapping them in a struct that includes the freshness of the data
+fn store_quotes(
+    quotes: &QuoteTable,
+    provider: settings::QuoteProvider,
+    redis_data: &settings::Redis,
+) -> Result<(), String> {
+    let quotes: QuoteTableDatabase = QuoteTableDatabase {
+        quotes: quotes.clone(),
+        freshness: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+
+    let serialized_quotes: String = match serde_json::to_string(&quotes) {
+        Ok(serialized) => serialized,
+        Err(error) => {
+            return Err(format!(
+                "An error occured while serializing the data: {error}",
+            ))
+        }
+    };
+
+    let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
+
+    match con.set::<String, String, redis::Value>(quote_table_key(provider), serialized_quotes) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!(
+            "Could not save serialized data into redis: {error}",
+        )),
+    }
+}
+
+/// Fetches new quotes from the API and stores them in the database
+async fn refresh_quotes(
+    settings: QuoteSettings,
+    redis_data: &settings::Redis,
+) -> Result<QuoteTable, String> {
+    let provider = settings.provider;
+    match QuoteTable::api_get(settings).await {
+        Ok(quotes) => {
+            info!("Storing quotes in database");
+            store_quotes(&quotes, provider, redis_data)?;
+            Ok(quotes)
+        }
+        Err(error) => {
+            error!("Could not fetch quotes from API: {}", error);
+            Err(error)
+        }
+    }
+}
+
+/// Fetches the quotes of the configured symbols from the database, if they are older than
+/// five minutes or miss one of the symbols, data will be refreshed before being returned
+pub async fn fetch_quotes(
+    settings: QuoteSettings,
+    redis_data: &settings::Redis,
+) -> Result<QuoteTable, String> {
+    if settings.symbols.is_empty() {
+        return Ok(QuoteTable::default());
+    }
+
+    match database::get_redis_key(quote_table_key(settings.provider), redis_data).await {
+        Ok(serialized) => match serde_json::from_str::<QuoteTableDatabase>(serialized.as_str()) {
+            Ok(QuoteTableDatabase { quotes, freshness }) => {
+                if SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+                    - freshness
+                    > 300
+                {
+                    info!("Quotes are older than five minutes, fetching new data from API");
+                    refresh_quotes(settings, redis_data).await
+                } else if !quotes.covers(&settings.symbols, &settings.vs_currency) {
+                    info!("Stored quotes do not cover the configured symbols, fetching new data from API");
+                    refresh_quotes(settings, redis_data).await
+                } else {
+                    info!("Data is fresh enough, returning data from redis");
+                    Ok(quotes)
+                }
+            }
+            Err(error) => {
+                error!("Could not deserialize the quotes: {error}. Fetching from API");
+                refresh_quotes(settings, redis_data).await
+            }
+        },
+        Err(err) => {
+            warn!("Could not fetch quotes from redis: {}", err);
+            info!("Fetching quotes from API");
+            refresh_quotes(settings, redis_data).await
+        }
+    }
+}
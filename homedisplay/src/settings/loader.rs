@@ -0,0 +1,552 @@
+/// Layered loading of the settings. Each layer overrides the previous one:
+/// 1. the defaults of the settings structures
+/// 2. the TOML settings file
+/// 3. the legacy environment variables (`OER_API_KEY`, `REDIS_HOST`, ...)
+/// 4. the `HOMEDISPLAY_*` environment variables
+/// 5. the overrides given on the command line
+use std::path::Path;
+
+use log::{info, warn};
+use toml::{Table, Value};
+
+use super::{migration, Settings};
+
+/// Prefix of the environment variables overriding a setting. The path of the setting
+/// follows, its parts separated by a double underscore, e.g. `HOMEDISPLAY_REDIS__HOST` or
+/// `HOMEDISPLAY_TRANSPORTS__0__SITE_ID`
+pub const ENV_PREFIX: &str = "HOMEDISPLAY_";
+
+/// The environment variables documented before the settings file existed, and the path of
+/// the setting they override
+const LEGACY_ENV: &[(&str, &str)] = &[
+    ("OER_API_KEY", "currency.api_key"),
+    ("OER_FROM", "currency.currency_from"),
+    ("OER_TO", "currency.currency_to"),
+    ("REDIS_HOST", "redis.host"),
+    ("REDIS_PORT", "redis.port"),
+    ("WEATHER_LAT", "weather.latitude"),
+    ("WEATHER_LON", "weather.longitude"),
+];
+
+/// Legacy comma separated list of the names of the stops to watch
+const LEGACY_STOPS_ENV: &str = "SL_PLACE_BUS_STOPS";
+
+/// Optional fields, absent from the settings while unset, and the type of their values:
+/// text fields must not be read as numbers, and lists may be given as a single item
+const TEXT_FIELDS: &[&str] = &[
+    "site_id",
+    "display_name",
+    "gtfs_static",
+    "webhook",
+    "local_file",
+    "zone",
+];
+const LIST_FIELDS: &[&str] = &["preferred_lines", "transport_modes", "destinations"];
+
+/// The settings sections without a default, created empty so that the defaults of their
+/// fields apply when neither the file nor the environment sets them
+const REQUIRED_SECTIONS: &[&str] = &["redis", "currency", "quotes", "weather", "transit"];
+const REQUIRED_LISTS: &[&str] = &["timezones", "transports"];
+
+#[derive(Debug, Default, Clone)]
+/// Builds the settings from the layers that were enabled
+pub struct SettingsLoader {
+    file: Option<String>,
+    env: Vec<(String, String)>,
+    overrides: Vec<(String, String)>,
+}
+
+impl SettingsLoader {
+    pub fn new() -> SettingsLoader {
+        SettingsLoader::default()
+    }
+
+    /// Reads the given TOML file. A missing file is skipped, so that the settings can come
+    /// from the environment only.
+    pub fn file(mut self, filename: &str) -> SettingsLoader {
+        self.file = Some(filename.to_string());
+        self
+    }
+
+    /// Reads the `HOMEDISPLAY_*` and legacy environment variables of the process
+    pub fn env(self) -> SettingsLoader {
+        self.env_vars(std::env::vars())
+    }
+
+    /// Reads the `HOMEDISPLAY_*` and legacy variables among the given ones
+    pub fn env_vars(mut self, vars: impl IntoIterator<Item = (String, String)>) -> SettingsLoader {
+        self.env.extend(vars);
+        self
+    }
+
+    /// Adds overrides given as `path=value`, e.g. `weather.latitude=59.3`
+    pub fn overrides(mut self, overrides: &[String]) -> Result<SettingsLoader, String> {
+        for entry in overrides {
+            let (path, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid override `{entry}`, expected `path=value`"))?;
+            self.overrides
+                .push((path.trim().to_string(), value.to_string()));
+        }
+        Ok(self)
+    }
+
+    /// Merges the layers into the settings
+    pub fn load(&self) -> Result<Settings, String> {
+        let mut table = Table::new();
+        if let Some(filename) = &self.file {
+            if Path::new(filename).exists() {
+                info!("Loading settings from file: {}", filename);
+                let content = std::fs::read_to_string(filename).map_err(|e| e.to_string())?;
+                table = migration::migrate_str(&content)
+                    .and_then(|content| toml::from_str(&content).map_err(|e| e.to_string()))
+                    .map_err(|e| format!("{filename}: {e}"))?;
+            } else {
+                warn!(
+                    "Settings file {} not found, using the defaults and the environment",
+                    filename
+                );
+            }
+        }
+
+        let mut settings = Value::Table(with_required(table));
+        // The defaults give the type of the values the overrides replace. The settings are
+        // not round-tripped themselves, as their secrets are redacted once serialized.
+        let defaults =
+            Value::try_from(from_table(with_required(Table::new()))?).map_err(|e| e.to_string())?;
+
+        for (name, path) in LEGACY_ENV {
+            if let Some((_, value)) = self.env.iter().find(|(key, _)| key == name) {
+                info!("Setting {} from the {} environment variable", path, name);
+                set_path(&mut settings, &defaults, path, value)
+                    .map_err(|e| format!("{name}: {e}"))?;
+            }
+        }
+        if let Some((_, stops)) = self.env.iter().find(|(key, _)| key == LEGACY_STOPS_ENV) {
+            info!("Setting the stops from the {LEGACY_STOPS_ENV} environment variable");
+            set_legacy_stops(&mut settings, stops);
+        }
+
+        let mut prefixed: Vec<(String, &String)> = self
+            .env
+            .iter()
+            .filter_map(|(key, value)| {
+                key.strip_prefix(ENV_PREFIX)
+                    .map(|path| (path.to_lowercase().replace("__", "."), value))
+            })
+            .collect();
+        // Sets the list items in order, as each one may only extend its list by one
+        prefixed.sort_by_key(|(path, _)| {
+            path.split('.')
+                .map(|part| format!("{part:0>8}"))
+                .collect::<Vec<String>>()
+        });
+        for (path, value) in prefixed {
+            info!("Setting {} from the environment", path);
+            set_path(&mut settings, &defaults, &path, value)
+                .map_err(|e| format!("{ENV_PREFIX}{e}"))?;
+        }
+
+        for (path, value) in self.overrides.iter() {
+            info!("Setting {} from the command line", path);
+            set_path(&mut settings, &defaults, path, value)?;
+        }
+
+        match settings {
+            Value::Table(table) => from_table(table),
+            _ => Err("The settings are not a table".to_string()),
+        }
+    }
+}
+
+fn with_required(mut table: Table) -> Table {
+    for section in REQUIRED_SECTIONS {
+        table
+            .entry(section.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+    }
+    for list in REQUIRED_LISTS {
+        table
+            .entry(list.to_string())
+            .or_insert_with(|| Value::Array(vec![]));
+    }
+    table
+}
+
+fn from_table(table: Table) -> Result<Settings, String> {
+    Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| e.to_string())
+}
+
+/// Replaces the stops with the comma separated names
+fn set_legacy_stops(settings: &mut Value, names: &str) {
+    let stops = names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let mut stop = Table::new();
+            stop.insert("name".to_string(), Value::String(name.to_string()));
+            Value::Table(stop)
+        })
+        .collect();
+    if let Value::Table(table) = settings {
+        table.insert("transports".to_string(), Value::Array(stops));
+    }
+}
+
+/// Parses a raw value into the type of the value it replaces. Unset values (e.g. an
+/// optional field) are read as TOML literals, falling back to strings.
+fn coerce(raw: &str, current: Option<&Value>) -> Result<Value, String> {
+    match current {
+        Some(Value::String(_)) => Ok(Value::String(raw.to_string())),
+        Some(Value::Integer(_)) => raw
+            .trim()
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| format!("`{raw}` is not an integer")),
+        Some(Value::Float(_)) => raw
+            .trim()
+            .parse()
+            .map(Value::Float)
+            .map_err(|_| format!("`{raw}` is not a number")),
+        Some(Value::Boolean(_)) => raw
+            .trim()
+            .parse()
+            .map(Value::Boolean)
+            .map_err(|_| format!("`{raw}` is not a boolean")),
+        Some(Value::Array(items)) if !raw.trim_start().starts_with('[') => {
+            // Comma separated lists, e.g. `BUS,METRO`
+            raw.split(',')
+                .map(|item| coerce(item.trim(), items.first()))
+                .collect::<Result<Vec<Value>, String>>()
+                .map(Value::Array)
+        }
+        _ => Ok(parse_literal(raw).unwrap_or_else(|| Value::String(raw.to_string()))),
+    }
+}
+
+/// Parses a raw value into the value of an unset field
+fn coerce_unset(field: &str, raw: &str) -> Result<Value, String> {
+    if TEXT_FIELDS.contains(&field) {
+        Ok(Value::String(raw.to_string()))
+    } else if LIST_FIELDS.contains(&field) {
+        coerce(raw, Some(&Value::Array(vec![])))
+    } else {
+        coerce(raw, None)
+    }
+}
+
+fn parse_literal(raw: &str) -> Option<Value> {
+    toml::from_str::<Table>(&format!("value = {raw}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+}
+
+/// Sets the value at the dotted path, list items being addressed by their index. A list
+/// can be extended by setting the item right after its end. The value is parsed into the
+/// type of the same field in the defaults, or of the value it replaces.
+fn set_path(settings: &mut Value, defaults: &Value, path: &str, raw: &str) -> Result<(), String> {
+    let parts: Vec<&str> = path.split('.').collect();
+    let mut current = settings;
+    let mut hint = Some(defaults);
+    for (depth, part) in parts.iter().enumerate() {
+        let last = depth + 1 == parts.len();
+        current = match current {
+            Value::Table(table) => {
+                hint = hint.and_then(|hint| hint.get(*part));
+                if last {
+                    let value = match hint.or(table.get(*part)) {
+                        Some(typed) => coerce(raw, Some(typed)),
+                        None => coerce_unset(part, raw),
+                    }
+                    .map_err(|e| format!("{path}: {e}"))?;
+                    table.insert(part.to_string(), value);
+                    return Ok(());
+                }
+                table.entry(part.to_string()).or_insert_with(|| match hint {
+                    Some(Value::Array(_)) => Value::Array(vec![]),
+                    _ => Value::Table(Table::new()),
+                })
+            }
+            Value::Array(items) => {
+                let index: usize = part
+                    .parse()
+                    .map_err(|_| format!("{path}: `{part}` is not a list index"))?;
+                if index > items.len() {
+                    return Err(format!(
+                        "{path}: index {index} is past the end of the list ({} items)",
+                        items.len()
+                    ));
+                }
+                hint = hint.and_then(|hint| hint.get(index));
+                if last {
+                    let value =
+                        coerce(raw, items.get(index)).map_err(|e| format!("{path}: {e}"))?;
+                    if index == items.len() {
+                        items.push(value);
+                    } else {
+                        items[index] = value;
+                    }
+                    return Ok(());
+                }
+                if index == items.len() {
+                    items.push(Value::Table(Table::new()));
+                }
+                &mut items[index]
+            }
+            _ => return Err(format!("{path}: `{part}` is not a section or a list")),
+        };
+    }
+    Err(format!("{path}: empty path"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the content to a new file of the temporary directory, returning its path
+    fn settings_file(name: &str, content: &str) -> String {
+        let directory =
+            std::env::temp_dir().join(format!("homedisplay-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(name).to_string_lossy().to_string();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// The file, environment and overrides of the settings, and the redis host they give
+    type Layers<'a> = (&'a str, &'a [(&'a str, &'a str)], &'a [&'a str], &'a str);
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn overrides(overrides: &[&str]) -> Vec<String> {
+        overrides.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn applies_the_layers_in_order() {
+        let file = settings_file(
+            "layers.toml",
+            "[redis]\nhost = \"file\"\n[weather]\nlatitude = 1.5\n",
+        );
+        let missing = format!("{file}.missing");
+
+        let cases: &[Layers] = &[
+            (&missing, &[], &[], "localhost"),
+            (&file, &[], &[], "file"),
+            (&file, &[("REDIS_HOST", "legacy")], &[], "legacy"),
+            (
+                &file,
+                &[
+                    ("HOMEDISPLAY_REDIS__HOST", "prefixed"),
+                    ("REDIS_HOST", "legacy"),
+                ],
+                &[],
+                "prefixed",
+            ),
+            (
+                &file,
+                &[
+                    ("HOMEDISPLAY_REDIS__HOST", "prefixed"),
+                    ("REDIS_HOST", "legacy"),
+                ],
+                &["redis.host=override"],
+                "override",
+            ),
+            (&file, &[("OTHER_HOST", "ignored")], &[], "file"),
+        ];
+
+        for (file, env, set, expected) in cases {
+            let settings = SettingsLoader::new()
+                .file(file)
+                .env_vars(vars(env))
+                .overrides(&overrides(set))
+                .unwrap()
+                .load()
+                .unwrap();
+            assert_eq!(settings.redis.host, *expected, "{env:?} {set:?}");
+            // The layers only replace the values they set
+            let latitude = if *file == missing { 59.0 } else { 1.5 };
+            assert_eq!(settings.weather.latitude, latitude);
+        }
+    }
+
+    #[test]
+    fn reads_the_legacy_environment() {
+        let settings = SettingsLoader::new()
+            .env_vars(vars(&[
+                ("OER_API_KEY", "key"),
+                ("OER_FROM", "USD"),
+                ("REDIS_PORT", "6380"),
+                ("WEATHER_LON", "18.07"),
+                ("SL_PLACE_BUS_STOPS", "Slussen, Odenplan,,"),
+            ]))
+            .load()
+            .unwrap();
+
+        assert_eq!(settings.currency.api_key.expose(), "key");
+        assert_eq!(settings.currency.currency_from, "USD");
+        assert_eq!(settings.redis.port, 6380);
+        assert_eq!(settings.weather.longitude, 18.07);
+        let names: Vec<&str> = settings
+            .transports
+            .iter()
+            .map(|stop| stop.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Slussen", "Odenplan"]);
+    }
+
+    #[test]
+    fn sets_nested_paths_and_extends_the_lists() {
+        let file = settings_file(
+            "lists.toml",
+            "[[transports]]\nname = \"Slussen\"\npreferred_lines = [4]\n",
+        );
+        let settings = SettingsLoader::new()
+            .file(&file)
+            .env_vars(vars(&[
+                ("HOMEDISPLAY_TRANSPORTS__0__SITE_ID", "9192"),
+                ("HOMEDISPLAY_TRANSPORTS__1__NAME", "Odenplan"),
+                ("HOMEDISPLAY_TRANSPORTS__1__TRANSPORT_MODES", "BUS,METRO"),
+            ]))
+            .overrides(&overrides(&[
+                "transports.0.preferred_lines.1=55",
+                "transports.1.preferred_lines=[1, 2]",
+                "currency.pairs.0.from=NOK",
+                "currency.pairs.0.to=EUR",
+            ]))
+            .unwrap()
+            .load()
+            .unwrap();
+
+        assert_eq!(settings.transports.len(), 2);
+        assert_eq!(settings.transports[0].site_id.as_deref(), Some("9192"));
+        assert_eq!(settings.transports[0].preferred_lines, Some(vec![4, 55]));
+        assert_eq!(settings.transports[1].name, "Odenplan");
+        assert_eq!(
+            settings.transports[1].transport_modes,
+            Some(vec!["BUS".to_string(), "METRO".to_string()])
+        );
+        assert_eq!(settings.transports[1].preferred_lines, Some(vec![1, 2]));
+        assert_eq!(settings.currency.pairs[0].from, "NOK");
+        assert_eq!(settings.currency.pairs[0].to, "EUR");
+    }
+
+    #[test]
+    fn sets_the_list_items_in_order() {
+        // Given from the last to the first, the items are still set one after the other,
+        // `10` being sorted after `9`
+        let env: Vec<(String, String)> = (0..=10)
+            .rev()
+            .map(|index| {
+                (
+                    format!("HOMEDISPLAY_TRANSPORTS__{index}__NAME"),
+                    format!("Stop {index}"),
+                )
+            })
+            .collect();
+        let settings = SettingsLoader::new().env_vars(env).load().unwrap();
+
+        let names: Vec<String> = settings
+            .transports
+            .into_iter()
+            .map(|stop| stop.name)
+            .collect();
+        let expected: Vec<String> = (0..=10).map(|index| format!("Stop {index}")).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn rejects_invalid_paths_and_overrides() {
+        let cases: &[(&[&str], &str)] = &[
+            (&["transports.1.name=Odenplan"], "past the end of the list"),
+            (&["transports.first.name=Odenplan"], "is not a list index"),
+            (
+                &["redis.host=localhost", "redis.host.name=localhost"],
+                "is not a section or a list",
+            ),
+            (&["redis.port=high"], "is not an integer"),
+            (&["weather.latitude=north"], "is not a number"),
+        ];
+        for (entries, message) in cases {
+            let error = SettingsLoader::new()
+                .overrides(&overrides(entries))
+                .unwrap()
+                .load()
+                .unwrap_err();
+            assert!(error.contains(message), "{entries:?}: {error}");
+        }
+
+        assert!(SettingsLoader::new()
+            .overrides(&overrides(&["redis.host"]))
+            .is_err());
+    }
+
+    #[test]
+    fn coerces_the_values_into_the_type_they_replace() {
+        let string = Value::String(String::new());
+        let integer = Value::Integer(0);
+        let float = Value::Float(0.0);
+        let boolean = Value::Boolean(false);
+        let integers = Value::Array(vec![integer.clone()]);
+
+        let cases: &[(&str, Option<&Value>, Value)] = &[
+            ("42", Some(&string), Value::String("42".to_string())),
+            (" 42 ", Some(&integer), Value::Integer(42)),
+            ("59.3", Some(&float), Value::Float(59.3)),
+            ("true", Some(&boolean), Value::Boolean(true)),
+            (" false", Some(&boolean), Value::Boolean(false)),
+            (
+                "4, 55",
+                Some(&integers),
+                Value::Array(vec![Value::Integer(4), Value::Integer(55)]),
+            ),
+            (
+                "[4, 55]",
+                Some(&integers),
+                Value::Array(vec![Value::Integer(4), Value::Integer(55)]),
+            ),
+            ("12", None, Value::Integer(12)),
+            ("true", None, Value::Boolean(true)),
+            ("Slussen", None, Value::String("Slussen".to_string())),
+        ];
+        for (raw, current, expected) in cases {
+            assert_eq!(coerce(raw, *current).as_ref(), Ok(expected), "{raw}");
+        }
+
+        for (raw, current) in [
+            ("yes", &boolean),
+            ("4.5", &integer),
+            ("north", &float),
+            ("4,x", &integers),
+        ] {
+            assert!(coerce(raw, Some(current)).is_err(), "{raw}");
+        }
+    }
+
+    #[test]
+    fn coerces_the_unset_fields_by_name() {
+        assert_eq!(
+            coerce_unset("site_id", "9192"),
+            Ok(Value::String("9192".to_string()))
+        );
+        assert_eq!(
+            coerce_unset("preferred_lines", "4"),
+            Ok(Value::Array(vec![Value::Integer(4)]))
+        );
+        assert_eq!(
+            coerce_unset("transport_modes", "BUS,TRAM"),
+            Ok(Value::Array(vec![
+                Value::String("BUS".to_string()),
+                Value::String("TRAM".to_string())
+            ]))
+        );
+        assert_eq!(coerce_unset("closest", "3"), Ok(Value::Integer(3)));
+    }
+}
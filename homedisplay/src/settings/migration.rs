@@ -0,0 +1,235 @@
+/// Upgrades of the settings files written for older versions of the application. Each
+/// migration takes the settings from one version to the next, the files without a
+/// `version` key being version 0. The migrations edit the TOML document itself, so that
+/// the comments and the layout of the file are kept.
+use log::info;
+use toml_edit::{value, DocumentMut, Item, TableLike};
+
+/// Version of the settings read and written by this version of the application
+pub const CURRENT_VERSION: u32 = 1;
+
+/// A migration to the next version, returning a description of each change made
+type Migration = fn(&mut DocumentMut) -> Vec<String>;
+
+/// The migrations, the one at index `n` upgrading from version `n` to `n + 1`
+const MIGRATIONS: &[Migration] = &[v0_to_v1];
+
+/// Returns the version of the settings document
+pub fn version(document: &DocumentMut) -> Result<u32, String> {
+    match document.get("version") {
+        None => Ok(0),
+        Some(item) => item
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| "`version` must be a positive integer".to_string()),
+    }
+}
+
+/// Upgrades the document to the current version, returning the changes made
+pub fn migrate(document: &mut DocumentMut) -> Result<Vec<String>, String> {
+    let from = version(document)?;
+    if from > CURRENT_VERSION {
+        return Err(format!(
+            "The settings are for version {from}, this application only reads up to version {CURRENT_VERSION}"
+        ));
+    }
+
+    let mut changes: Vec<String> = vec![];
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        for change in migration(document) {
+            changes.push(format!("v{} → v{}: {change}", index, index + 1));
+        }
+    }
+    document["version"] = value(CURRENT_VERSION as i64);
+    Ok(changes)
+}
+
+/// Upgrades the TOML settings to the current version in memory, logging the changes made
+pub fn migrate_str(content: &str) -> Result<String, String> {
+    let mut document = content.parse::<DocumentMut>().map_err(|e| e.to_string())?;
+    if version(&document)? == CURRENT_VERSION {
+        return Ok(content.to_string());
+    }
+    for change in migrate(&mut document)? {
+        info!("Settings upgrade: {}", change);
+    }
+    Ok(document.to_string())
+}
+
+/// Upgrades the settings file to the current version, the original file being kept next
+/// to it as `<file>.v<version>.bak`. Returns whether the file was upgraded.
+pub fn upgrade_file(filename: &str) -> Result<bool, String> {
+    let content = match std::fs::read_to_string(filename) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(format!("{filename}: {e}")),
+    };
+    let mut document = content
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("{filename}: {e}"))?;
+    let from = version(&document).map_err(|e| format!("{filename}: {e}"))?;
+    if from == CURRENT_VERSION {
+        return Ok(false);
+    }
+
+    let changes = migrate(&mut document).map_err(|e| format!("{filename}: {e}"))?;
+    let backup = format!("{filename}.v{from}.bak");
+    std::fs::copy(filename, &backup).map_err(|e| format!("Unable to back up {filename}: {e}"))?;
+    std::fs::write(filename, document.to_string()).map_err(|e| format!("{filename}: {e}"))?;
+
+    info!(
+        "Upgraded {} from version {} to {}, the original is kept as {}",
+        filename, from, CURRENT_VERSION, backup
+    );
+    for change in changes {
+        info!("Settings upgrade: {}", change);
+    }
+    Ok(true)
+}
+
+/// Returns the tables of a list of tables, written either as `[[name]]` sections or as an
+/// inline array
+fn tables_mut<'a>(document: &'a mut DocumentMut, name: &str) -> Vec<&'a mut dyn TableLike> {
+    match document.get_mut(name) {
+        Some(Item::ArrayOfTables(tables)) => tables
+            .iter_mut()
+            .map(|table| table as &mut dyn TableLike)
+            .collect(),
+        Some(Item::Value(toml_edit::Value::Array(items))) => items
+            .iter_mut()
+            .filter_map(|item| item.as_inline_table_mut())
+            .map(|table| table as &mut dyn TableLike)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Renames a key of a table, keeping its value and its decoration
+fn rename_key(table: &mut dyn TableLike, from: &str, to: &str) -> bool {
+    match table.remove(from) {
+        Some(item) => {
+            table.insert(to, item);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Version 1 fixes the spelling of `preferred_lines`
+fn v0_to_v1(document: &mut DocumentMut) -> Vec<String> {
+    tables_mut(document, "transports")
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, stop)| {
+            rename_key(stop, "preffered_lines", "preferred_lines")
+                .then(|| format!("transports[{index}].preffered_lines renamed to preferred_lines"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V0: &str = r#"# Stops near home
+[[transports]]
+name = "Slussen"
+preffered_lines = [4, 55] # the buses
+
+[[transports]]
+name = "Odenplan"
+"#;
+
+    /// Writes the content to a new file of the temporary directory, returning its path
+    fn settings_file(name: &str, content: &str) -> String {
+        let directory =
+            std::env::temp_dir().join(format!("homedisplay-migration-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(name).to_string_lossy().to_string();
+        std::fs::write(&path, content).unwrap();
+        let _ = std::fs::remove_file(format!("{path}.v0.bak"));
+        path
+    }
+
+    #[test]
+    fn renames_the_preferred_lines_of_a_v0_document() {
+        let mut document = V0.parse::<DocumentMut>().unwrap();
+        let changes = migrate(&mut document).unwrap();
+
+        assert_eq!(
+            changes,
+            vec!["v0 → v1: transports[0].preffered_lines renamed to preferred_lines"]
+        );
+        assert_eq!(version(&document), Ok(CURRENT_VERSION));
+        let migrated = document.to_string();
+        assert!(migrated.contains("# Stops near home"));
+        assert!(migrated.contains("preferred_lines = [4, 55] # the buses"));
+        assert!(!migrated.contains("preffered_lines"));
+    }
+
+    #[test]
+    fn renames_the_preferred_lines_of_inline_stops() {
+        let migrated = migrate_str(
+            r#"transports = [{ name = "Slussen", preffered_lines = [4] }, { name = "Odenplan" }]"#,
+        )
+        .unwrap();
+        let document = migrated.parse::<DocumentMut>().unwrap();
+
+        assert_eq!(version(&document), Ok(CURRENT_VERSION));
+        assert_eq!(
+            document["transports"][0]["preferred_lines"]
+                .as_array()
+                .map(|lines| lines.len()),
+            Some(1)
+        );
+        assert!(document["transports"][0].get("preffered_lines").is_none());
+    }
+
+    #[test]
+    fn keeps_current_documents_as_they_are() {
+        let content = "version = 1\n\n[[transports]]\nname = \"Slussen\" # home\n";
+        assert_eq!(migrate_str(content).unwrap(), content);
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        for content in ["version = 99", "version = -1", "version = \"1\""] {
+            let mut document = content.parse::<DocumentMut>().unwrap();
+            assert!(migrate(&mut document).is_err(), "{content}");
+            assert!(migrate_str(content).is_err(), "{content}");
+        }
+    }
+
+    #[test]
+    fn upgrades_a_v0_file_keeping_a_backup() {
+        let path = settings_file("v0.toml", V0);
+
+        assert_eq!(upgrade_file(&path), Ok(true));
+        assert_eq!(
+            std::fs::read_to_string(format!("{path}.v0.bak")).unwrap(),
+            V0
+        );
+        let upgraded = std::fs::read_to_string(&path).unwrap();
+        assert!(upgraded.contains("preferred_lines = [4, 55]"));
+        assert!(upgraded.contains("version = 1"));
+
+        // The upgraded file is current
+        assert_eq!(upgrade_file(&path), Ok(false));
+    }
+
+    #[test]
+    fn leaves_current_and_unknown_files_untouched() {
+        let current = "version = 1\n";
+        let path = settings_file("current.toml", current);
+        assert_eq!(upgrade_file(&path), Ok(false));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), current);
+        assert!(!std::path::Path::new(&format!("{path}.v1.bak")).exists());
+
+        let future = "version = 99\n";
+        let path = settings_file("future.toml", future);
+        assert!(upgrade_file(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), future);
+
+        assert_eq!(upgrade_file(&format!("{path}.missing")), Ok(false));
+    }
+}
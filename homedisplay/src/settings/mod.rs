@@ -0,0 +1,548 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+/// This file contains all the settings structures and functions
+/// used to configure the application.
+use std::fs;
+
+use secret::Secret;
+
+pub mod loader;
+pub mod migration;
+pub mod secret;
+pub mod validation;
+#[cfg(feature = "watch")]
+pub mod watcher;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// The settings structure
+/// This structure is used to store all the settings of the application
+/// It is loaded from a file and can be modified by the user
+pub struct Settings {
+    /// Version of the settings format, older files are upgraded when loaded
+    #[serde(default = "current_version")]
+    pub version: u32,
+    pub redis: Redis,
+    pub currency: Currency,
+    #[serde(default)]
+    pub quotes: Quotes,
+    pub weather: Weather,
+    pub timezones: Vec<TimezoneData>,
+    #[serde(default)]
+    pub transit: Transit,
+    pub transports: Vec<BusStop>,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+}
+
+fn current_version() -> u32 {
+    migration::CURRENT_VERSION
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: current_version(),
+            redis: Redis::default(),
+            currency: Currency::default(),
+            quotes: Quotes::default(),
+            weather: Weather::default(),
+            timezones: Vec::new(),
+            transit: Transit::default(),
+            transports: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load_from_file(filename: &str) -> Result<Settings, String> {
+        info!("Loading settings from file: {}", filename);
+        let content = fs::read_to_string(filename).map_err(|e| e.to_string())?;
+        let settings: Settings =
+            toml::from_str(&migration::migrate_str(&content)?).map_err(|e| e.to_string())?;
+        Ok(settings)
+    }
+
+    /// Dumps this settings structure into a toml string, the inline secrets being redacted
+    pub fn to_string(&self) -> Result<String, String> {
+        toml::to_string(&self).map_err(|e| e.to_string())
+    }
+
+    /// Replaces the stops of the settings file, leaving the rest of the file untouched so
+    /// that its comments and secrets are kept
+    pub fn save_transports(filename: &str, stops: &[BusStop]) -> Result<(), String> {
+        #[derive(Serialize)]
+        struct Transports<'a> {
+            transports: &'a [BusStop],
+        }
+
+        let mut document = match fs::read_to_string(filename) {
+            Ok(content) => content
+                .parse::<toml_edit::DocumentMut>()
+                .map_err(|e| format!("{filename}: {e}"))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml_edit::DocumentMut::new(),
+            Err(e) => return Err(format!("{filename}: {e}")),
+        };
+        let transports = toml::to_string(&Transports { transports: stops })
+            .map_err(|e| e.to_string())?
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|e| e.to_string())?;
+        document["transports"] = transports["transports"].clone();
+
+        fs::write(filename, document.to_string()).map_err(|e| format!("{filename}: {e}"))
+    }
+}
+
+fn default_lat() -> f64 {
+    59.0
+}
+
+fn default_lon() -> f64 {
+    17.0
+}
+
+fn default_display_amount() -> i32 {
+    5
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Weather {
+    #[serde(default = "default_lat")]
+    pub latitude: f64,
+    #[serde(default = "default_lon")]
+    pub longitude: f64,
+    #[serde(default = "default_display_amount")]
+    pub display_amount: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// Structure to hold the timezone data
+/// This is used to display the time in different timezones
+/// Local time is always displayed
+pub struct TimezoneData {
+    /// IANA name of the zone (e.g. America/New_York), following its daylight saving time.
+    /// When unset, the fixed `direction` and `offset` are used instead.
+    #[serde(default)]
+    pub zone: Option<String>,
+    #[serde(default)]
+    pub direction: String, // E or W
+    #[serde(default)]
+    pub offset: f32, // In hours
+    pub name: String, // A City or a Country (e.g. Paris, France)
+}
+
+impl TimezoneData {
+    /// Returns the given instant in this timezone, along with the abbreviation of the zone
+    /// at that instant (e.g. EST or EDT for America/New_York, UTC+05:30 for fixed offsets)
+    pub fn at(&self, instant: DateTime<Utc>) -> Result<(DateTime<FixedOffset>, String), String> {
+        if let Some(zone) = &self.zone {
+            let tz: chrono_tz::Tz = zone
+                .parse()
+                .map_err(|_| format!("Unknown IANA timezone '{zone}'"))?;
+            let time = instant.with_timezone(&tz);
+            return Ok((time.fixed_offset(), time.format("%Z").to_string()));
+        }
+
+        let seconds = (self.offset * 3600.0).round() as i32;
+        let offset = match self.direction.to_uppercase().as_str() {
+            "E" => FixedOffset::east_opt(seconds),
+            "W" => FixedOffset::west_opt(seconds),
+            other => {
+                return Err(format!(
+                    "Incorrect timezone direction '{other}'. Expected 'E' or 'W'"
+                ))
+            }
+        }
+        .ok_or_else(|| format!("Invalid timezone offset of {} hours", self.offset))?;
+        let time = instant.with_timezone(&offset);
+        Ok((time, format!("UTC{}", time.format("%:z"))))
+    }
+}
+
+fn default_from_currency() -> String {
+    "SEK".to_string()
+}
+
+fn default_to_currency() -> String {
+    "EUR".to_string()
+}
+
+fn default_history_days() -> u32 {
+    90
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+/// The source of the exchange rates
+pub enum CurrencyProvider {
+    #[default]
+    /// Open Exchange Rates, requires an `api_key`
+    OpenExchangeRates,
+    /// The European Central Bank daily reference rates, no account required
+    Ecb,
+    /// The Riksbank SWEA API, no account required
+    Riksbank,
+}
+
+impl std::fmt::Display for CurrencyProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CurrencyProvider::OpenExchangeRates => write!(f, "openexchangerates"),
+            CurrencyProvider::Ecb => write!(f, "ecb"),
+            CurrencyProvider::Riksbank => write!(f, "riksbank"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// Structure to hold the currency settings
+pub struct Currency {
+    #[serde(default)]
+    pub provider: CurrencyProvider,
+    #[serde(default)]
+    pub api_key: Secret,
+    #[serde(default = "default_from_currency")]
+    pub currency_from: String,
+    #[serde(default = "default_to_currency")]
+    pub currency_to: String,
+    #[serde(default)]
+    pub pairs: Vec<CurrencyPair>,
+    /// Days of rate history to keep. With Open Exchange Rates, the missing days are
+    /// fetched a week at a time, once a day, to spare the request quota
+    #[serde(default = "default_history_days")]
+    pub history_days: u32,
+    #[serde(default)]
+    pub alerts: Vec<CurrencyAlert>,
+    /// URL to POST the newly triggered alerts to, as JSON
+    #[serde(default)]
+    pub webhook: Option<String>,
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Currency {
+            provider: CurrencyProvider::default(),
+            api_key: Secret::default(),
+            currency_from: default_from_currency(),
+            currency_to: default_to_currency(),
+            pairs: Vec::new(),
+            history_days: default_history_days(),
+            alerts: Vec::new(),
+            webhook: None,
+        }
+    }
+}
+
+impl Currency {
+    /// Returns the currency pairs to display. When no `pairs` are configured,
+    /// the single `currency_from`/`currency_to` pair is used instead.
+    pub fn pairs(&self) -> Vec<CurrencyPair> {
+        if self.pairs.is_empty() {
+            vec![CurrencyPair {
+                from: self.currency_from.clone(),
+                to: self.currency_to.clone(),
+            }]
+        } else {
+            self.pairs.clone()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// A conversion to display, from one currency code to another (e.g. SEK to EUR)
+pub struct CurrencyPair {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// A rule on a currency pair, triggering an alert when the rate goes above or below
+/// a value, or when it moves by more than a percentage in a day
+pub struct CurrencyAlert {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub above: Option<f32>,
+    #[serde(default)]
+    pub below: Option<f32>,
+    /// Percentage of change (in either direction) compared to the previous day
+    #[serde(default)]
+    pub daily_change: Option<f32>,
+}
+
+fn default_vs_currency() -> String {
+    "USD".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+/// The source of the cryptocurrency and commodity quotes
+pub enum QuoteProvider {
+    #[default]
+    /// The CoinGecko simple price API, an `api_key` is optional
+    CoinGecko,
+    /// A JSON file in the CoinGecko simple price format, read from `local_file`
+    Local,
+}
+
+impl std::fmt::Display for QuoteProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuoteProvider::CoinGecko => write!(f, "coingecko"),
+            QuoteProvider::Local => write!(f, "local"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// Structure to hold the cryptocurrency and commodity quotes settings
+pub struct Quotes {
+    #[serde(default)]
+    pub provider: QuoteProvider,
+    #[serde(default)]
+    pub api_key: Secret,
+    /// Tickers (e.g. BTC, ETH, XAU) or CoinGecko coin ids (e.g. `pax-gold`)
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    /// The currency the prices are expressed in
+    #[serde(default = "default_vs_currency")]
+    pub vs_currency: String,
+    #[serde(default)]
+    pub local_file: Option<String>,
+}
+
+impl Default for Quotes {
+    fn default() -> Self {
+        Quotes {
+            provider: QuoteProvider::default(),
+            api_key: Secret::default(),
+            symbols: Vec::new(),
+            vs_currency: default_vs_currency(),
+            local_file: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+/// The source of the sites and departures
+pub enum TransitProvider {
+    #[default]
+    /// Storstockholms Lokaltrafik, no account required
+    Sl,
+    /// A GTFS static feed read from disk, with optional GTFS-Realtime trip updates
+    Gtfs,
+    /// Trafiklab ResRobot, the national Swedish timetables, requires an `api_key`
+    ResRobot,
+}
+
+impl std::fmt::Display for TransitProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransitProvider::Sl => write!(f, "sl"),
+            TransitProvider::Gtfs => write!(f, "gtfs"),
+            TransitProvider::ResRobot => write!(f, "resrobot"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// Structure to hold the transit provider settings
+pub struct Transit {
+    #[serde(default)]
+    pub provider: TransitProvider,
+    /// The ResRobot access id
+    #[serde(default)]
+    pub api_key: Secret,
+    /// Path to the GTFS static feed, either a zip archive or an extracted directory
+    #[serde(default)]
+    pub gtfs_static: Option<String>,
+    /// URLs or paths of GTFS-Realtime TripUpdates feeds
+    #[serde(default)]
+    pub gtfs_realtime: Vec<String>,
+}
+
+fn default_route_trips() -> u8 {
+    3
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// A named commute planned with the SL journey planner, e.g. Home → Office
+pub struct Route {
+    pub name: String,
+    /// SL site id (e.g. `9192`) or journey planner stop id of the start of the route
+    pub origin: String,
+    /// SL site id or journey planner stop id of the end of the route
+    pub destination: String,
+    /// Number of itineraries to display
+    #[serde(default = "default_route_trips")]
+    pub trips: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+/// How the name of a bus stop selects the sites
+pub enum StopMatch {
+    #[default]
+    /// Every site whose name contains the stop name
+    Contains,
+    /// The sites whose name is the stop name, accents and case aside
+    Exact,
+    /// Only the site with the `site_id` of the stop, the name is then only a label
+    Id,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// Structure to hold the bus stop data
+/// This is used to display the bus departures
+pub struct BusStop {
+    pub name: String,
+    pub preferred_lines: Option<Vec<i32>>,
+    pub site_id: Option<String>,
+    /// Transport modes to keep, as named by SL (e.g. BUS, METRO, TRAM, TRAIN, SHIP)
+    #[serde(default)]
+    pub transport_modes: Option<Vec<String>>,
+    /// Destinations to keep, matched on a part of the destination name
+    #[serde(default)]
+    pub destinations: Option<Vec<String>>,
+    /// Direction of travel to keep, 1 or 2 as given by SL
+    #[serde(default)]
+    pub direction_code: Option<i32>,
+    /// Minutes needed to walk to the stop, departures that can't be reached are hidden
+    #[serde(default)]
+    pub walking_minutes: Option<u32>,
+    /// Selects this many sites closest to the weather location instead of matching the
+    /// name, which is then only a label
+    #[serde(default)]
+    pub closest: Option<usize>,
+    #[serde(default)]
+    pub match_mode: StopMatch,
+    /// Name displayed instead of the name of the selected sites
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+fn default_redis_host() -> String {
+    info!("Using default redis value");
+    "localhost".to_string()
+}
+
+fn default_redis_port() -> u16 {
+    6379
+}
+
+fn default_redis_db() -> u8 {
+    0
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// Structure to hold the Redis settings
+pub struct Redis {
+    #[serde(default = "default_redis_host")]
+    pub host: String,
+    #[serde(default = "default_redis_port")]
+    pub port: u16,
+    #[serde(default = "default_redis_db")]
+    pub db: u8,
+    #[serde(default)]
+    pub password: Option<Secret>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn currency_defaults_match_an_empty_section() {
+        assert_eq!(Currency::default(), toml::from_str::<Currency>("").unwrap());
+        assert_eq!(Currency::default().history_days, 90);
+    }
+
+    #[test]
+    fn quotes_defaults_match_an_empty_section() {
+        assert_eq!(Quotes::default(), toml::from_str::<Quotes>("").unwrap());
+        assert_eq!(Quotes::default().vs_currency, "USD");
+    }
+
+    fn at(timezone: &TimezoneData, utc: &str) -> Result<(String, String), String> {
+        let instant = utc.parse::<DateTime<Utc>>().unwrap();
+        timezone
+            .at(instant)
+            .map(|(time, abbreviation)| (time.format("%F %H:%M %:z").to_string(), abbreviation))
+    }
+
+    fn zone(zone: &str) -> TimezoneData {
+        TimezoneData {
+            zone: Some(zone.to_string()),
+            ..TimezoneData::default()
+        }
+    }
+
+    fn fixed(direction: &str, offset: f32) -> TimezoneData {
+        TimezoneData {
+            direction: direction.to_string(),
+            offset,
+            ..TimezoneData::default()
+        }
+    }
+
+    #[test]
+    fn follows_the_daylight_saving_time_of_the_zone() {
+        let stockholm = zone("Europe/Stockholm");
+        // Summer time starts at 01:00 UTC on the last Sunday of March
+        let cases = [
+            ("2024-03-31T00:59:00Z", "2024-03-31 01:59 +01:00", "CET"),
+            ("2024-03-31T01:00:00Z", "2024-03-31 03:00 +02:00", "CEST"),
+            // And ends at 01:00 UTC on the last Sunday of October
+            ("2024-10-27T00:59:00Z", "2024-10-27 02:59 +02:00", "CEST"),
+            ("2024-10-27T01:00:00Z", "2024-10-27 02:00 +01:00", "CET"),
+        ];
+        for (utc, time, abbreviation) in cases {
+            assert_eq!(
+                at(&stockholm, utc),
+                Ok((time.to_string(), abbreviation.to_string())),
+                "{utc}"
+            );
+        }
+    }
+
+    #[test]
+    fn applies_fixed_offsets_in_both_directions() {
+        let cases = [
+            (fixed("E", 5.5), "2024-03-13 05:00 +05:30", "UTC+05:30"),
+            (fixed("e", 5.5), "2024-03-13 05:00 +05:30", "UTC+05:30"),
+            (fixed("W", 10.0), "2024-03-12 13:30 -10:00", "UTC-10:00"),
+            (fixed("w", 10.0), "2024-03-12 13:30 -10:00", "UTC-10:00"),
+        ];
+        for (timezone, time, abbreviation) in cases {
+            assert_eq!(
+                at(&timezone, "2024-03-12T23:30:00Z"),
+                Ok((time.to_string(), abbreviation.to_string())),
+                "{timezone:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_timezones() {
+        assert!(at(&zone("Europe/Pariss"), "2024-03-12T12:00:00Z").is_err());
+        assert!(at(&fixed("N", 1.0), "2024-03-12T12:00:00Z").is_err());
+        assert!(at(&fixed("E", 30.0), "2024-03-12T12:00:00Z").is_err());
+    }
+}
@@ -0,0 +1,233 @@
+/// Secret settings values (api keys, passwords), which can be kept out of the settings file.
+/// A secret is either written inline, or references where to read it from:
+/// ```toml
+/// api_key = "inline value"
+/// api_key = { file = "/run/secrets/oer_api_key" }
+/// api_key = { env = "OER_API_KEY" }
+/// api_key = { credential = "oer_api_key" } # systemd LoadCredential=
+/// ```
+/// Inline values are redacted when the settings are serialized or debug printed, the
+/// references are kept as they are.
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Replaces the inline secrets in the settings dumps
+pub const REDACTED: &str = "********";
+
+/// Environment variable set by systemd to the directory holding the service credentials
+const CREDENTIALS_DIRECTORY: &str = "CREDENTIALS_DIRECTORY";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+/// Where a secret is read from
+enum SecretSource {
+    Inline(String),
+    File { file: String },
+    Env { env: String },
+    Credential { credential: String },
+}
+
+impl Default for SecretSource {
+    fn default() -> Self {
+        SecretSource::Inline(String::new())
+    }
+}
+
+#[derive(Clone, Default, PartialEq)]
+/// A secret, resolved when the settings are loaded
+pub struct Secret {
+    source: SecretSource,
+    value: String,
+}
+
+impl Secret {
+    /// Returns the value of the secret, to be sent to the service it authenticates with
+    pub fn expose(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Returns whether the secret was read from a dump of the settings, its value lost
+    pub fn is_redacted(&self) -> bool {
+        self.source == SecretSource::Inline(REDACTED.to_string())
+    }
+
+    fn resolve(source: SecretSource) -> Result<Secret, String> {
+        let value = match &source {
+            SecretSource::Inline(value) => value.clone(),
+            SecretSource::File { file } => read_secret_file(file)?,
+            SecretSource::Env { env } => std::env::var(env)
+                .map_err(|e| format!("unable to read the secret from ${env}: {e}"))?,
+            SecretSource::Credential { credential } => {
+                let directory = std::env::var(CREDENTIALS_DIRECTORY).map_err(|_| {
+                    format!(
+                        "unable to read the credential {credential}: ${CREDENTIALS_DIRECTORY} is not set, is the service started with LoadCredential= ?"
+                    )
+                })?;
+                read_secret_file(&format!("{directory}/{credential}"))?
+            }
+        };
+        Ok(Secret { source, value })
+    }
+}
+
+/// Reads a secret file, without the trailing newline most editors add
+fn read_secret_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map(|content| content.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|e| format!("unable to read the secret file {path}: {e}"))
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret {
+            source: SecretSource::Inline(value.to_string()),
+            value: value.to_string(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = SecretSource::deserialize(deserializer)?;
+        Secret::resolve(source).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.source {
+            SecretSource::Inline(value) if value.is_empty() => serializer.serialize_str(""),
+            SecretSource::Inline(_) => serializer.serialize_str(REDACTED),
+            source => source.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Secret {
+    fn schema_name() -> String {
+        "Secret".to_string()
+    }
+
+    fn json_schema(generator: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        <SecretSource as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            SecretSource::Inline(value) if value.is_empty() => write!(f, "Secret(empty)"),
+            SecretSource::Inline(_) => write!(f, "Secret({REDACTED})"),
+            SecretSource::File { file } => write!(f, "Secret(file {file})"),
+            SecretSource::Env { env } => write!(f, "Secret(env ${env})"),
+            SecretSource::Credential { credential } => {
+                write!(f, "Secret(credential {credential})")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Section {
+        api_key: Secret,
+    }
+
+    fn read(toml: &str) -> Result<Secret, String> {
+        toml::from_str::<Section>(toml)
+            .map(|section| section.api_key)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn resolves_inline_secrets() {
+        let secret = read(r#"api_key = "s3cr3t""#).unwrap();
+        assert_eq!(secret.expose(), "s3cr3t");
+        assert!(!secret.is_empty());
+        assert!(!secret.is_redacted());
+    }
+
+    #[test]
+    fn resolves_file_secrets_without_the_trailing_newline() {
+        let path = std::env::temp_dir().join(format!("homedisplay-secret-{}", std::process::id()));
+        std::fs::write(&path, "from-file\n").unwrap();
+
+        let secret = read(&format!(
+            "api_key = {{ file = {:?} }}",
+            path.to_string_lossy()
+        ))
+        .unwrap();
+        assert_eq!(secret.expose(), "from-file");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(read(&format!(
+            "api_key = {{ file = {:?} }}",
+            path.to_string_lossy()
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn resolves_env_secrets() {
+        std::env::set_var("HOMEDISPLAY_TEST_SECRET", "from-env");
+        let secret = read(r#"api_key = { env = "HOMEDISPLAY_TEST_SECRET" }"#).unwrap();
+        assert_eq!(secret.expose(), "from-env");
+
+        assert!(read(r#"api_key = { env = "HOMEDISPLAY_TEST_SECRET_UNSET" }"#).is_err());
+    }
+
+    #[test]
+    fn redacts_inline_secrets() {
+        let section = Section {
+            api_key: Secret::from("s3cr3t"),
+        };
+
+        assert_eq!(format!("{:?}", section.api_key), "Secret(********)");
+        let dumped = toml::to_string(&section).unwrap();
+        assert_eq!(dumped.trim(), r#"api_key = "********""#);
+        assert!(!dumped.contains("s3cr3t"));
+    }
+
+    #[test]
+    fn keeps_empty_secrets_and_references_in_dumps() {
+        let empty = Section {
+            api_key: Secret::default(),
+        };
+        assert_eq!(format!("{:?}", empty.api_key), "Secret(empty)");
+        assert_eq!(toml::to_string(&empty).unwrap().trim(), r#"api_key = """#);
+
+        std::env::set_var("HOMEDISPLAY_TEST_REFERENCE", "from-env");
+        let reference = Section {
+            api_key: read(r#"api_key = { env = "HOMEDISPLAY_TEST_REFERENCE" }"#).unwrap(),
+        };
+        assert_eq!(
+            format!("{:?}", reference.api_key),
+            "Secret(env $HOMEDISPLAY_TEST_REFERENCE)"
+        );
+        let dumped = toml::to_string(&reference).unwrap();
+        assert!(dumped.contains("HOMEDISPLAY_TEST_REFERENCE"));
+        assert!(!dumped.contains("from-env"));
+    }
+
+    #[test]
+    fn detects_redacted_secrets_read_back_from_a_dump() {
+        let dumped = toml::to_string(&Section {
+            api_key: Secret::from("s3cr3t"),
+        })
+        .unwrap();
+
+        let secret = read(&dumped).unwrap();
+        assert!(secret.is_redacted());
+        assert_eq!(secret.expose(), REDACTED);
+    }
+}
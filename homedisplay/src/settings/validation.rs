@@ -0,0 +1,872 @@
+/// Validation of the settings, reporting the invalid values with the path of the field
+/// holding them (e.g. `transports[2].site_id`) before they turn into failed fetches.
+use std::fmt;
+use std::path::Path;
+
+use super::secret::Secret;
+use super::{
+    BusStop, Currency, CurrencyProvider, QuoteProvider, Quotes, Redis, Route, Settings, StopMatch,
+    TimezoneData, Transit, TransitProvider, Weather,
+};
+
+/// The ISO 4217 codes of the currencies in circulation
+const ISO_4217: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD",
+    "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD",
+    "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ",
+    "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD", "JOD",
+    "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR",
+    "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR",
+    "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN",
+    "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR",
+    "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB",
+    "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS",
+    "VES", "VND", "VUV", "WST", "XAF", "XAG", "XAU", "XCD", "XCG", "XDR", "XOF", "XPD", "XPF",
+    "XPT", "YER", "ZAR", "ZMW", "ZWG",
+];
+
+/// The transport modes, as named by SL
+const TRANSPORT_MODES: &[&str] = &["BUS", "METRO", "TRAM", "TRAIN", "SHIP"];
+
+/// Largest offset from UTC in use, in hours (Line Islands)
+const MAX_UTC_OFFSET: f32 = 14.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The value can't work, e.g. a latitude of 500
+    Error,
+    /// The value works but probably not as intended, e.g. an empty stop list
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A problem found in the settings
+pub struct SettingsIssue {
+    pub severity: Severity,
+    /// Path of the field holding the value, e.g. `transports[2].site_id`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SettingsIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}: {}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// The problems found in the settings, in the order of the fields
+pub struct SettingsReport {
+    pub issues: Vec<SettingsIssue>,
+}
+
+impl SettingsReport {
+    fn error(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(SettingsIssue {
+            severity: Severity::Error,
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    fn warning(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(SettingsIssue {
+            severity: Severity::Warning,
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &SettingsIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &SettingsIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for SettingsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in self.issues.iter() {
+            writeln!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Settings {
+    /// Checks the values of the settings, without reaching any API
+    pub fn validate(&self) -> SettingsReport {
+        let mut report = SettingsReport::default();
+        validate_redis(&self.redis, &mut report);
+        validate_currency(&self.currency, &mut report);
+        validate_quotes(&self.quotes, &mut report);
+        validate_weather(&self.weather, &mut report);
+        for (index, timezone) in self.timezones.iter().enumerate() {
+            validate_timezone(timezone, &format!("timezones[{index}]"), &mut report);
+        }
+        validate_transit(&self.transit, &mut report);
+        if self.transports.is_empty() {
+            report.warning(
+                "transports",
+                "no stop is configured, no departure will be shown",
+            );
+        }
+        for (index, stop) in self.transports.iter().enumerate() {
+            validate_stop(
+                stop,
+                self.transit.provider,
+                &format!("transports[{index}]"),
+                &mut report,
+            );
+        }
+        for (index, route) in self.routes.iter().enumerate() {
+            validate_route(route, &format!("routes[{index}]"), &mut report);
+        }
+        report
+    }
+}
+
+/// Reports the secrets read back from a settings dump, their value having been redacted
+fn validate_secret(secret: &Secret, path: &str, report: &mut SettingsReport) {
+    if secret.is_redacted() {
+        report.error(
+            path,
+            "holds the redacted value of a settings dump, set the secret again or reference it with `{ file = \"...\" }`, `{ env = \"...\" }` or `{ credential = \"...\" }`",
+        );
+    }
+}
+
+fn validate_redis(redis: &Redis, report: &mut SettingsReport) {
+    if redis.host.trim().is_empty() {
+        report.error("redis.host", "must not be empty");
+    }
+    if redis.port == 0 {
+        report.error("redis.port", "must not be 0");
+    }
+    if let Some(password) = &redis.password {
+        validate_secret(password, "redis.password", report);
+    }
+}
+
+/// Checks that a currency code is made of three letters, and warns about the codes
+/// missing from ISO 4217
+fn validate_currency_code(code: &str, path: &str, report: &mut SettingsReport) {
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        report.error(
+            path,
+            format!("`{code}` is not a currency code, expected three letters (e.g. SEK)"),
+        );
+    } else if !ISO_4217.contains(&code.to_uppercase().as_str()) {
+        report.warning(
+            path,
+            format!("`{code}` is not a known ISO 4217 currency code"),
+        );
+    }
+}
+
+fn validate_currency(currency: &Currency, report: &mut SettingsReport) {
+    validate_secret(&currency.api_key, "currency.api_key", report);
+    if currency.provider == CurrencyProvider::OpenExchangeRates && currency.api_key.is_empty() {
+        report.error(
+            "currency.api_key",
+            "required by the openexchangerates provider, or use provider = \"ecb\" or \"riksbank\"",
+        );
+    }
+
+    if currency.pairs.is_empty() {
+        validate_currency_code(&currency.currency_from, "currency.currency_from", report);
+        validate_currency_code(&currency.currency_to, "currency.currency_to", report);
+    }
+    for (index, pair) in currency.pairs.iter().enumerate() {
+        validate_currency_code(&pair.from, &format!("currency.pairs[{index}].from"), report);
+        validate_currency_code(&pair.to, &format!("currency.pairs[{index}].to"), report);
+    }
+
+    if currency.history_days == 0 {
+        report.warning("currency.history_days", "no history will be kept");
+    }
+
+    for (index, alert) in currency.alerts.iter().enumerate() {
+        let path = format!("currency.alerts[{index}]");
+        validate_currency_code(&alert.from, &format!("{path}.from"), report);
+        validate_currency_code(&alert.to, &format!("{path}.to"), report);
+        if alert.above.is_none() && alert.below.is_none() && alert.daily_change.is_none() {
+            report.warning(
+                &path,
+                "sets none of `above`, `below` or `daily_change`, it never triggers",
+            );
+        }
+        if let Some(change) = alert.daily_change {
+            if change <= 0.0 {
+                report.error(
+                    format!("{path}.daily_change"),
+                    format!("must be a positive percentage (got {change})"),
+                );
+            }
+        }
+    }
+
+    if let Some(webhook) = &currency.webhook {
+        if !webhook.starts_with("http://") && !webhook.starts_with("https://") {
+            report.error(
+                "currency.webhook",
+                format!("`{webhook}` is not an http(s) URL"),
+            );
+        }
+    }
+}
+
+fn validate_quotes(quotes: &Quotes, report: &mut SettingsReport) {
+    validate_secret(&quotes.api_key, "quotes.api_key", report);
+    if quotes.provider == QuoteProvider::Local {
+        match &quotes.local_file {
+            None => report.error("quotes.local_file", "required by the local provider"),
+            Some(file) if !Path::new(file).exists() => {
+                report.warning("quotes.local_file", format!("`{file}` does not exist"))
+            }
+            Some(_) => {}
+        }
+    }
+    for (index, symbol) in quotes.symbols.iter().enumerate() {
+        if symbol.trim().is_empty() {
+            report.error(format!("quotes.symbols[{index}]"), "must not be empty");
+        }
+    }
+    validate_currency_code(&quotes.vs_currency, "quotes.vs_currency", report);
+}
+
+fn validate_weather(weather: &Weather, report: &mut SettingsReport) {
+    if !(-90.0..=90.0).contains(&weather.latitude) {
+        report.error(
+            "weather.latitude",
+            format!("must be between -90 and 90 (got {})", weather.latitude),
+        );
+    }
+    if !(-180.0..=180.0).contains(&weather.longitude) {
+        report.error(
+            "weather.longitude",
+            format!("must be between -180 and 180 (got {})", weather.longitude),
+        );
+    }
+    if weather.display_amount <= 0 {
+        report.error(
+            "weather.display_amount",
+            format!("must be positive (got {})", weather.display_amount),
+        );
+    }
+}
+
+fn validate_timezone(timezone: &TimezoneData, path: &str, report: &mut SettingsReport) {
+    if let Some(zone) = &timezone.zone {
+        if zone.parse::<chrono_tz::Tz>().is_err() {
+            report.error(
+                format!("{path}.zone"),
+                format!("`{zone}` is not an IANA timezone, e.g. Europe/Paris or America/New_York"),
+            );
+        }
+        if timezone.name.trim().is_empty() {
+            report.warning(format!("{path}.name"), "is empty, the clock has no label");
+        }
+        return;
+    }
+    report.warning(
+        format!("{path}.zone"),
+        "unset, the fixed offset ignores daylight saving time, set an IANA name (e.g. Europe/Paris) instead",
+    );
+    if !matches!(timezone.direction.to_uppercase().as_str(), "E" | "W") {
+        report.error(
+            format!("{path}.direction"),
+            format!("must be \"E\" or \"W\" (got \"{}\")", timezone.direction),
+        );
+    }
+    if !(0.0..=MAX_UTC_OFFSET).contains(&timezone.offset) {
+        report.error(
+            format!("{path}.offset"),
+            format!(
+                "must be between 0 and {MAX_UTC_OFFSET} hours, the sign being given by `direction` (got {})",
+                timezone.offset
+            ),
+        );
+    }
+    if timezone.name.trim().is_empty() {
+        report.warning(format!("{path}.name"), "is empty, the clock has no label");
+    }
+}
+
+fn validate_transit(transit: &Transit, report: &mut SettingsReport) {
+    validate_secret(&transit.api_key, "transit.api_key", report);
+    match transit.provider {
+        TransitProvider::ResRobot if transit.api_key.is_empty() => {
+            report.error("transit.api_key", "required by the resrobot provider")
+        }
+        TransitProvider::Gtfs => match &transit.gtfs_static {
+            None => report.error("transit.gtfs_static", "required by the gtfs provider"),
+            Some(path) if !Path::new(path).exists() => {
+                report.error("transit.gtfs_static", format!("`{path}` does not exist"))
+            }
+            Some(_) => {}
+        },
+        _ => {}
+    }
+
+    for (index, feed) in transit.gtfs_realtime.iter().enumerate() {
+        let path = format!("transit.gtfs_realtime[{index}]");
+        if transit.provider != TransitProvider::Gtfs {
+            report.warning(&path, "only used by the gtfs provider");
+        } else if !feed.starts_with("http://")
+            && !feed.starts_with("https://")
+            && !Path::new(feed).exists()
+        {
+            report.warning(
+                &path,
+                format!("`{feed}` is neither a URL nor an existing file"),
+            );
+        }
+    }
+}
+
+/// Returns whether the id is a valid site id for the provider. SL and ResRobot use
+/// numeric ids, GTFS feeds are free to use any string.
+fn is_site_id(id: &str, provider: TransitProvider) -> bool {
+    match provider {
+        TransitProvider::Gtfs => !id.trim().is_empty(),
+        TransitProvider::Sl | TransitProvider::ResRobot => {
+            !id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+        }
+    }
+}
+
+fn validate_stop(
+    stop: &BusStop,
+    provider: TransitProvider,
+    path: &str,
+    report: &mut SettingsReport,
+) {
+    if stop.name.trim().is_empty() {
+        report.error(format!("{path}.name"), "must not be empty");
+    }
+
+    match &stop.site_id {
+        Some(id) if !is_site_id(id, provider) => report.error(
+            format!("{path}.site_id"),
+            format!("`{id}` is not a valid {provider} site id"),
+        ),
+        None if stop.match_mode == StopMatch::Id && stop.closest.is_none() => report.error(
+            format!("{path}.site_id"),
+            "required when `match_mode` is \"id\"",
+        ),
+        _ => {}
+    }
+
+    match stop.closest {
+        Some(0) => report.error(format!("{path}.closest"), "must select at least one site"),
+        Some(_) if stop.site_id.is_some() || stop.match_mode != StopMatch::Contains => report
+            .warning(
+                format!("{path}.closest"),
+                "selects the sites by distance, `site_id` and `match_mode` are ignored",
+            ),
+        _ => {}
+    }
+
+    if let Some(modes) = &stop.transport_modes {
+        for (index, mode) in modes.iter().enumerate() {
+            if !TRANSPORT_MODES.contains(&mode.to_uppercase().as_str()) {
+                report.error(
+                    format!("{path}.transport_modes[{index}]"),
+                    format!(
+                        "unknown transport mode `{mode}`, expected one of {}",
+                        TRANSPORT_MODES.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    if let Some(code) = stop.direction_code {
+        if code != 1 && code != 2 {
+            report.error(
+                format!("{path}.direction_code"),
+                format!("must be 1 or 2 (got {code})"),
+            );
+        }
+    }
+
+    if let Some(lines) = &stop.preferred_lines {
+        for (index, line) in lines.iter().enumerate() {
+            if *line <= 0 {
+                report.error(
+                    format!("{path}.preferred_lines[{index}]"),
+                    format!("must be a positive line number (got {line})"),
+                );
+            }
+        }
+    }
+
+    if let Some(name) = &stop.display_name {
+        if name.trim().is_empty() {
+            report.warning(
+                format!("{path}.display_name"),
+                "is empty, the stop has no label",
+            );
+        }
+    }
+}
+
+fn validate_route(route: &Route, path: &str, report: &mut SettingsReport) {
+    if route.name.trim().is_empty() {
+        report.warning(format!("{path}.name"), "is empty, the route has no label");
+    }
+    for (field, id) in [
+        ("origin", &route.origin),
+        ("destination", &route.destination),
+    ] {
+        if !is_site_id(id, TransitProvider::Sl) {
+            report.error(
+                format!("{path}.{field}"),
+                format!("`{id}` is not an SL site id or journey planner stop id"),
+            );
+        }
+    }
+    if route.origin == route.destination {
+        report.warning(path, "starts and ends at the same stop");
+    }
+    if route.trips == 0 {
+        report.error(format!("{path}.trips"), "must plan at least one itinerary");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::secret::REDACTED;
+    use super::super::{CurrencyAlert, CurrencyPair};
+    use super::*;
+
+    /// Settings without any issue, each test breaking one section of them
+    fn settings() -> Settings {
+        toml::from_str(
+            r#"
+            timezones = [{ zone = "Europe/Paris", name = "Paris" }]
+
+            [redis]
+            [currency]
+            provider = "ecb"
+            [quotes]
+            [weather]
+
+            [[transports]]
+            name = "Slussen"
+            "#,
+        )
+        .unwrap()
+    }
+
+    /// Returns the severity and path of the issues of the report, in order
+    fn issues(settings: &Settings) -> Vec<(Severity, String)> {
+        settings
+            .validate()
+            .issues
+            .into_iter()
+            .map(|issue| (issue.severity, issue.path))
+            .collect()
+    }
+
+    fn error(path: &str) -> (Severity, String) {
+        (Severity::Error, path.to_string())
+    }
+
+    fn warning(path: &str) -> (Severity, String) {
+        (Severity::Warning, path.to_string())
+    }
+
+    fn sample_feed() -> String {
+        format!("{}/tests/gtfs_sample", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn valid_settings_have_no_issue() {
+        let report = settings().validate();
+        assert!(report.is_empty(), "{report}");
+        assert!(!report.has_errors());
+    }
+
+    #[test]
+    fn reports_the_path_and_severity_of_the_issues() {
+        let mut settings = settings();
+        settings.redis.port = 0;
+        settings.currency.history_days = 0;
+
+        let report = settings.validate();
+        assert!(report.has_errors());
+        assert_eq!(report.errors().count(), 1);
+        assert_eq!(report.warnings().count(), 1);
+        assert_eq!(
+            report.to_string(),
+            "error: redis.port: must not be 0\nwarning: currency.history_days: no history will be kept\n"
+        );
+    }
+
+    #[test]
+    fn validates_redis() {
+        let mut settings = settings();
+        settings.redis.host = " ".to_string();
+        settings.redis.port = 0;
+        settings.redis.password = Some(Secret::from(REDACTED));
+
+        assert_eq!(
+            issues(&settings),
+            vec![
+                error("redis.host"),
+                error("redis.port"),
+                error("redis.password")
+            ]
+        );
+    }
+
+    #[test]
+    fn requires_an_api_key_for_openexchangerates() {
+        let mut settings = settings();
+        settings.currency.provider = CurrencyProvider::OpenExchangeRates;
+        assert_eq!(issues(&settings), vec![error("currency.api_key")]);
+
+        settings.currency.api_key = Secret::from(REDACTED);
+        assert_eq!(issues(&settings), vec![error("currency.api_key")]);
+
+        settings.currency.api_key = Secret::from("key");
+        assert!(issues(&settings).is_empty());
+    }
+
+    #[test]
+    fn validates_the_currency_codes() {
+        let mut settings = settings();
+        settings.currency.currency_from = "SE1".to_string();
+        settings.currency.currency_to = "ABC".to_string();
+        assert_eq!(
+            issues(&settings),
+            vec![
+                error("currency.currency_from"),
+                warning("currency.currency_to")
+            ]
+        );
+
+        // The pairs replace the single pair
+        settings.currency.pairs = vec![CurrencyPair {
+            from: "sek".to_string(),
+            to: "EURO".to_string(),
+        }];
+        assert_eq!(issues(&settings), vec![error("currency.pairs[0].to")]);
+    }
+
+    #[test]
+    fn validates_the_currency_history_alerts_and_webhook() {
+        let mut settings = settings();
+        settings.currency.history_days = 0;
+        settings.currency.alerts = vec![
+            CurrencyAlert {
+                from: "SEK".to_string(),
+                to: "EUR".to_string(),
+                ..CurrencyAlert::default()
+            },
+            CurrencyAlert {
+                from: "SEK".to_string(),
+                to: "XYZ".to_string(),
+                daily_change: Some(-1.0),
+                ..CurrencyAlert::default()
+            },
+        ];
+        settings.currency.webhook = Some("ftp://example.com".to_string());
+
+        assert_eq!(
+            issues(&settings),
+            vec![
+                warning("currency.history_days"),
+                warning("currency.alerts[0]"),
+                warning("currency.alerts[1].to"),
+                error("currency.alerts[1].daily_change"),
+                error("currency.webhook"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_quotes() {
+        let mut settings = settings();
+        settings.quotes.provider = QuoteProvider::Local;
+        settings.quotes.symbols = vec!["BTC".to_string(), " ".to_string()];
+        settings.quotes.vs_currency = "US".to_string();
+        settings.quotes.api_key = Secret::from(REDACTED);
+        assert_eq!(
+            issues(&settings),
+            vec![
+                error("quotes.api_key"),
+                error("quotes.local_file"),
+                error("quotes.symbols[1]"),
+                error("quotes.vs_currency"),
+            ]
+        );
+
+        let mut settings = self::settings();
+        settings.quotes.provider = QuoteProvider::Local;
+        settings.quotes.local_file = Some("/nonexistent/quotes.json".to_string());
+        assert_eq!(issues(&settings), vec![warning("quotes.local_file")]);
+    }
+
+    #[test]
+    fn validates_weather() {
+        let mut settings = settings();
+        settings.weather.latitude = 91.0;
+        settings.weather.longitude = -180.5;
+        settings.weather.display_amount = 0;
+
+        assert_eq!(
+            issues(&settings),
+            vec![
+                error("weather.latitude"),
+                error("weather.longitude"),
+                error("weather.display_amount"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_the_iana_timezones() {
+        let mut settings = settings();
+        settings.timezones = vec![
+            TimezoneData {
+                zone: Some("Europe/Pariss".to_string()),
+                name: "Paris".to_string(),
+                ..TimezoneData::default()
+            },
+            TimezoneData {
+                zone: Some("Asia/Tokyo".to_string()),
+                ..TimezoneData::default()
+            },
+        ];
+
+        assert_eq!(
+            issues(&settings),
+            vec![error("timezones[0].zone"), warning("timezones[1].name")]
+        );
+    }
+
+    #[test]
+    fn validates_the_fixed_offset_timezones() {
+        let mut settings = settings();
+        settings.timezones = vec![
+            TimezoneData {
+                direction: "W".to_string(),
+                offset: 5.0,
+                name: "New York".to_string(),
+                ..TimezoneData::default()
+            },
+            TimezoneData {
+                direction: "e".to_string(),
+                offset: 5.5,
+                name: "Mumbai".to_string(),
+                ..TimezoneData::default()
+            },
+            TimezoneData {
+                direction: "N".to_string(),
+                offset: 15.0,
+                ..TimezoneData::default()
+            },
+        ];
+
+        assert_eq!(
+            issues(&settings),
+            vec![
+                warning("timezones[0].zone"),
+                warning("timezones[1].zone"),
+                warning("timezones[2].zone"),
+                error("timezones[2].direction"),
+                error("timezones[2].offset"),
+                warning("timezones[2].name"),
+            ]
+        );
+    }
+
+    #[test]
+    fn requires_an_api_key_for_resrobot() {
+        let mut settings = settings();
+        settings.transit.provider = TransitProvider::ResRobot;
+        assert_eq!(issues(&settings), vec![error("transit.api_key")]);
+
+        settings.transit.api_key = Secret::from(REDACTED);
+        assert_eq!(issues(&settings), vec![error("transit.api_key")]);
+    }
+
+    #[test]
+    fn requires_an_existing_feed_for_gtfs() {
+        let mut settings = settings();
+        settings.transit.provider = TransitProvider::Gtfs;
+        assert_eq!(issues(&settings), vec![error("transit.gtfs_static")]);
+
+        settings.transit.gtfs_static = Some("/nonexistent/gtfs.zip".to_string());
+        assert_eq!(issues(&settings), vec![error("transit.gtfs_static")]);
+
+        settings.transit.gtfs_static = Some(sample_feed());
+        settings.transit.gtfs_realtime = vec![
+            "https://example.com/trip-updates.pb".to_string(),
+            "/nonexistent/trip-updates.pb".to_string(),
+        ];
+        assert_eq!(issues(&settings), vec![warning("transit.gtfs_realtime[1]")]);
+    }
+
+    #[test]
+    fn warns_about_realtime_feeds_outside_gtfs() {
+        let mut settings = settings();
+        settings.transit.gtfs_realtime = vec!["https://example.com/trip-updates.pb".to_string()];
+
+        assert_eq!(issues(&settings), vec![warning("transit.gtfs_realtime[0]")]);
+    }
+
+    #[test]
+    fn warns_about_missing_stops() {
+        let mut settings = settings();
+        settings.transports.clear();
+
+        assert_eq!(issues(&settings), vec![warning("transports")]);
+    }
+
+    #[test]
+    fn validates_the_stop_names_and_site_ids() {
+        let mut settings = settings();
+        settings.transports = vec![
+            BusStop {
+                name: " ".to_string(),
+                site_id: Some("9192a".to_string()),
+                ..BusStop::default()
+            },
+            BusStop {
+                name: "Odenplan".to_string(),
+                match_mode: StopMatch::Id,
+                ..BusStop::default()
+            },
+            BusStop {
+                name: "Odenplan".to_string(),
+                site_id: Some("9117".to_string()),
+                match_mode: StopMatch::Id,
+                display_name: Some(" ".to_string()),
+                ..BusStop::default()
+            },
+        ];
+
+        assert_eq!(
+            issues(&settings),
+            vec![
+                error("transports[0].name"),
+                error("transports[0].site_id"),
+                error("transports[1].site_id"),
+                warning("transports[2].display_name"),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_any_site_id_from_gtfs() {
+        let mut settings = settings();
+        settings.transit.provider = TransitProvider::Gtfs;
+        settings.transit.gtfs_static = Some(sample_feed());
+        settings.transports[0].site_id = Some("STOP-A".to_string());
+        assert!(issues(&settings).is_empty());
+
+        settings.transports[0].site_id = Some(" ".to_string());
+        assert_eq!(issues(&settings), vec![error("transports[0].site_id")]);
+    }
+
+    #[test]
+    fn validates_the_closest_stops() {
+        let mut settings = settings();
+        settings.transports = vec![
+            BusStop {
+                name: "Nearby".to_string(),
+                closest: Some(0),
+                ..BusStop::default()
+            },
+            BusStop {
+                name: "Nearby".to_string(),
+                closest: Some(2),
+                match_mode: StopMatch::Id,
+                ..BusStop::default()
+            },
+            BusStop {
+                name: "Nearby".to_string(),
+                closest: Some(2),
+                ..BusStop::default()
+            },
+        ];
+
+        assert_eq!(
+            issues(&settings),
+            vec![
+                error("transports[0].closest"),
+                warning("transports[1].closest")
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_the_stop_filters() {
+        let mut settings = settings();
+        settings.transports[0].transport_modes = Some(vec!["bus".to_string(), "PLANE".to_string()]);
+        settings.transports[0].direction_code = Some(3);
+        settings.transports[0].preferred_lines = Some(vec![4, 0]);
+
+        assert_eq!(
+            issues(&settings),
+            vec![
+                error("transports[0].transport_modes[1]"),
+                error("transports[0].direction_code"),
+                error("transports[0].preferred_lines[1]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_routes() {
+        let mut settings = settings();
+        settings.routes = vec![
+            Route {
+                name: "Home → Office".to_string(),
+                origin: "9192".to_string(),
+                destination: "9117".to_string(),
+                trips: 3,
+            },
+            Route {
+                name: " ".to_string(),
+                origin: "Slussen".to_string(),
+                destination: "Slussen".to_string(),
+                trips: 0,
+            },
+        ];
+
+        assert_eq!(
+            issues(&settings),
+            vec![
+                warning("routes[1].name"),
+                error("routes[1].origin"),
+                error("routes[1].destination"),
+                warning("routes[1]"),
+                error("routes[1].trips"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+/// Watches the settings file and reloads the settings when it changes, so that a running
+/// display picks up a new stop or location without being restarted.
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+
+use super::loader::SettingsLoader;
+use super::validation::Severity;
+use super::Settings;
+
+/// Editors write a file in several steps, the settings are reloaded once the file has been
+/// left alone for this long
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Serialize, Default, Clone, Copy, PartialEq)]
+/// The sections that differ between two versions of the settings
+pub struct SettingsChanges {
+    pub redis: bool,
+    pub currency: bool,
+    pub quotes: bool,
+    pub weather: bool,
+    pub timezones: bool,
+    /// The transit provider, the stops or the routes
+    pub transports: bool,
+}
+
+impl SettingsChanges {
+    pub fn between(old: &Settings, new: &Settings) -> SettingsChanges {
+        SettingsChanges {
+            redis: differs(&old.redis, &new.redis),
+            currency: differs(&old.currency, &new.currency),
+            quotes: differs(&old.quotes, &new.quotes),
+            weather: differs(&old.weather, &new.weather),
+            timezones: differs(&old.timezones, &new.timezones),
+            transports: differs(&old.transit, &new.transit)
+                || differs(&old.transports, &new.transports)
+                || differs(&old.routes, &new.routes),
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        self.redis
+            || self.currency
+            || self.quotes
+            || self.weather
+            || self.timezones
+            || self.transports
+    }
+}
+
+fn differs<T: PartialEq>(old: &T, new: &T) -> bool {
+    old != new
+}
+
+/// Watches the settings file until dropped
+pub struct SettingsWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl SettingsWatcher {
+    /// Watches the settings file read by the loader. On each change the settings are loaded
+    /// again and validated: invalid settings are reported and ignored, the others are given
+    /// to `on_change` along with the sections that changed since `current`.
+    pub fn watch<F>(
+        filename: &str,
+        loader: SettingsLoader,
+        current: Settings,
+        on_change: F,
+    ) -> Result<SettingsWatcher, String>
+    where
+        F: Fn(Settings, SettingsChanges) + Send + 'static,
+    {
+        let path = PathBuf::from(filename);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_os_string())
+            .ok_or_else(|| format!("{filename} is not a file"))?;
+        // The directory is watched rather than the file, as editors often replace the file
+        // instead of writing into it
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (tx, rx) = mpsc::channel::<()>();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) => {
+                    let relevant = !matches!(event.kind, EventKind::Access(_))
+                        && event
+                            .paths
+                            .iter()
+                            .any(|path| path.file_name() == Some(file_name.as_os_str()));
+                    if relevant {
+                        let _ = tx.send(());
+                    }
+                }
+                Err(e) => warn!("Settings watcher: {}", e),
+            })
+            .map_err(|e| format!("Unable to watch the settings: {e}"))?;
+        watcher
+            .watch(&directory, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Unable to watch {}: {e}", directory.display()))?;
+        info!("Watching {} for changes", path.display());
+
+        thread::spawn(move || {
+            let mut current = current;
+            // Ends when the watcher, and with it the sender, is dropped
+            while rx.recv().is_ok() {
+                while rx.recv_timeout(DEBOUNCE).is_ok() {}
+                if let Some((settings, changes)) = reload(&path, &loader, &current) {
+                    current = settings.clone();
+                    on_change(settings, changes);
+                }
+            }
+        });
+
+        Ok(SettingsWatcher { _watcher: watcher })
+    }
+}
+
+/// Loads and validates the settings, returning them when they are valid and differ from
+/// the current ones
+fn reload(
+    path: &Path,
+    loader: &SettingsLoader,
+    current: &Settings,
+) -> Option<(Settings, SettingsChanges)> {
+    if !path.exists() {
+        // Removed, or in the middle of being replaced
+        return None;
+    }
+    let settings = match loader.load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("Settings reload: unable to load the settings, keeping the current ones: {e}");
+            return None;
+        }
+    };
+
+    let report = settings.validate();
+    for issue in report.issues.iter() {
+        match issue.severity {
+            Severity::Error => error!("Settings reload: invalid settings: {}", issue),
+            Severity::Warning => warn!("Settings reload: suspicious settings: {}", issue),
+        }
+    }
+    if report.has_errors() {
+        error!("Settings reload: keeping the current settings until the errors are fixed");
+        return None;
+    }
+
+    let changes = SettingsChanges::between(current, &settings);
+    if !changes.any() {
+        return None;
+    }
+    info!("Settings reload: applying {:?}", changes);
+    Some((settings, changes))
+}
+
+impl std::fmt::Debug for SettingsWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SettingsWatcher").finish_non_exhaustive()
+    }
+}
@@ -1,9 +1,14 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use chrono_tz::Europe::Stockholm;
 use reqwest::header::CONTENT_TYPE;
 use reqwest::Url;
 use serde_derive::{Deserialize, Serialize};
 
-use crate::models::transports::{Coordinates, Departure, Site};
+use crate::models::transports::{
+    Coordinates, Departure, Itinerary, ItineraryLeg, Site, TrafficDeviation,
+};
+use crate::settings;
 use crate::traits::Api;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -113,3 +118,336 @@ impl Api<String, Vec<Departure>> for Departure {
         }
     }
 }
+
+#[derive(Deserialize, Debug)]
+struct DeviationVariantAPI {
+    pub header: String,
+    #[serde(default)]
+    pub details: String,
+    #[serde(default)]
+    pub language: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct DeviationPriorityAPI {
+    #[serde(default)]
+    pub importance_level: i32,
+}
+
+#[derive(Deserialize, Debug)]
+struct DeviationPublishAPI {
+    #[serde(default)]
+    pub upto: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DeviationStopAreaAPI {
+    pub id: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct DeviationLineAPI {
+    pub id: i32,
+    #[serde(default)]
+    pub designation: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct DeviationScopeAPI {
+    #[serde(default)]
+    pub stop_areas: Vec<DeviationStopAreaAPI>,
+    #[serde(default)]
+    pub lines: Vec<DeviationLineAPI>,
+}
+
+#[derive(Deserialize, Debug)]
+/// A message of the SL Deviations API
+struct DeviationAPI {
+    pub deviation_case_id: i64,
+    #[serde(default)]
+    pub priority: DeviationPriorityAPI,
+    pub publish: Option<DeviationPublishAPI>,
+    #[serde(default)]
+    pub message_variants: Vec<DeviationVariantAPI>,
+    #[serde(default)]
+    pub scope: DeviationScopeAPI,
+}
+
+impl DeviationAPI {
+    /// Converts the message into a deviation, using its english variant when available.
+    /// Messages without any variant are skipped.
+    fn into_deviation(self) -> Option<TrafficDeviation> {
+        let variant = self
+            .message_variants
+            .iter()
+            .find(|variant| variant.language == "en")
+            .or(self.message_variants.first())?;
+
+        Some(TrafficDeviation {
+            id: self.deviation_case_id,
+            header: variant.header.clone(),
+            details: variant.details.clone(),
+            importance_level: self.priority.importance_level,
+            site_ids: self
+                .scope
+                .stop_areas
+                .iter()
+                .map(|area| area.id.to_string())
+                .collect(),
+            line_ids: self.scope.lines.iter().map(|line| line.id).collect(),
+            lines: self
+                .scope
+                .lines
+                .iter()
+                .map(|line| line.designation.clone().unwrap_or(line.id.to_string()))
+                .collect(),
+            publish_upto: self
+                .publish
+                .and_then(|publish| publish.upto)
+                .and_then(|upto| upto.parse::<chrono::DateTime<chrono::FixedOffset>>().ok())
+                .map(|upto| upto.naive_local()),
+        })
+    }
+}
+
+#[async_trait]
+impl Api<(), Vec<TrafficDeviation>> for TrafficDeviation {
+    /// Returns the deviations currently published by SL, for the whole network
+    async fn api_get(_: ()) -> Result<Vec<TrafficDeviation>, String> {
+        let url: Url =
+            match Url::parse("https://deviations.integration.sl.se/v1/messages?future=false") {
+                Ok(url) => url,
+                Err(err) => return Err(format!("Could not parse URL: {err}")),
+            };
+
+        let client = reqwest::Client::new();
+        let result = match client
+            .get(url)
+            .header(CONTENT_TYPE, "application/json")
+            .send()
+            .await
+        {
+            Ok(resp) => resp,
+            Err(err) => return Err(format!("Unable to fetch deviations, Err: {err}")),
+        };
+
+        let result_status = result.status();
+        let result_body = match result.text().await {
+            Ok(body) => body,
+            Err(err) => return Err(format!("Unable to fetch deviations, Err: {err}")),
+        };
+
+        match result_status {
+            reqwest::StatusCode::OK => {
+                match serde_json::from_str::<Vec<DeviationAPI>>(&result_body) {
+                    Ok(data) => Ok(data
+                        .into_iter()
+                        .filter_map(DeviationAPI::into_deviation)
+                        .collect()),
+                    Err(e) => Err(format!("Error while fetching deviations: {e}")),
+                }
+            }
+            status => Err(format!(
+                "Uh oh! Something unexpected happened while fetching deviations: {status}"
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct JourneysAPI {
+    #[serde(default)]
+    journeys: Vec<JourneyAPI>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct JourneyAPI {
+    legs: Vec<LegAPI>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct LegAPI {
+    origin: LegStopAPI,
+    destination: LegStopAPI,
+    transportation: Option<TransportationAPI>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LegStopAPI {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    disassembled_name: Option<String>,
+    departure_time_planned: Option<DateTime<Utc>>,
+    departure_time_estimated: Option<DateTime<Utc>>,
+    arrival_time_planned: Option<DateTime<Utc>>,
+    arrival_time_estimated: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TransportationAPI {
+    #[serde(default)]
+    disassembled_name: Option<String>,
+    #[serde(default)]
+    number: Option<String>,
+    product: Option<ProductAPI>,
+    destination: Option<ProductDestinationAPI>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ProductAPI {
+    #[serde(default)]
+    class: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ProductDestinationAPI {
+    #[serde(default)]
+    name: String,
+}
+
+/// Returns the transport mode of a journey planner product class, `None` for walks
+fn product_mode(class: i32) -> Option<&'static str> {
+    match class {
+        0 | 1 => Some("TRAIN"),
+        2 => Some("METRO"),
+        4 => Some("TRAM"),
+        5 | 7 => Some("BUS"),
+        9 => Some("SHIP"),
+        99 | 100 => None, // Footpaths
+        _ => Some("BUS"),
+    }
+}
+
+impl LegStopAPI {
+    /// Returns the stop name without the municipality prefix
+    fn short_name(&self) -> String {
+        self.disassembled_name.clone().unwrap_or(self.name.clone())
+    }
+}
+
+impl LegAPI {
+    fn into_leg(self) -> Option<ItineraryLeg> {
+        let departure = self
+            .origin
+            .departure_time_estimated
+            .or(self.origin.departure_time_planned)?;
+        let arrival = self
+            .destination
+            .arrival_time_estimated
+            .or(self.destination.arrival_time_planned)?;
+        let transportation = self.transportation.filter(|transportation| {
+            transportation
+                .product
+                .as_ref()
+                .and_then(|product| product_mode(product.class))
+                .is_some()
+        });
+
+        Some(ItineraryLeg {
+            origin: self.origin.short_name(),
+            destination: self.destination.short_name(),
+            // Stockholm wall-clock times, as the departures
+            departure: departure.with_timezone(&Stockholm).naive_local(),
+            arrival: arrival.with_timezone(&Stockholm).naive_local(),
+            line: transportation.as_ref().and_then(|transportation| {
+                transportation
+                    .disassembled_name
+                    .clone()
+                    .or(transportation.number.clone())
+            }),
+            transport_mode: transportation
+                .as_ref()
+                .and_then(|transportation| transportation.product.as_ref())
+                .and_then(|product| product_mode(product.class))
+                .map(str::to_string),
+            direction: transportation
+                .and_then(|transportation| transportation.destination)
+                .map(|destination| destination.name),
+        })
+    }
+}
+
+/// Returns the journey planner id of a stop: SL site ids are expanded to the global stop
+/// id, other ids are used as is
+fn journey_planner_id(id: &str) -> String {
+    match id.parse::<u32>() {
+        Ok(site_id) if site_id < 1_000_000 => format!("9091001000{site_id:06}"),
+        _ => id.to_string(),
+    }
+}
+
+#[async_trait]
+impl Api<settings::Route, Vec<Itinerary>> for Itinerary {
+    /// Returns the next itineraries of the route from the SL journey planner
+    async fn api_get(route: settings::Route) -> Result<Vec<Itinerary>, String> {
+        let url: Url = match Url::parse_with_params(
+            "https://journeyplanner.integration.sl.se/v2/trips",
+            &[
+                ("type_origin", "any".to_string()),
+                ("name_origin", journey_planner_id(&route.origin)),
+                ("type_destination", "any".to_string()),
+                ("name_destination", journey_planner_id(&route.destination)),
+                ("calc_number_of_trips", route.trips.to_string()),
+            ],
+        ) {
+            Ok(url) => url,
+            Err(err) => return Err(format!("Could not parse URL: {err}")),
+        };
+
+        let client = reqwest::Client::new();
+        let result = match client
+            .get(url)
+            .header(CONTENT_TYPE, "application/json")
+            .send()
+            .await
+        {
+            Ok(resp) => resp,
+            Err(err) => {
+                return Err(format!(
+                    "Unable to fetch trips of {}, Err: {err}",
+                    route.name
+                ))
+            }
+        };
+
+        let result_status = result.status();
+        let result_body = match result.text().await {
+            Ok(body) => body,
+            Err(err) => {
+                return Err(format!(
+                    "Unable to fetch trips of {}, Err: {err}",
+                    route.name
+                ))
+            }
+        };
+
+        match result_status {
+            reqwest::StatusCode::OK => match serde_json::from_str::<JourneysAPI>(&result_body) {
+                Ok(data) => Ok(data
+                    .journeys
+                    .into_iter()
+                    .filter_map(|journey| {
+                        let legs: Vec<ItineraryLeg> = journey
+                            .legs
+                            .into_iter()
+                            .map(LegAPI::into_leg)
+                            .collect::<Option<Vec<ItineraryLeg>>>()?;
+                        Some(Itinerary {
+                            departure: legs.first()?.departure,
+                            arrival: legs.last()?.arrival,
+                            legs,
+                        })
+                    })
+                    .collect()),
+                Err(e) => Err(format!("Error while fetching trips of {}: {e}", route.name)),
+            },
+            status => Err(format!(
+                "Uh oh! Something unexpected happened while fetching trips of {}: {status}",
+                route.name
+            )),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+/// In-memory index of all the sites of a transit provider, for id lookups and fuzzy,
+/// accent-insensitive name searches
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::models::transports::Site;
+use crate::settings::BusStop;
+
+use super::filters;
+
+/// Minimal Jaro-Winkler similarity for a name to match a misspelled query
+const FUZZY_THRESHOLD: f64 = 0.85;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+/// The sites of a provider, as stored in the database
+pub struct SiteCatalogue {
+    pub sites: Vec<Site>,
+    pub freshness: u64, // Time the sites were fetched from the provider
+    /// Time before which a failed refresh of the catalogue is not attempted again
+    #[serde(default)]
+    pub retry_after: u64,
+    #[serde(skip)]
+    index: CatalogueIndex,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CatalogueIndex {
+    by_id: HashMap<String, usize>,
+    names: Vec<String>, // Normalized names, in the order of the sites
+}
+
+impl SiteCatalogue {
+    /// Builds the catalogue and its index
+    pub fn new(sites: Vec<Site>, freshness: u64) -> SiteCatalogue {
+        SiteCatalogue {
+            sites,
+            freshness,
+            retry_after: 0,
+            index: CatalogueIndex::default(),
+        }
+        .indexed()
+    }
+
+    /// Returns the catalogue with the given sites added, replacing the sites with the same
+    /// id, for providers that only return the sites matching a query
+    pub fn merged(&self, sites: Vec<Site>, freshness: u64) -> SiteCatalogue {
+        let mut merged: Vec<Site> = self
+            .sites
+            .iter()
+            .filter(|site| !sites.iter().any(|new_site| new_site.id == site.id))
+            .cloned()
+            .collect();
+        merged.extend(sites);
+        SiteCatalogue::new(merged, freshness)
+    }
+
+    /// Builds the index of the sites, needed after deserializing the catalogue
+    pub fn indexed(mut self) -> SiteCatalogue {
+        self.index = CatalogueIndex {
+            by_id: self
+                .sites
+                .iter()
+                .enumerate()
+                .map(|(position, site)| (site.id.clone(), position))
+                .collect(),
+            names: self
+                .sites
+                .iter()
+                .map(|site| filters::normalize(&site.name))
+                .collect(),
+        };
+        self
+    }
+
+    /// Returns the site with the given id
+    pub fn get(&self, id: &str) -> Option<&Site> {
+        self.index
+            .by_id
+            .get(id)
+            .and_then(|position| self.sites.get(*position))
+    }
+
+    /// Returns the sites selected by the stops of the settings, or all of them when there
+    /// are no stops. Sites are named after the display name of their stop, when it has one.
+    pub fn select(&self, stops: &[BusStop]) -> Vec<Site> {
+        self.sites
+            .iter()
+            .filter(|site| {
+                stops.is_empty() || stops.iter().any(|stop| filters::matches_site(stop, site))
+            })
+            .map(|site| {
+                let display_name = filters::stop_for_site(stops, &site.id, Some(&site.name))
+                    .and_then(|stop| stop.display_name.clone());
+                Site {
+                    name: display_name.unwrap_or(site.name.clone()),
+                    ..site.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the sites selected by the given stop
+    pub fn matches(&self, stop: &BusStop) -> Vec<&Site> {
+        self.sites
+            .iter()
+            .filter(|site| filters::matches_site(stop, site))
+            .collect()
+    }
+
+    /// Returns whether every stop matching by name selects at least one site of the catalogue
+    pub fn covers(&self, stops: &[BusStop]) -> bool {
+        stops
+            .iter()
+            .filter(|stop| stop.closest.is_none())
+            .all(|stop| {
+                self.sites
+                    .iter()
+                    .any(|site| filters::matches_site(stop, site))
+            })
+    }
+
+    /// Returns the sites within the radius (in meters) of the given point with their distance,
+    /// closest first. Sites without coordinates are left out.
+    pub fn nearest(&self, latitude: f64, longitude: f64, radius: f64) -> Vec<(&Site, f64)> {
+        let mut sites: Vec<(&Site, f64)> = self
+            .sites
+            .iter()
+            .filter(|site| site.coord.is_known())
+            .map(|site| (site, site.coord.distance_to(latitude, longitude)))
+            .filter(|(_, distance)| *distance <= radius)
+            .collect();
+        sites.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        sites
+    }
+
+    /// Returns the sites whose name matches the query, best matches first. Names equal to,
+    /// starting with or containing the query rank first, then names close to it, so that
+    /// `sodermalm` finds `Södermalmstorg` and `slusen` finds `Slussen`.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&Site> {
+        let query = filters::normalize(query.trim());
+        if query.is_empty() {
+            return vec![];
+        }
+
+        let mut matches: Vec<(f64, usize)> = self
+            .index
+            .names
+            .iter()
+            .enumerate()
+            .filter_map(|(position, name)| {
+                let score = if *name == query {
+                    4.0
+                } else if name.starts_with(&query) {
+                    3.0
+                } else if name.contains(&query) {
+                    2.0
+                } else {
+                    let similarity = name
+                        .split(|c: char| !c.is_alphanumeric())
+                        .map(|word| strsim::jaro_winkler(word, &query))
+                        .fold(strsim::jaro_winkler(name, &query), f64::max);
+                    if similarity < FUZZY_THRESHOLD {
+                        return None;
+                    }
+                    similarity
+                };
+                Some((score, position))
+            })
+            .collect();
+
+        matches.sort_by(|(score_a, a), (score_b, b)| {
+            score_b
+                .total_cmp(score_a)
+                .then(self.index.names[*a].len().cmp(&self.index.names[*b].len()))
+        });
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, position)| &self.sites[position])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transports::Coordinates;
+
+    fn site(id: &str, name: &str) -> Site {
+        Site {
+            name: name.to_string(),
+            id: id.to_string(),
+            coord: Coordinates {
+                latitude: 0.0,
+                longitude: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn merges_the_sites_of_a_search() {
+        let catalogue = SiteCatalogue::new(vec![site("1", "Slussen"), site("2", "Odenplan")], 10);
+        let merged = catalogue.merged(
+            vec![site("2", "Odenplan T"), site("3", "Tekniska högskolan")],
+            20,
+        );
+
+        assert_eq!(merged.sites.len(), 3);
+        assert_eq!(merged.freshness, 20);
+        assert_eq!(
+            merged.get("1").map(|site| site.name.as_str()),
+            Some("Slussen")
+        );
+        assert_eq!(
+            merged.get("2").map(|site| site.name.as_str()),
+            Some("Odenplan T")
+        );
+        assert_eq!(merged.search("tekniska", 5).len(), 1);
+    }
+
+    fn catalogue() -> SiteCatalogue {
+        SiteCatalogue::new(
+            vec![
+                site("1", "Slussen"),
+                site("2", "Södermalmstorg"),
+                site("3", "Odenplan"),
+                site("4", "Slussen (Stadsgården)"),
+                site("5", "Gamla stan"),
+                site("6", "Södermalm"),
+            ],
+            0,
+        )
+    }
+
+    fn ids(sites: Vec<&Site>) -> Vec<&str> {
+        sites.into_iter().map(|site| site.id.as_str()).collect()
+    }
+
+    #[test]
+    fn searches_the_exact_names_first() {
+        let catalogue = catalogue();
+        assert_eq!(ids(catalogue.search("Slussen", 5)), vec!["1", "4"]);
+        assert_eq!(ids(catalogue.search("  odenplan ", 5)), vec!["3"]);
+    }
+
+    #[test]
+    fn searches_without_accents() {
+        let catalogue = catalogue();
+        assert_eq!(ids(catalogue.search("sodermalm", 5)), vec!["6", "2"]);
+        // The exact name first, then the close one
+        assert_eq!(ids(catalogue.search("SÖDERMALMSTORG", 5)), vec!["2", "6"]);
+    }
+
+    #[test]
+    fn searches_misspelled_names() {
+        let catalogue = catalogue();
+        assert_eq!(ids(catalogue.search("slusen", 5)), vec!["1", "4"]);
+        assert_eq!(ids(catalogue.search("odenpaln", 5)), vec!["3"]);
+    }
+
+    #[test]
+    fn ranks_names_starting_with_the_query_before_names_containing_it() {
+        let catalogue = catalogue();
+        // `Gamla stan` contains `stan`, `Stadsgården` is only close to it
+        assert_eq!(ids(catalogue.search("stan", 5)).first(), Some(&"5"));
+        // Equal, then starting with, then containing, the shortest names first
+        let catalogue = SiteCatalogue::new(
+            vec![
+                site("1", "Centralen Norra"),
+                site("2", "T-Centralen"),
+                site("3", "Centralen"),
+                site("4", "Centralen Södra Uppgången"),
+            ],
+            0,
+        );
+        assert_eq!(
+            ids(catalogue.search("centralen", 5)),
+            vec!["3", "1", "4", "2"]
+        );
+        assert_eq!(ids(catalogue.search("centralen", 2)), vec!["3", "1"]);
+    }
+
+    #[test]
+    fn finds_nothing_for_unrelated_queries() {
+        let catalogue = catalogue();
+        assert!(catalogue.search("Kungsträdgården", 5).is_empty());
+        assert!(catalogue.search("   ", 5).is_empty());
+    }
+
+    fn located(id: &str, latitude: f32, longitude: f32) -> Site {
+        Site {
+            coord: Coordinates {
+                latitude,
+                longitude,
+            },
+            ..site(id, id)
+        }
+    }
+
+    #[test]
+    fn orders_the_nearest_sites_within_the_radius() {
+        let catalogue = SiteCatalogue::new(
+            vec![
+                located("gamla-stan", 59.3233, 18.0719),
+                located("unknown", 0.0, 0.0),
+                located("odenplan", 59.3429, 18.0496),
+                located("slussen", 59.3195, 18.0719),
+            ],
+            0,
+        );
+        let nearest = |radius: f64| -> Vec<(&str, f64)> {
+            catalogue
+                .nearest(59.3195, 18.0719, radius)
+                .into_iter()
+                .map(|(site, distance)| (site.id.as_str(), distance))
+                .collect()
+        };
+
+        let all = nearest(f64::INFINITY);
+        let ids: Vec<&str> = all.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!["slussen", "gamla-stan", "odenplan"]);
+        assert!(all[0].1 < 1.0);
+        assert!((all[1].1 - 423.0).abs() < 1.0);
+        assert!(all[2].1 > 2_000.0 && all[2].1 < 3_500.0);
+
+        let close: Vec<&str> = nearest(500.0).iter().map(|(id, _)| *id).collect();
+        assert_eq!(close, vec!["slussen", "gamla-stan"]);
+        assert!(nearest(-1.0).is_empty());
+    }
+}
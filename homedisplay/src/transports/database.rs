@@ -3,39 +3,93 @@
 use log::{info, warn};
 use redis::Commands;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{SystemTime, UNIX_EPOCH};
-use unidecode::unidecode;
 
 use crate::database;
-use crate::models::transports::{Departure, Site};
+use crate::models::transports::{
+    stockholm_now, Departure, Itinerary, RoutePlan, Site, TrafficDeviation,
+};
 use crate::traits::Api;
 
 use crate::settings;
 
+use super::catalogue::SiteCatalogue;
+use super::filters;
+use super::provider;
+
 #[derive(Serialize, Deserialize)]
-struct SiteDatabase {
-    site: Site,
+struct DepartureDatabase {
+    departures: Vec<Departure>,
     freshness: u64,
 }
 
 #[derive(Serialize, Deserialize)]
-struct DepartureDatabase {
-    departures: Vec<Departure>,
+struct ItineraryDatabase {
+    itineraries: Vec<Itinerary>,
     freshness: u64,
 }
 
-/// Stores the site in the database, wrapped in a SiteDatabase struct to store the freshness
-/// of the data
-fn store_site(site: &Site, redis_data: &settings::Redis) -> Result<(), String> {
-    let site: SiteDatabase = SiteDatabase {
-        site: site.clone(),
-        freshness: SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs(),
-    };
+#[derive(Serialize, Deserialize)]
+struct DeviationDatabase {
+    deviations: Vec<TrafficDeviation>,
+    freshness: u64,
+}
+
+/// Returns the prefix of the redis keys holding the sites of the given provider. SL keeps the
+/// historical prefix so existing databases remain valid
+fn sites_key_prefix(provider: settings::TransitProvider) -> String {
+    match provider {
+        settings::TransitProvider::Sl => "homedisplay:sites".to_string(),
+        provider => format!("homedisplay:{provider}:sites"),
+    }
+}
+
+/// Returns the redis key holding the site catalogue of the given provider
+fn catalogue_key(provider: settings::TransitProvider) -> String {
+    format!("{}:catalogue", sites_key_prefix(provider))
+}
+
+/// The site catalogues loaded so far, by provider, so that the whole catalogue is only
+/// read from the database once per process
+static CATALOGUES: OnceLock<Mutex<HashMap<settings::TransitProvider, Arc<SiteCatalogue>>>> =
+    OnceLock::new();
+
+/// Delay before fetching the site catalogue again after a failure
+const CATALOGUE_RETRY_DELAY: u64 = 3600;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Returns the catalogue of the provider kept in memory, if any
+fn cached_catalogue(provider: settings::TransitProvider) -> Option<Arc<SiteCatalogue>> {
+    CATALOGUES
+        .get_or_init(Default::default)
+        .lock()
+        .ok()?
+        .get(&provider)
+        .cloned()
+}
 
-    let serialized_site: String = match serde_json::to_string(&site) {
+/// Keeps the catalogue of the provider in memory
+fn cache_catalogue(provider: settings::TransitProvider, catalogue: Arc<SiteCatalogue>) {
+    if let Ok(mut catalogues) = CATALOGUES.get_or_init(Default::default).lock() {
+        catalogues.insert(provider, catalogue);
+    }
+}
+
+/// Stores the site catalogue in the database, as a single key
+fn store_catalogue(
+    catalogue: &SiteCatalogue,
+    provider: settings::TransitProvider,
+    redis_data: &settings::Redis,
+) -> Result<(), String> {
+    let serialized_catalogue: String = match serde_json::to_string(catalogue) {
         Ok(serialized) => serialized,
         Err(error) => {
             return Err(format!(
@@ -46,10 +100,7 @@ fn store_site(site: &Site, redis_data: &settings::Redis) -> Result<(), String> {
 
     let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
 
-    match con.set::<String, String, redis::Value>(
-        format!("homedisplay:sites:{}", site.site.id),
-        serialized_site,
-    ) {
+    match con.set::<String, String, redis::Value>(catalogue_key(provider), serialized_catalogue) {
         Ok(_) => Ok(()),
         Err(error) => Err(format!(
             "Could not save serialized data into redis: {error}"
@@ -62,6 +113,7 @@ fn store_site(site: &Site, redis_data: &settings::Redis) -> Result<(), String> {
 fn store_departures(
     new_departures: &[Departure],
     site_id: &str,
+    provider: settings::TransitProvider,
     redis_data: &settings::Redis,
 ) -> Result<(), String> {
     let departures = DepartureDatabase {
@@ -84,7 +136,7 @@ fn store_departures(
     let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
 
     match con.set::<String, String, redis::Value>(
-        format!("homedisplay:sites:{site_id}:departures"),
+        format!("{}:{site_id}:departures", sites_key_prefix(provider)),
         serialized_departures,
     ) {
         Ok(_) => Ok(()),
@@ -94,10 +146,112 @@ fn store_departures(
     }
 }
 
-/// Fetches the sites from the API, filters them using the transports settings
-/// and stores them in the database
+/// Returns the site catalogue of the provider, from memory, then from the database
+async fn load_catalogue(
+    provider: settings::TransitProvider,
+    redis_data: &settings::Redis,
+) -> Option<Arc<SiteCatalogue>> {
+    if let Some(catalogue) = cached_catalogue(provider) {
+        return Some(catalogue);
+    }
+    match database::get_redis_key(catalogue_key(provider), redis_data).await {
+        Ok(serialized) => match serde_json::from_str::<SiteCatalogue>(&serialized) {
+            Ok(catalogue) => {
+                let catalogue = Arc::new(catalogue.indexed());
+                cache_catalogue(provider, catalogue.clone());
+                Some(catalogue)
+            }
+            Err(e) => {
+                warn!("Error while deserializing the site catalogue: {e}");
+                None
+            }
+        },
+        Err(e) => {
+            warn!("Could not fetch the site catalogue from redis: {e}");
+            None
+        }
+    }
+}
+
+/// Fetches the sites from the transit provider and stores them in the database and in
+/// memory as the new catalogue. Providers only returning the sites of the stops have them
+/// merged into the current catalogue, so that searches don't replace the configured stops.
 pub async fn fetch_new_sites(
     stops: &[settings::BusStop],
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Result<Arc<SiteCatalogue>, String> {
+    let sites = provider::from_settings(transit)?.sites(stops).await?;
+    info!("Fetched {} sites from the provider", sites.len());
+
+    let catalogue = match load_catalogue(transit.provider, redis_data).await {
+        Some(current) if !provider::lists_all_sites(transit) => {
+            Arc::new(current.merged(sites, now_secs()))
+        }
+        _ => Arc::new(SiteCatalogue::new(sites, now_secs())),
+    };
+    store_catalogue(&catalogue, transit.provider, redis_data)?;
+    cache_catalogue(transit.provider, catalogue.clone());
+    Ok(catalogue)
+}
+
+/// Returns the site catalogue of the provider, from memory, then from the database. The
+/// catalogue is fetched again, once, when it is older than 30 days or when a stop of the
+/// settings selects none of its sites while the provider only returns the sites of the stops.
+/// If fetching fails, the outdated catalogue is used and no fetch is attempted again for
+/// `CATALOGUE_RETRY_DELAY`.
+pub async fn get_catalogue(
+    stops: &[settings::BusStop],
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Result<Arc<SiteCatalogue>, String> {
+    let catalogue = load_catalogue(transit.provider, redis_data).await;
+
+    let outdated = match &catalogue {
+        Some(catalogue) if now_secs() < catalogue.retry_after => false,
+        Some(catalogue) if now_secs() - catalogue.freshness > 30 * 86400 => {
+            info!("The site catalogue is older than 30 days, fetching new data");
+            true
+        }
+        Some(catalogue) if !catalogue.covers(stops) && !provider::lists_all_sites(transit) => {
+            info!("Some stops are missing from the site catalogue, fetching new data");
+            true
+        }
+        Some(_) => false,
+        None => true,
+    };
+    if !outdated {
+        if let Some(catalogue) = catalogue {
+            return Ok(catalogue);
+        }
+    }
+
+    match fetch_new_sites(stops, transit, redis_data).await {
+        Ok(catalogue) => Ok(catalogue),
+        Err(e) => match catalogue {
+            Some(catalogue) => {
+                warn!(
+                    "Unable to refresh the site catalogue, keeping the current one for {} minutes: {e}",
+                    CATALOGUE_RETRY_DELAY / 60
+                );
+                let mut stale = (*catalogue).clone();
+                stale.retry_after = now_secs() + CATALOGUE_RETRY_DELAY;
+                let stale = Arc::new(stale);
+                if let Err(e) = store_catalogue(&stale, transit.provider, redis_data) {
+                    warn!("Could not store the site catalogue: {e}");
+                }
+                cache_catalogue(transit.provider, stale.clone());
+                Ok(stale)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Returns the sites of the catalogue, filtered using the stops from the settings
+pub async fn get_sites(
+    stops: &[settings::BusStop],
+    transit: &settings::Transit,
     redis_data: &settings::Redis,
 ) -> Result<Vec<Site>, String> {
     info!("Filtering on:");
@@ -105,118 +259,110 @@ pub async fn fetch_new_sites(
         info!(" - {}", stop.name);
     }
 
-    // Fetch sites from the API
-    let sites = Site::api_get(()).await?;
-    let mut filtered_sites: Vec<Site> = vec![];
-    info!("Fetched {} sites from the API", sites.len());
-    for site in sites.iter() {
-        if stops.is_empty()
-            || stops.iter().any(|s| {
-                unidecode(&site.name.to_lowercase()).contains(&unidecode(&s.name.to_lowercase()))
-                    && s.site_id.as_ref().map(|id| id == &site.id) != Some(false)
-            })
-        {
-            filtered_sites.push(site.clone());
-        }
+    let site_list = get_catalogue(stops, transit, redis_data)
+        .await?
+        .select(stops);
+    info!("Selected {} sites", site_list.len());
+    if site_list.is_empty() {
+        warn!("No sites match the stops, empty list will be returned");
     }
-    info!("Filtered {} sites", filtered_sites.len());
+    Ok(site_list)
+}
 
-    for site in filtered_sites.iter() {
-        store_site(site, redis_data)?;
+/// Checks that every stop of the settings selects a site, and that the stops matching by
+/// name select a single one. Returns one message per stop selecting none or several sites,
+/// the latter listing the matches with their ids and coordinates.
+pub async fn validate_stops(
+    stops: &[settings::BusStop],
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Result<(), Vec<String>> {
+    let catalogue = get_catalogue(stops, transit, redis_data)
+        .await
+        .map_err(|e| vec![format!("Unable to validate the stops: {e}")])?;
+
+    let mut errors: Vec<String> = vec![];
+    for stop in stops.iter().filter(|stop| stop.closest.is_none()) {
+        let matches = catalogue.matches(stop);
+        match matches.len() {
+            0 => errors.push(format!("Stop {} matches no site", stop.name)),
+            1 => {}
+            count => errors.push(format!(
+                "Stop {} matches {count} sites: {}. Pin one with `site_id` and `match_mode = \"id\"`, or use `match_mode = \"exact\"`",
+                stop.name,
+                matches
+                    .iter()
+                    .map(|site| format!(
+                        "{} (id {}, {:.5}, {:.5})",
+                        site.name, site.id, site.coord.latitude, site.coord.longitude
+                    ))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
+        }
     }
 
-    // Store the sites in the database
-    Ok(filtered_sites)
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
-/// Returns the sites from the database. The list is filtered using elements in the
-/// stops vector from the settings
-pub async fn get_sites(
-    stops: &[settings::BusStop],
+/// Returns the sites whose name matches the query, best matches first, e.g. to find the
+/// site id of a stop
+pub async fn search_sites(
+    query: &str,
+    limit: usize,
+    transit: &settings::Transit,
     redis_data: &settings::Redis,
 ) -> Result<Vec<Site>, String> {
-    // Get all sites, filter them and return the list
-    let mut site_list: Vec<Site> = vec![];
-
-    info!("Scanning for sites in the database");
-    let sites = database::scan_iter("homedisplay:sites:*".to_string(), redis_data).await?;
-    for site in sites.iter() {
-        if let Ok(serialized_site) = database::get_redis_key(site.to_string(), redis_data).await {
-            let site = match serde_json::from_str::<SiteDatabase>(&serialized_site) {
-                Ok(site)
-                    if SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                        - site.freshness
-                        > 30 * 86400 =>
-                {
-                    info!(
-                        "Site {} is older than 30 days, fetching new data",
-                        site.site.id
-                    );
-                    match Site::api_get(()).await {
-                        Ok(new_sites) => {
-                            let mut site_to_return: Option<Site> = None;
-                            for new_site in new_sites.into_iter() {
-                                store_site(&new_site, redis_data)?;
-                                if new_site.id == site.site.id {
-                                    site_to_return = Some(new_site);
-                                }
-                            }
-                            if let Some(site) = site_to_return {
-                                site
-                            } else {
-                                warn!(
-                                    "Could not find current site ({}) in new sites",
-                                    site.site.id
-                                );
-                                continue;
-                            }
-                        }
-                        Err(e) => {
-                            warn!("Error while fetching bus sites: {e}");
-                            continue;
-                        }
-                    }
-                }
-                Ok(site) => site.site,
-                Err(e) => {
-                    warn!("Error while deserializing bus sites: {e}");
-                    continue;
-                }
-            };
-            let site_name = &site.name.to_lowercase();
-            if stops.is_empty()
-                || stops.iter().any(|stop| {
-                    unidecode(site_name).contains(&unidecode(&stop.name.to_lowercase()))
-                        && stop.site_id.as_ref().map(|id| id == &site.id) != Some(false)
-                })
-            {
-                site_list.push(site);
-            }
-        } else {
-            warn!("Could not fetch site from redis");
-        }
-    }
-    if site_list.is_empty() {
-        site_list = fetch_new_sites(stops, redis_data).await?;
+    let stop = settings::BusStop {
+        name: query.to_string(),
+        ..Default::default()
+    };
+    let catalogue = get_catalogue(&[stop], transit, redis_data).await?;
+    Ok(catalogue
+        .search(query, limit)
+        .into_iter()
+        .cloned()
+        .collect())
+}
 
-        if site_list.is_empty() {
-            warn!("No sites found in the database, empty list will be returned");
+/// Fetches the departures of a site from the transit provider and stores them in the database
+async fn refresh_departures(
+    site_id: &str,
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Result<Vec<Departure>, String> {
+    let departures = match provider::from_settings(transit) {
+        Ok(provider) => provider.departures(site_id).await,
+        Err(e) => Err(e),
+    };
+    match departures {
+        Ok(new_departures) => {
+            store_departures(&new_departures, site_id, transit.provider, redis_data)?;
+            Ok(new_departures)
+        }
+        Err(e) => {
+            warn!("Error while fetching departures: {}", e);
+            Err(e)
         }
     }
-    Ok(site_list)
 }
 
 /// Fetches the current departures from the database, if it is older than a minute,
 /// data will be refreshed before being returned
 pub async fn get_departures(
     site_id: String,
+    transit: &settings::Transit,
     redis_data: &settings::Redis,
 ) -> Result<Vec<Departure>, String> {
     match database::get_redis_key(
-        format!("homedisplay:sites:{site_id}:departures"),
+        format!(
+            "{}:{site_id}:departures",
+            sites_key_prefix(transit.provider)
+        ),
         redis_data,
     )
     .await
@@ -236,16 +382,7 @@ pub async fn get_departures(
                         "Departures for site {} are older than 60 seconds, fetching new data",
                         site_id
                     );
-                    match Departure::api_get(site_id.clone()).await {
-                        Ok(new_departures) => {
-                            store_departures(&new_departures, &site_id, redis_data)?;
-                            new_departures
-                        }
-                        Err(e) => {
-                            warn!("Error while fetching departures: {}", e.to_string());
-                            return Err(e);
-                        }
-                    }
+                    refresh_departures(&site_id, transit, redis_data).await?
                 }
                 Ok(departures) => departures.departures,
                 Err(e) => {
@@ -257,17 +394,337 @@ pub async fn get_departures(
         }
         Err(e) => {
             warn!("Could not fetch departures from redis: {}", e);
-            info!("Fetching new departures from API for site {}", site_id);
-            match Departure::api_get(site_id.clone()).await {
-                Ok(new_departures) => {
-                    store_departures(&new_departures, &site_id, redis_data)?;
-                    Ok(new_departures)
+            info!(
+                "Fetching new departures from the provider for site {}",
+                site_id
+            );
+            refresh_departures(&site_id, transit, redis_data).await
+        }
+    }
+}
+
+/// Returns the sites of the catalogue within the radius (in meters) of the given point,
+/// with their distance, closest first
+pub async fn nearest_sites(
+    latitude: f64,
+    longitude: f64,
+    radius: f64,
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Result<Vec<(Site, f64)>, String> {
+    let catalogue = get_catalogue(&[], transit, redis_data).await?;
+    Ok(catalogue
+        .nearest(latitude, longitude, radius)
+        .into_iter()
+        .map(|(site, distance)| (site.clone(), distance))
+        .collect())
+}
+
+/// Replaces the stops selecting the sites closest to home by one stop pinned to each of
+/// these sites, keeping their filters. The other stops are returned as they are.
+pub async fn resolve_stops(
+    stops: &[settings::BusStop],
+    home: &settings::Weather,
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Result<Vec<settings::BusStop>, String> {
+    if stops.iter().all(|stop| stop.closest.is_none()) {
+        return Ok(stops.to_vec());
+    }
+
+    let catalogue = get_catalogue(stops, transit, redis_data).await?;
+    let nearest = catalogue.nearest(home.latitude, home.longitude, f64::INFINITY);
+    let mut resolved: Vec<settings::BusStop> = vec![];
+    for stop in stops.iter() {
+        match stop.closest {
+            Some(count) => {
+                for (site, distance) in nearest.iter().take(count) {
+                    info!(
+                        "{}: selected {} ({}), {:.0} m away",
+                        stop.name, site.name, site.id, distance
+                    );
+                    resolved.push(settings::BusStop {
+                        name: site.name.clone(),
+                        site_id: Some(site.id.clone()),
+                        closest: None,
+                        match_mode: settings::StopMatch::Id,
+                        ..stop.clone()
+                    });
                 }
+            }
+            None => resolved.push(stop.clone()),
+        }
+    }
+    Ok(resolved)
+}
+
+/// Returns the name of the site with the given id, if it is in the catalogue
+async fn get_site_name(
+    site_id: &str,
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Option<String> {
+    get_catalogue(&[], transit, redis_data)
+        .await
+        .ok()?
+        .get(site_id)
+        .map(|site| site.name.clone())
+}
+
+/// Fetches the departures of a site, keeping only those accepted by the line, transport
+/// mode, destination and direction filters of the stop that selected the site, and that
+/// can still be reached given its walking time
+pub async fn get_stop_departures(
+    site_id: String,
+    stops: &[settings::BusStop],
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Result<Vec<Departure>, String> {
+    let site_name = get_site_name(&site_id, transit, redis_data).await;
+    let departures = get_departures(site_id.clone(), transit, redis_data).await?;
+
+    match filters::stop_for_site(stops, &site_id, site_name.as_deref()) {
+        Some(stop) => {
+            let filtered = filters::mark_walking_time(
+                stop,
+                filters::filter_departures(stop, departures),
+                stockholm_now(),
+            );
+            info!(
+                "Kept {} departures for site {} after filtering on stop {}",
+                filtered.len(),
+                site_id,
+                stop.name
+            );
+            Ok(filtered)
+        }
+        None => Ok(departures),
+    }
+}
+
+/// Stores the deviations of the whole network in the database, wrapped in a DeviationDatabase
+/// struct to store the freshness of the data
+fn store_deviations(
+    deviations: &[TrafficDeviation],
+    redis_data: &settings::Redis,
+) -> Result<(), String> {
+    let deviations = DeviationDatabase {
+        deviations: deviations.to_vec(),
+        freshness: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+
+    let serialized_deviations: String = match serde_json::to_string(&deviations) {
+        Ok(serialized) => serialized,
+        Err(error) => {
+            return Err(format!(
+                "An error occured while serializing the data: {error}"
+            ))
+        }
+    };
+
+    let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
+
+    match con.set::<String, String, redis::Value>(
+        "homedisplay:deviations".to_string(),
+        serialized_deviations,
+    ) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!(
+            "Could not save serialized data into redis: {error}"
+        )),
+    }
+}
+
+/// Fetches the deviations of the whole network from the API and stores them in the database
+async fn refresh_deviations(redis_data: &settings::Redis) -> Result<Vec<TrafficDeviation>, String> {
+    match TrafficDeviation::api_get(()).await {
+        Ok(deviations) => {
+            store_deviations(&deviations, redis_data)?;
+            Ok(deviations)
+        }
+        Err(e) => {
+            warn!("Error while fetching deviations: {}", e);
+            Err(e)
+        }
+    }
+}
+
+/// Fetches the current deviations affecting the given sites or the lines of the stops from
+/// the database, most important first. If they are older than five minutes, data will be
+/// refreshed before being returned. Only SL publishes deviations, other providers have none
+pub async fn get_deviations(
+    sites: &[Site],
+    stops: &[settings::BusStop],
+    transit: &settings::Transit,
+    redis_data: &settings::Redis,
+) -> Result<Vec<TrafficDeviation>, String> {
+    if transit.provider != settings::TransitProvider::Sl {
+        return Ok(vec![]);
+    }
+
+    let deviations =
+        match database::get_redis_key("homedisplay:deviations".to_string(), redis_data).await {
+            Ok(serialized) => match serde_json::from_str::<DeviationDatabase>(&serialized) {
+                Ok(deviations)
+                    if SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs()
+                        - deviations.freshness
+                        > 300 =>
+                {
+                    info!("Deviations are older than five minutes, fetching new data");
+                    refresh_deviations(redis_data).await?
+                }
+                Ok(deviations) => deviations.deviations,
                 Err(e) => {
-                    warn!("Error while fetching departures: {}", e.to_string());
-                    Err(e)
+                    warn!("Error while deserializing deviations: {}", e);
+                    refresh_deviations(redis_data).await?
                 }
+            },
+            Err(e) => {
+                warn!("Could not fetch deviations from redis: {}", e);
+                info!("Fetching new deviations from API");
+                refresh_deviations(redis_data).await?
             }
+        };
+
+    let site_ids: Vec<String> = sites.iter().map(|site| site.id.clone()).collect();
+    let line_ids: Vec<i32> = stops
+        .iter()
+        .filter_map(|stop| stop.preferred_lines.clone())
+        .flatten()
+        .collect();
+    let now = stockholm_now();
+
+    let mut deviations: Vec<TrafficDeviation> = deviations
+        .into_iter()
+        .filter(|deviation| deviation.affects(&site_ids, &line_ids))
+        .filter(|deviation| deviation.publish_upto.is_none_or(|upto| upto > now))
+        .collect();
+    deviations.sort_by_key(|deviation| -deviation.importance_level);
+    Ok(deviations)
+}
+
+/// Returns the redis key holding the itineraries of the given route
+fn route_key(route: &settings::Route) -> String {
+    format!(
+        "homedisplay:routes:{}:{}:{}",
+        route.origin, route.destination, route.trips
+    )
+}
+
+/// Stores the itineraries of a route in the database, wrapped in an ItineraryDatabase struct
+/// to store the freshness of the data
+fn store_itineraries(
+    itineraries: &[Itinerary],
+    route: &settings::Route,
+    redis_data: &settings::Redis,
+) -> Result<(), String> {
+    let itineraries = ItineraryDatabase {
+        itineraries: itineraries.to_vec(),
+        freshness: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+
+    let serialized_itineraries: String = match serde_json::to_string(&itineraries) {
+        Ok(serialized) => serialized,
+        Err(error) => {
+            return Err(format!(
+                "An error occured while serializing the data: {error}"
+            ))
         }
+    };
+
+    let mut con: redis::Connection = database::get_redis_connection(redis_data)?;
+
+    match con.set::<String, String, redis::Value>(route_key(route), serialized_itineraries) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!(
+            "Could not save serialized data into redis: {error}"
+        )),
+    }
+}
+
+/// Fetches the itineraries of a route from the journey planner and stores them in the database
+async fn refresh_itineraries(
+    route: &settings::Route,
+    redis_data: &settings::Redis,
+) -> Result<Vec<Itinerary>, String> {
+    match Itinerary::api_get(route.clone()).await {
+        Ok(itineraries) => {
+            store_itineraries(&itineraries, route, redis_data)?;
+            Ok(itineraries)
+        }
+        Err(e) => {
+            warn!("Error while fetching itineraries: {}", e);
+            Err(e)
+        }
+    }
+}
+
+/// Fetches the next itineraries of a route from the database. If they are older than two
+/// minutes, or the first one has already left, data will be refreshed before being returned
+pub async fn get_route_plan(
+    route: &settings::Route,
+    redis_data: &settings::Redis,
+) -> Result<RoutePlan, String> {
+    let now = stockholm_now();
+    let itineraries = match database::get_redis_key(route_key(route), redis_data).await {
+        Ok(serialized) => match serde_json::from_str::<ItineraryDatabase>(&serialized) {
+            Ok(itineraries)
+                if SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+                    - itineraries.freshness
+                    > 120
+                    || itineraries
+                        .itineraries
+                        .first()
+                        .is_none_or(|itinerary| itinerary.departure < now) =>
+            {
+                info!(
+                    "Itineraries of {} are outdated, fetching new data",
+                    route.name
+                );
+                refresh_itineraries(route, redis_data).await?
+            }
+            Ok(itineraries) => itineraries.itineraries,
+            Err(e) => {
+                warn!("Error while deserializing itineraries: {}", e);
+                refresh_itineraries(route, redis_data).await?
+            }
+        },
+        Err(e) => {
+            warn!("Could not fetch itineraries from redis: {}", e);
+            info!(
+                "Fetching new itineraries of {} from the journey planner",
+                route.name
+            );
+            refresh_itineraries(route, redis_data).await?
+        }
+    };
+
+    Ok(RoutePlan {
+        name: route.name.clone(),
+        itineraries,
+    })
+}
+
+/// Returns the plans of all the routes of the settings
+pub async fn get_route_plans(
+    routes: &[settings::Route],
+    redis_data: &settings::Redis,
+) -> Result<Vec<RoutePlan>, String> {
+    let mut plans: Vec<RoutePlan> = vec![];
+    for route in routes.iter() {
+        plans.push(get_route_plan(route, redis_data).await?);
     }
+    Ok(plans)
 }
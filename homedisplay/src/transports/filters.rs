@@ -0,0 +1,319 @@
+/// Matches the sites and departures against the bus stops of the settings
+use unidecode::unidecode;
+
+use chrono::NaiveDateTime;
+
+use crate::models::transports::{Departure, Reachability, Site};
+use crate::settings::{BusStop, StopMatch};
+
+/// Lowercases the text and strips its accents, so `Södermalm` matches `sodermalm`
+pub(crate) fn normalize(text: &str) -> String {
+    unidecode(&text.to_lowercase())
+}
+
+/// Returns whether the site name matches the name of the stop, following its match mode
+fn matches_name(stop: &BusStop, site_name: &str) -> bool {
+    match stop.match_mode {
+        StopMatch::Contains => normalize(site_name).contains(&normalize(&stop.name)),
+        StopMatch::Exact => normalize(site_name) == normalize(&stop.name),
+        StopMatch::Id => false,
+    }
+}
+
+/// Returns whether the site was selected by the given stop: its name must match the stop
+/// name, and its id must be the stop's `site_id` when one is set. Stops in the `id` mode
+/// only select the site with their `site_id`.
+pub fn matches_site(stop: &BusStop, site: &Site) -> bool {
+    match (&stop.site_id, stop.match_mode) {
+        (Some(site_id), StopMatch::Id) => site_id == &site.id,
+        (None, StopMatch::Id) => false,
+        (site_id, _) => {
+            matches_name(stop, &site.name) && site_id.as_ref().is_none_or(|id| id == &site.id)
+        }
+    }
+}
+
+/// Returns the stop of the settings that selected the site with the given id and name.
+/// Stops pinned to the site id are preferred over stops matching by name.
+pub fn stop_for_site<'a>(
+    stops: &'a [BusStop],
+    site_id: &str,
+    site_name: Option<&str>,
+) -> Option<&'a BusStop> {
+    stops
+        .iter()
+        .find(|stop| stop.site_id.as_deref() == Some(site_id))
+        .or_else(|| {
+            let site_name = site_name?;
+            stops
+                .iter()
+                .find(|stop| stop.site_id.is_none() && matches_name(stop, site_name))
+        })
+}
+
+/// Returns whether the departure passes the line, transport mode, destination and
+/// direction filters of the stop. Unset filters accept every departure.
+pub fn accepts(stop: &BusStop, departure: &Departure) -> bool {
+    let line = stop
+        .preferred_lines
+        .as_ref()
+        .is_none_or(|lines| lines.contains(&departure.line.id));
+    let mode = stop.transport_modes.as_ref().is_none_or(|modes| {
+        modes
+            .iter()
+            .any(|mode| mode.eq_ignore_ascii_case(&departure.line.transport_mode))
+    });
+    let destination = stop.destinations.as_ref().is_none_or(|destinations| {
+        let departure_destination = normalize(&departure.destination);
+        destinations
+            .iter()
+            .any(|destination| departure_destination.contains(&normalize(destination)))
+    });
+    let direction = stop
+        .direction_code
+        .is_none_or(|code| departure.direction_code == Some(code));
+
+    line && mode && destination && direction
+}
+
+/// Keeps the departures accepted by the filters of the stop
+pub fn filter_departures(stop: &BusStop, departures: Vec<Departure>) -> Vec<Departure> {
+    departures
+        .into_iter()
+        .filter(|departure| accepts(stop, departure))
+        .collect()
+}
+
+/// Marks the departures with the walking time of the stop, and hides the ones that can't
+/// be reached anymore from the given Stockholm time
+pub fn mark_walking_time(
+    stop: &BusStop,
+    departures: Vec<Departure>,
+    now: NaiveDateTime,
+) -> Vec<Departure> {
+    departures
+        .into_iter()
+        .map(|mut departure| {
+            departure.walking_minutes = stop.walking_minutes;
+            departure
+        })
+        .filter(|departure| departure.reachability(now) != Some(Reachability::Missed))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transports::{Coordinates, DepartureState, Line};
+    use chrono::NaiveDate;
+
+    fn departure(line: i32, mode: &str, destination: &str, direction: Option<i32>) -> Departure {
+        Departure {
+            destination: destination.to_string(),
+            direction_code: direction,
+            display: "10 min".to_string(),
+            state: DepartureState::Expected,
+            scheduled: None,
+            expected: None,
+            line: Line {
+                id: line,
+                designation: line.to_string(),
+                transport_mode: mode.to_string(),
+                group_of_lines: None,
+            },
+            stop_point: None,
+            journey: None,
+            deviations: vec![],
+            walking_minutes: None,
+        }
+    }
+
+    fn stop(name: &str) -> BusStop {
+        BusStop {
+            name: name.to_string(),
+            ..BusStop::default()
+        }
+    }
+
+    fn site(id: &str, name: &str) -> Site {
+        Site {
+            name: name.to_string(),
+            id: id.to_string(),
+            coord: Coordinates {
+                latitude: 0.0,
+                longitude: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn accepts_every_departure_without_filters() {
+        let stop = stop("Slussen");
+        assert!(accepts(&stop, &departure(43, "BUS", "Hjulsta", None)));
+        assert!(accepts(&stop, &departure(0, "", "", Some(1))));
+    }
+
+    #[test]
+    fn filters_the_lines() {
+        let stop = BusStop {
+            preferred_lines: Some(vec![43, 53]),
+            ..stop("Slussen")
+        };
+        assert!(accepts(&stop, &departure(43, "BUS", "Hjulsta", None)));
+        assert!(!accepts(
+            &stop,
+            &departure(2, "BUS", "Norra Hammarbyhamnen", None)
+        ));
+    }
+
+    #[test]
+    fn filters_the_transport_modes_whatever_their_case() {
+        let stop = BusStop {
+            transport_modes: Some(vec!["metro".to_string(), "TRAM".to_string()]),
+            ..stop("Slussen")
+        };
+        assert!(accepts(&stop, &departure(13, "METRO", "Norsborg", None)));
+        assert!(accepts(&stop, &departure(7, "TRAM", "Djurgården", None)));
+        assert!(!accepts(&stop, &departure(43, "BUS", "Hjulsta", None)));
+    }
+
+    #[test]
+    fn filters_the_destinations_on_a_part_of_their_name() {
+        let stop = BusStop {
+            destinations: Some(vec!["sodra".to_string()]),
+            ..stop("Slussen")
+        };
+        assert!(accepts(
+            &stop,
+            &departure(43, "BUS", "Stockholm Södra", None)
+        ));
+        assert!(!accepts(&stop, &departure(43, "BUS", "Hjulsta", None)));
+    }
+
+    #[test]
+    fn filters_the_direction() {
+        let stop = BusStop {
+            direction_code: Some(2),
+            ..stop("Slussen")
+        };
+        assert!(accepts(&stop, &departure(13, "METRO", "Ropsten", Some(2))));
+        assert!(!accepts(
+            &stop,
+            &departure(13, "METRO", "Norsborg", Some(1))
+        ));
+        assert!(!accepts(&stop, &departure(13, "METRO", "Norsborg", None)));
+    }
+
+    #[test]
+    fn combines_the_filters() {
+        let stop = BusStop {
+            preferred_lines: Some(vec![13]),
+            transport_modes: Some(vec!["METRO".to_string()]),
+            direction_code: Some(2),
+            ..stop("Slussen")
+        };
+        let departures = vec![
+            departure(13, "METRO", "Ropsten", Some(2)),
+            departure(13, "METRO", "Norsborg", Some(1)),
+            departure(14, "METRO", "Mörby centrum", Some(2)),
+            departure(13, "BUS", "Ropsten", Some(2)),
+        ];
+
+        let kept = filter_departures(&stop, departures);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].destination, "Ropsten");
+    }
+
+    #[test]
+    fn matches_the_sites_by_name() {
+        let contains = stop("sodermalm");
+        assert!(matches_site(&contains, &site("1", "Södermalmstorg")));
+        assert!(!matches_site(&contains, &site("2", "Slussen")));
+
+        let exact = BusStop {
+            match_mode: StopMatch::Exact,
+            ..stop("SÖDERMALMSTORG")
+        };
+        assert!(matches_site(&exact, &site("1", "Södermalmstorg")));
+        assert!(!matches_site(&exact, &site("3", "Södermalmstorg norra")));
+
+        // A site id narrows the sites matching by name
+        let pinned = BusStop {
+            site_id: Some("1".to_string()),
+            ..stop("sodermalm")
+        };
+        assert!(matches_site(&pinned, &site("1", "Södermalmstorg")));
+        assert!(!matches_site(&pinned, &site("3", "Södermalmstorg norra")));
+    }
+
+    #[test]
+    fn matches_the_sites_by_id() {
+        let by_id = BusStop {
+            site_id: Some("9192".to_string()),
+            match_mode: StopMatch::Id,
+            ..stop("Home")
+        };
+        // The name is only a label
+        assert!(matches_site(&by_id, &site("9192", "Slussen")));
+        assert!(!matches_site(&by_id, &site("9193", "Home")));
+
+        let without_id = BusStop {
+            match_mode: StopMatch::Id,
+            ..stop("Slussen")
+        };
+        assert!(!matches_site(&without_id, &site("9192", "Slussen")));
+    }
+
+    #[test]
+    fn finds_the_stop_of_a_site() {
+        let stops = vec![
+            stop("Slussen"),
+            BusStop {
+                site_id: Some("9192".to_string()),
+                match_mode: StopMatch::Id,
+                ..stop("Home")
+            },
+            BusStop {
+                site_id: Some("1".to_string()),
+                ..stop("Odenplan")
+            },
+        ];
+
+        // Stops pinned to the id come first
+        assert_eq!(
+            stop_for_site(&stops, "9192", Some("Slussen")).map(|stop| stop.name.as_str()),
+            Some("Home")
+        );
+        assert_eq!(
+            stop_for_site(&stops, "9193", Some("Slussen (Stadsgården)"))
+                .map(|stop| stop.name.as_str()),
+            Some("Slussen")
+        );
+        // Stops with a site id only select that site
+        assert!(stop_for_site(&stops, "2", Some("Odenplan")).is_none());
+        assert!(stop_for_site(&stops, "9193", None).is_none());
+    }
+
+    #[test]
+    fn hides_the_departures_that_cannot_be_reached() {
+        let now = NaiveDate::from_ymd_opt(2024, 3, 12)
+            .unwrap()
+            .and_hms_opt(8, 0, 0)
+            .unwrap();
+        let at = |minutes: i64| Departure {
+            expected: Some(now + chrono::Duration::minutes(minutes)),
+            ..departure(43, "BUS", "Hjulsta", None)
+        };
+        let stop = BusStop {
+            walking_minutes: Some(5),
+            ..stop("Slussen")
+        };
+
+        let kept = mark_walking_time(&stop, vec![at(2), at(4), at(10)], now);
+        assert_eq!(kept.len(), 2);
+        assert!(kept
+            .iter()
+            .all(|departure| departure.walking_minutes == Some(5)));
+        assert_eq!(kept[0].expected, at(4).expected);
+    }
+}
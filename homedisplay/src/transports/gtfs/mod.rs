@@ -0,0 +1,457 @@
+/// A transit provider reading a GTFS static feed from disk, overlaid with the trip updates
+/// of GTFS-Realtime feeds
+use async_trait::async_trait;
+use chrono::Utc;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::models::transports::{Departure, Site};
+use crate::settings::BusStop;
+
+use super::provider::Provider;
+
+pub mod realtime;
+pub mod schedule;
+
+use realtime::RealtimeUpdates;
+use schedule::GtfsFeed;
+
+/// A static feed, with the modification time of its files when it was loaded
+struct LoadedFeed {
+    modified: Option<SystemTime>,
+    feed: Arc<GtfsFeed>,
+}
+
+/// The static feeds loaded so far, by path, as they are expensive to parse
+static FEEDS: OnceLock<Mutex<HashMap<String, LoadedFeed>>> = OnceLock::new();
+
+/// Returns the last modification time of the feed, the latest of its tables when it is a
+/// directory
+fn modified(path: &str) -> Option<SystemTime> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_dir() {
+        return metadata.modified().ok();
+    }
+    std::fs::read_dir(path)
+        .ok()?
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .chain(metadata.modified().ok())
+        .max()
+}
+
+/// Returns the static feed at the given path, loading it on first use and again once the
+/// feed was modified
+fn load_feed(path: &str) -> Result<Arc<GtfsFeed>, String> {
+    let mut feeds = FEEDS
+        .get_or_init(Default::default)
+        .lock()
+        .map_err(|e| e.to_string())?;
+    let modified = modified(path);
+    if let Some(loaded) = feeds.get(path) {
+        if loaded.modified == modified {
+            return Ok(loaded.feed.clone());
+        }
+        info!("GTFS feed {path} was modified, loading it again");
+    }
+
+    info!("Loading GTFS feed from {path}");
+    let feed = Arc::new(GtfsFeed::from_path(path)?);
+    feeds.insert(
+        path.to_string(),
+        LoadedFeed {
+            modified,
+            feed: feed.clone(),
+        },
+    );
+    Ok(feed)
+}
+
+/// Reads a GTFS-Realtime feed from a URL or a local file
+async fn fetch_realtime(source: &str) -> Result<Vec<u8>, String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let result = reqwest::get(source)
+            .await
+            .map_err(|e| format!("Unable to fetch GTFS-Realtime feed {source}: {e}"))?;
+        match result.status() {
+            reqwest::StatusCode::OK => result
+                .bytes()
+                .await
+                .map(|bytes| bytes.to_vec())
+                .map_err(|e| format!("Unable to read GTFS-Realtime feed {source}: {e}")),
+            status => Err(format!("Unexpected error ({status}) for {source}")),
+        }
+    } else {
+        std::fs::read(source)
+            .map_err(|e| format!("Unable to read GTFS-Realtime feed {source}: {e}"))
+    }
+}
+
+/// A GTFS static feed, with the GTFS-Realtime feeds to overlay
+pub struct GtfsProvider {
+    feed: Arc<GtfsFeed>,
+    realtime: Vec<String>,
+}
+
+impl GtfsProvider {
+    /// Loads the static feed at the given path, a zip archive or an extracted directory
+    pub fn load(path: &str, realtime: Vec<String>) -> Result<GtfsProvider, String> {
+        Ok(GtfsProvider {
+            feed: load_feed(path)?,
+            realtime,
+        })
+    }
+
+    /// Fetches the realtime feeds. Feeds that can't be fetched are skipped, the schedule
+    /// being used as is for their trips.
+    async fn realtime_updates(&self) -> RealtimeUpdates {
+        let mut updates = RealtimeUpdates::default();
+        for source in self.realtime.iter() {
+            if let Err(e) = fetch_realtime(source)
+                .await
+                .and_then(|bytes| updates.add_feed(&bytes))
+            {
+                warn!("Ignoring GTFS-Realtime feed: {e}");
+            }
+        }
+        updates
+    }
+}
+
+#[async_trait]
+impl Provider for GtfsProvider {
+    async fn sites(&self, _stops: &[BusStop]) -> Result<Vec<Site>, String> {
+        Ok(self.feed.sites())
+    }
+
+    async fn departures(&self, site_id: &str) -> Result<Vec<Departure>, String> {
+        let updates = self.realtime_updates().await;
+        Ok(self.feed.departures(site_id, Utc::now(), &updates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+    use chrono_tz::Europe::Stockholm;
+    use prost::Message;
+
+    use super::realtime::{
+        FeedEntity, FeedHeader, FeedMessage, RealtimeUpdates, StopTimeEvent, StopTimeUpdate,
+        TripDescriptor, TripUpdate,
+    };
+    use super::schedule::GtfsFeed;
+    use crate::models::transports::Departure;
+
+    /// The sample feed: trips T1 to T3 of line 1 run on weekdays between the Harbour and the
+    /// Airport through the platform A of the Central Station, the Airport Express T4 and T5
+    /// run daily from its platform B
+    const SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/gtfs_sample");
+    const CENTRAL_STATION: &str = "1000";
+
+    fn sample() -> GtfsFeed {
+        GtfsFeed::from_path(SAMPLE).unwrap()
+    }
+
+    /// Returns the instant of the given Stockholm wall-clock time
+    fn stockholm(time: &str) -> DateTime<Utc> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        Stockholm
+            .from_local_datetime(&time)
+            .single()
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn wall_clock(time: &str) -> Option<NaiveDateTime> {
+        Some(NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    /// Builds a realtime feed delaying trip T1 by three minutes and cancelling trip T2,
+    /// encoded as it would be served
+    fn sample_realtime() -> RealtimeUpdates {
+        let trip_update =
+            |trip_id: &str, relationship: i32, updates: Vec<StopTimeUpdate>| FeedEntity {
+                id: trip_id.to_string(),
+                trip_update: Some(TripUpdate {
+                    trip: TripDescriptor {
+                        trip_id: Some(trip_id.to_string()),
+                        route_id: None,
+                        start_date: None,
+                        schedule_relationship: Some(relationship),
+                    },
+                    stop_time_update: updates,
+                    delay: None,
+                }),
+            };
+        let feed = FeedMessage {
+            header: FeedHeader {
+                gtfs_realtime_version: "2.0".to_string(),
+                timestamp: None,
+            },
+            entity: vec![
+                trip_update(
+                    "T1",
+                    0,
+                    vec![StopTimeUpdate {
+                        stop_sequence: Some(1),
+                        arrival: None,
+                        departure: Some(StopTimeEvent {
+                            delay: Some(180),
+                            time: None,
+                        }),
+                        stop_id: None,
+                        schedule_relationship: None,
+                    }],
+                ),
+                trip_update("T2", 3, vec![]),
+            ],
+        };
+
+        let mut updates = RealtimeUpdates::default();
+        updates.add_feed(&feed.encode_to_vec()).unwrap();
+        updates
+    }
+
+    fn summary(departures: &[Departure]) -> Vec<(String, String, Option<NaiveDateTime>)> {
+        departures
+            .iter()
+            .map(|departure| {
+                (
+                    departure.line.designation.clone(),
+                    departure.destination.clone(),
+                    departure.scheduled,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lists_the_stations_and_standalone_stops() {
+        let sites: Vec<(String, String)> = sample()
+            .sites()
+            .into_iter()
+            .map(|site| (site.id, site.name))
+            .collect();
+        assert_eq!(
+            sites,
+            vec![
+                ("3000".to_string(), "Airport".to_string()),
+                ("1000".to_string(), "Central Station".to_string()),
+                ("2000".to_string(), "Harbour".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_the_times_in_the_agency_timezone() {
+        let feed = sample();
+        assert_eq!(feed.timezone(), Stockholm);
+
+        let departures = feed.departures(
+            CENTRAL_STATION,
+            stockholm("2025-06-02 07:55"),
+            &RealtimeUpdates::default(),
+        );
+        assert_eq!(
+            summary(&departures),
+            vec![
+                (
+                    "1".to_string(),
+                    "Airport".to_string(),
+                    wall_clock("2025-06-02 08:11")
+                ),
+                (
+                    "Airport Express".to_string(),
+                    "Airport".to_string(),
+                    wall_clock("2025-06-02 08:15")
+                ),
+                (
+                    "1".to_string(),
+                    "Harbour".to_string(),
+                    wall_clock("2025-06-02 08:36")
+                ),
+                (
+                    "1".to_string(),
+                    "Airport".to_string(),
+                    wall_clock("2025-06-02 08:41")
+                ),
+            ]
+        );
+
+        let first = &departures[0];
+        assert_eq!(first.display, "08:11");
+        assert_eq!(first.platform(), Some("A"));
+        assert_eq!(first.line.id, 1);
+        assert_eq!(first.line.transport_mode, "BUS");
+        assert_eq!(first.direction_code, Some(1));
+        assert_eq!(departures[1].line.transport_mode, "TRAIN");
+        assert_eq!(departures[1].platform(), Some("B"));
+        assert!(departures.iter().all(
+            |departure| !departure.is_cancelled() && departure.expected == departure.scheduled
+        ));
+    }
+
+    #[test]
+    fn converts_other_timezones_to_stockholm_time() {
+        // The same timetable, written in Helsinki time, an hour ahead of Stockholm
+        let feed = GtfsFeed::from_tables(|name| match name {
+            "agency.txt" => Ok(Some(
+                b"agency_id,agency_name,agency_url,agency_timezone\nHD,Homedisplay Transit,https://example.org,Europe/Helsinki\n".to_vec(),
+            )),
+            name => Ok(std::fs::read(format!("{SAMPLE}/{name}")).ok()),
+        })
+        .unwrap();
+
+        let departures = feed.departures(
+            CENTRAL_STATION,
+            stockholm("2025-06-02 07:05"),
+            &RealtimeUpdates::default(),
+        );
+        let first = &departures[0];
+        assert_eq!(first.scheduled, wall_clock("2025-06-02 07:11"));
+        assert_eq!(first.display, "08:11");
+    }
+
+    #[test]
+    fn rejects_an_unknown_agency_timezone() {
+        let feed = GtfsFeed::from_tables(|name| {
+            match name {
+            "agency.txt" => Ok(Some(
+                b"agency_id,agency_name,agency_url,agency_timezone\nHD,Homedisplay Transit,https://example.org,Europe/Atlantis\n".to_vec(),
+            )),
+            name => Ok(std::fs::read(format!("{SAMPLE}/{name}")).ok()),
+        }
+        });
+        assert!(feed.is_err());
+    }
+
+    #[test]
+    fn applies_the_realtime_delays_and_cancellations() {
+        let departures = sample().departures(
+            CENTRAL_STATION,
+            stockholm("2025-06-02 07:55"),
+            &sample_realtime(),
+        );
+        assert_eq!(departures.len(), 4);
+
+        // T1 left the Harbour three minutes late
+        let delayed = &departures[0];
+        assert_eq!(delayed.scheduled, wall_clock("2025-06-02 08:11"));
+        assert_eq!(delayed.expected, wall_clock("2025-06-02 08:14"));
+        assert_eq!(delayed.delay_minutes(), Some(3));
+        assert_eq!(delayed.display, "08:14");
+        assert!(!delayed.is_cancelled());
+
+        // T2 is cancelled, and kept to be shown as such
+        let cancelled = departures
+            .iter()
+            .find(|departure| departure.scheduled == wall_clock("2025-06-02 08:41"))
+            .unwrap();
+        assert!(cancelled.is_cancelled());
+        assert_eq!(
+            departures
+                .iter()
+                .filter(|departure| departure.is_cancelled())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn follows_the_service_calendar() {
+        // Weekday service removed on Christmas Eve, only the daily Airport Express runs
+        let departures = sample().departures(
+            CENTRAL_STATION,
+            stockholm("2025-12-24 07:55"),
+            &RealtimeUpdates::default(),
+        );
+        assert_eq!(
+            summary(&departures),
+            vec![(
+                "Airport Express".to_string(),
+                "Airport".to_string(),
+                wall_clock("2025-12-24 08:15")
+            )]
+        );
+
+        // No weekday service on Sundays either
+        let departures = sample().departures(
+            CENTRAL_STATION,
+            stockholm("2025-06-01 07:55"),
+            &RealtimeUpdates::default(),
+        );
+        assert_eq!(departures.len(), 1);
+    }
+
+    #[test]
+    fn keeps_the_trips_running_past_midnight() {
+        // T5 leaves at 24:15 of its service day, a quarter past midnight the day after
+        let departures = sample().departures(
+            CENTRAL_STATION,
+            stockholm("2025-06-03 00:05"),
+            &RealtimeUpdates::default(),
+        );
+        assert_eq!(
+            summary(&departures),
+            vec![(
+                "Airport Express".to_string(),
+                "Airport".to_string(),
+                wall_clock("2025-06-03 00:15")
+            )]
+        );
+    }
+
+    #[test]
+    fn counts_the_times_from_noon_on_daylight_saving_days() {
+        // The clocks are set forward at 2:00 on the 30th of March 2025
+        let departures = sample().departures(
+            CENTRAL_STATION,
+            stockholm("2025-03-30 07:55"),
+            &RealtimeUpdates::default(),
+        );
+        assert_eq!(departures[0].scheduled, wall_clock("2025-03-30 08:15"));
+    }
+
+    #[test]
+    fn loads_the_feed_again_once_modified() {
+        let directory =
+            std::env::temp_dir().join(format!("homedisplay-gtfs-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for entry in std::fs::read_dir(SAMPLE).unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), directory.join(entry.file_name())).unwrap();
+        }
+        let path = directory.to_string_lossy().to_string();
+        let name = |feed: &GtfsFeed| {
+            feed.sites()
+                .into_iter()
+                .find(|site| site.id == CENTRAL_STATION)
+                .map(|site| site.name)
+        };
+
+        let feed = super::load_feed(&path).unwrap();
+        assert_eq!(name(&feed).as_deref(), Some("Central Station"));
+        // Unchanged, the parsed feed is reused
+        assert!(std::sync::Arc::ptr_eq(
+            &feed,
+            &super::load_feed(&path).unwrap()
+        ));
+
+        let stops = directory.join("stops.txt");
+        let content = std::fs::read_to_string(&stops).unwrap();
+        std::fs::write(&stops, content.replace("Central Station", "Centralen")).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&stops)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))
+            .unwrap();
+
+        let reloaded = super::load_feed(&path).unwrap();
+        assert_eq!(name(&reloaded).as_deref(), Some("Centralen"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+/// The subset of the GTFS-Realtime protobuf messages needed to overlay trip updates on the
+/// static schedule. Field tags follow `gtfs-realtime.proto`.
+use prost::Message;
+use std::collections::HashMap;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FeedMessage {
+    #[prost(message, required, tag = "1")]
+    pub header: FeedHeader,
+    #[prost(message, repeated, tag = "2")]
+    pub entity: Vec<FeedEntity>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FeedHeader {
+    #[prost(string, required, tag = "1")]
+    pub gtfs_realtime_version: String,
+    #[prost(uint64, optional, tag = "3")]
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FeedEntity {
+    #[prost(string, required, tag = "1")]
+    pub id: String,
+    #[prost(message, optional, tag = "3")]
+    pub trip_update: Option<TripUpdate>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TripUpdate {
+    #[prost(message, required, tag = "1")]
+    pub trip: TripDescriptor,
+    #[prost(message, repeated, tag = "2")]
+    pub stop_time_update: Vec<StopTimeUpdate>,
+    #[prost(int32, optional, tag = "5")]
+    pub delay: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TripDescriptor {
+    #[prost(string, optional, tag = "1")]
+    pub trip_id: Option<String>,
+    #[prost(string, optional, tag = "5")]
+    pub route_id: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub start_date: Option<String>,
+    /// SCHEDULED = 0, ADDED = 1, UNSCHEDULED = 2, CANCELED = 3
+    #[prost(int32, optional, tag = "4")]
+    pub schedule_relationship: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StopTimeUpdate {
+    #[prost(uint32, optional, tag = "1")]
+    pub stop_sequence: Option<u32>,
+    #[prost(message, optional, tag = "2")]
+    pub arrival: Option<StopTimeEvent>,
+    #[prost(message, optional, tag = "3")]
+    pub departure: Option<StopTimeEvent>,
+    #[prost(string, optional, tag = "4")]
+    pub stop_id: Option<String>,
+    /// SCHEDULED = 0, SKIPPED = 1, NO_DATA = 2
+    #[prost(int32, optional, tag = "5")]
+    pub schedule_relationship: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StopTimeEvent {
+    #[prost(int32, optional, tag = "1")]
+    pub delay: Option<i32>,
+    #[prost(int64, optional, tag = "2")]
+    pub time: Option<i64>,
+}
+
+const TRIP_CANCELED: i32 = 3;
+const STOP_SKIPPED: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The realtime state of a trip at one of its stops
+pub enum StopStatus {
+    /// The vehicle leaves at the given unix timestamp
+    At(i64),
+    /// The vehicle leaves this many seconds after its schedule (or before, if negative)
+    Delayed(i32),
+    /// The trip does not serve the stop anymore
+    Cancelled,
+}
+
+#[derive(Debug, Default)]
+/// The trip updates of one or more GTFS-Realtime feeds, indexed by trip id
+pub struct RealtimeUpdates {
+    trips: HashMap<String, TripUpdate>,
+}
+
+impl RealtimeUpdates {
+    /// Decodes a GTFS-Realtime feed and adds its trip updates
+    pub fn add_feed(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let feed = FeedMessage::decode(bytes)
+            .map_err(|e| format!("Unable to decode GTFS-Realtime feed: {e}"))?;
+
+        for update in feed
+            .entity
+            .into_iter()
+            .filter_map(|entity| entity.trip_update)
+        {
+            if let Some(trip_id) = update.trip.trip_id.clone() {
+                self.trips.insert(trip_id, update);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the realtime state of the trip at the given stop. Delays of earlier stops
+    /// propagate to the following ones, as stated by the specification.
+    pub fn status(&self, trip_id: &str, stop_sequence: u32, stop_id: &str) -> Option<StopStatus> {
+        let update = self.trips.get(trip_id)?;
+        if update.trip.schedule_relationship == Some(TRIP_CANCELED) {
+            return Some(StopStatus::Cancelled);
+        }
+
+        let exact = update.stop_time_update.iter().find(|stop| {
+            stop.stop_sequence == Some(stop_sequence) || stop.stop_id.as_deref() == Some(stop_id)
+        });
+        if let Some(stop) = exact {
+            if stop.schedule_relationship == Some(STOP_SKIPPED) {
+                return Some(StopStatus::Cancelled);
+            }
+            let event = stop.departure.as_ref().or(stop.arrival.as_ref());
+            if let Some(time) = event.and_then(|event| event.time) {
+                return Some(StopStatus::At(time));
+            }
+            if let Some(delay) = event.and_then(|event| event.delay) {
+                return Some(StopStatus::Delayed(delay));
+            }
+        }
+
+        update
+            .stop_time_update
+            .iter()
+            .filter(|stop| {
+                stop.stop_sequence
+                    .is_some_and(|sequence| sequence < stop_sequence)
+            })
+            .max_by_key(|stop| stop.stop_sequence)
+            .and_then(|stop| stop.departure.as_ref().or(stop.arrival.as_ref()))
+            .and_then(|event| event.delay)
+            .or(update.delay)
+            .map(StopStatus::Delayed)
+    }
+}
@@ -0,0 +1,466 @@
+/// Loads a GTFS static feed (agency, stops, routes, trips, stop_times and calendars) and
+/// computes the departures of its stops from the timetable.
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::{Europe::Stockholm, Tz};
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::models::transports::{Coordinates, Departure, DepartureState, Line, Site, StopPoint};
+
+use super::realtime::{RealtimeUpdates, StopStatus};
+
+/// How far ahead departures are computed
+const DEPARTURE_WINDOW: Duration = Duration::hours(2);
+
+#[derive(Deserialize, Debug, Clone)]
+struct GtfsAgency {
+    agency_timezone: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GtfsStop {
+    stop_id: String,
+    stop_name: String,
+    #[serde(default)]
+    stop_lat: Option<f32>,
+    #[serde(default)]
+    stop_lon: Option<f32>,
+    #[serde(default)]
+    location_type: Option<u8>,
+    #[serde(default)]
+    parent_station: Option<String>,
+    #[serde(default)]
+    platform_code: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GtfsRoute {
+    route_id: String,
+    #[serde(default)]
+    route_short_name: String,
+    #[serde(default)]
+    route_long_name: String,
+    route_type: u16,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GtfsTrip {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    #[serde(default)]
+    trip_headsign: Option<String>,
+    #[serde(default)]
+    direction_id: Option<i32>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GtfsStopTime {
+    trip_id: String,
+    #[serde(default)]
+    arrival_time: String,
+    #[serde(default)]
+    departure_time: String,
+    stop_id: String,
+    stop_sequence: u32,
+    #[serde(default)]
+    stop_headsign: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GtfsCalendar {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: String,
+    end_date: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GtfsCalendarDate {
+    service_id: String,
+    date: String,
+    exception_type: u8, // 1: service added, 2: service removed
+}
+
+#[derive(Debug, Clone)]
+/// A scheduled passage of a trip at a stop
+struct StopTime {
+    trip_id: String,
+    departure: u32, // Seconds since noon minus 12h on the service day, may exceed 24h
+    stop_sequence: u32,
+    stop_headsign: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+/// The days a service runs on
+struct Service {
+    weekdays: [bool; 7], // Monday first
+    start: NaiveDate,
+    end: NaiveDate,
+}
+
+#[derive(Debug, Default)]
+/// A GTFS static feed, indexed for departure lookups
+pub struct GtfsFeed {
+    timezone: Tz, // Of the agency, the timetable times are wall-clock times in it
+    stops: HashMap<String, GtfsStop>,
+    routes: HashMap<String, GtfsRoute>,
+    trips: HashMap<String, GtfsTrip>,
+    stop_times: HashMap<String, Vec<StopTime>>, // By stop id, sorted by departure
+    services: HashMap<String, Service>,
+    exceptions: HashMap<(String, NaiveDate), bool>, // Whether the service runs that day
+}
+
+/// Parses a GTFS time (`HH:MM:SS`, hours may exceed 23) into seconds
+fn parse_time(time: &str) -> Option<u32> {
+    let mut parts = time.trim().split(':').map(|part| part.parse::<u32>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Parses a GTFS date (`YYYYMMDD`)
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date.trim(), "%Y%m%d")
+        .map_err(|e| format!("Invalid GTFS date `{date}`: {e}"))
+}
+
+/// Returns the Stockholm wall-clock time of an instant, the times of the departures being
+/// Stockholm wall-clock times whatever the provider
+fn wall_clock(time: DateTime<Utc>) -> NaiveDateTime {
+    time.with_timezone(&Stockholm).naive_local()
+}
+
+/// Parses the records of a GTFS table
+fn parse_table<T: DeserializeOwned>(name: &str, content: &[u8]) -> Result<Vec<T>, String> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content)
+        .deserialize()
+        .collect::<Result<Vec<T>, csv::Error>>()
+        .map_err(|e| format!("Invalid GTFS table {name}: {e}"))
+}
+
+/// Returns the transport mode of a GTFS route type, named as by SL. Both the basic and
+/// the extended route types are supported.
+fn transport_mode(route_type: u16) -> &'static str {
+    match route_type {
+        0 | 5 | 6 | 900..=999 => "TRAM",
+        1 | 400..=499 => "METRO",
+        2 | 100..=199 => "TRAIN",
+        4 | 1000..=1099 | 1200..=1299 => "SHIP",
+        _ => "BUS",
+    }
+}
+
+/// Returns a numeric id for a route: its short name when numeric, otherwise a stable hash
+/// of its id
+fn line_id(route: &GtfsRoute) -> i32 {
+    route
+        .route_short_name
+        .parse::<i32>()
+        .unwrap_or_else(|_| Line::hashed_id(&route.route_id))
+}
+
+impl GtfsFeed {
+    /// Loads a feed from a zip archive, or from a directory holding the extracted tables
+    pub fn from_path(path: &str) -> Result<GtfsFeed, String> {
+        if Path::new(path).is_dir() {
+            GtfsFeed::from_tables(|name| match fs::read(Path::new(path).join(name)) {
+                Ok(content) => Ok(Some(content)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(format!("Unable to read {name}: {e}")),
+            })
+        } else {
+            let file = fs::File::open(path)
+                .map_err(|e| format!("Unable to open GTFS feed {path}: {e}"))?;
+            let mut archive = zip::ZipArchive::new(file)
+                .map_err(|e| format!("Invalid GTFS archive {path}: {e}"))?;
+            GtfsFeed::from_tables(|name| match archive.by_name(name) {
+                Ok(mut entry) => {
+                    let mut content = vec![];
+                    entry
+                        .read_to_end(&mut content)
+                        .map_err(|e| format!("Unable to read {name}: {e}"))?;
+                    Ok(Some(content))
+                }
+                Err(zip::result::ZipError::FileNotFound) => Ok(None),
+                Err(e) => Err(format!("Unable to read {name}: {e}")),
+            })
+        }
+    }
+
+    /// Builds a feed from its tables, `read` returning the content of a table by file name,
+    /// or `None` when the feed does not contain it
+    pub fn from_tables(
+        mut read: impl FnMut(&str) -> Result<Option<Vec<u8>>, String>,
+    ) -> Result<GtfsFeed, String> {
+        let mut required = |name: &str| -> Result<Vec<u8>, String> {
+            read(name)?.ok_or(format!("The GTFS feed has no {name}"))
+        };
+        let stops: Vec<GtfsStop> = parse_table("stops.txt", &required("stops.txt")?)?;
+        let routes: Vec<GtfsRoute> = parse_table("routes.txt", &required("routes.txt")?)?;
+        let trips: Vec<GtfsTrip> = parse_table("trips.txt", &required("trips.txt")?)?;
+        let stop_times: Vec<GtfsStopTime> =
+            parse_table("stop_times.txt", &required("stop_times.txt")?)?;
+        let agencies: Vec<GtfsAgency> = match read("agency.txt")? {
+            Some(content) => parse_table("agency.txt", &content)?,
+            None => vec![],
+        };
+        let calendars: Vec<GtfsCalendar> = match read("calendar.txt")? {
+            Some(content) => parse_table("calendar.txt", &content)?,
+            None => vec![],
+        };
+        let calendar_dates: Vec<GtfsCalendarDate> = match read("calendar_dates.txt")? {
+            Some(content) => parse_table("calendar_dates.txt", &content)?,
+            None => vec![],
+        };
+
+        // All the agencies of a feed share the same timezone
+        let timezone = match agencies.first() {
+            Some(agency) => agency.agency_timezone.trim().parse::<Tz>().map_err(|e| {
+                format!(
+                    "Invalid GTFS agency_timezone `{}`: {e}",
+                    agency.agency_timezone
+                )
+            })?,
+            None => {
+                warn!("The GTFS feed has no agency.txt, reading its times in the Europe/Stockholm timezone");
+                Stockholm
+            }
+        };
+
+        let mut feed = GtfsFeed {
+            timezone,
+            stops: stops
+                .into_iter()
+                .map(|stop| (stop.stop_id.clone(), stop))
+                .collect(),
+            routes: routes
+                .into_iter()
+                .map(|route| (route.route_id.clone(), route))
+                .collect(),
+            trips: trips
+                .into_iter()
+                .map(|trip| (trip.trip_id.clone(), trip))
+                .collect(),
+            ..GtfsFeed::default()
+        };
+
+        for calendar in calendars {
+            feed.services.insert(
+                calendar.service_id,
+                Service {
+                    weekdays: [
+                        calendar.monday == 1,
+                        calendar.tuesday == 1,
+                        calendar.wednesday == 1,
+                        calendar.thursday == 1,
+                        calendar.friday == 1,
+                        calendar.saturday == 1,
+                        calendar.sunday == 1,
+                    ],
+                    start: parse_date(&calendar.start_date)?,
+                    end: parse_date(&calendar.end_date)?,
+                },
+            );
+        }
+        for exception in calendar_dates {
+            feed.exceptions.insert(
+                (exception.service_id, parse_date(&exception.date)?),
+                exception.exception_type == 1,
+            );
+        }
+
+        for stop_time in stop_times {
+            let time = if stop_time.departure_time.is_empty() {
+                &stop_time.arrival_time
+            } else {
+                &stop_time.departure_time
+            };
+            // Untimed stops are skipped, only timepoints are displayed
+            let Some(departure) = parse_time(time) else {
+                continue;
+            };
+            feed.stop_times
+                .entry(stop_time.stop_id)
+                .or_default()
+                .push(StopTime {
+                    trip_id: stop_time.trip_id,
+                    departure,
+                    stop_sequence: stop_time.stop_sequence,
+                    stop_headsign: stop_time.stop_headsign.filter(|h| !h.is_empty()),
+                });
+        }
+        for times in feed.stop_times.values_mut() {
+            times.sort_by_key(|time| time.departure);
+        }
+
+        Ok(feed)
+    }
+
+    /// Returns the timezone of the agency, the timetable being written in it
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    /// Returns the instant the times of a service day are counted from: noon minus twelve
+    /// hours in the agency timezone, which is not midnight on the days the clocks change
+    fn service_day_start(&self, day: NaiveDate) -> Option<DateTime<Utc>> {
+        let noon = self
+            .timezone
+            .from_local_datetime(&day.and_hms_opt(12, 0, 0)?)
+            .single()?;
+        Some(noon.with_timezone(&Utc) - Duration::hours(12))
+    }
+
+    /// Returns whether the service runs on the given day
+    fn runs_on(&self, service_id: &str, date: NaiveDate) -> bool {
+        if let Some(runs) = self.exceptions.get(&(service_id.to_string(), date)) {
+            return *runs;
+        }
+        self.services.get(service_id).is_some_and(|service| {
+            service.start <= date
+                && date <= service.end
+                && service.weekdays[date.weekday().num_days_from_monday() as usize]
+        })
+    }
+
+    /// Returns the sites of the feed: stations, and the stops that are not part of a station
+    pub fn sites(&self) -> Vec<Site> {
+        let mut sites: Vec<Site> = self
+            .stops
+            .values()
+            .filter(|stop| {
+                stop.location_type == Some(1)
+                    || (stop.location_type.unwrap_or(0) == 0
+                        && stop.parent_station.as_deref().unwrap_or("").is_empty())
+            })
+            .map(|stop| Site {
+                name: stop.stop_name.clone(),
+                id: stop.stop_id.clone(),
+                coord: Coordinates {
+                    latitude: stop.stop_lat.unwrap_or(0.0),
+                    longitude: stop.stop_lon.unwrap_or(0.0),
+                },
+            })
+            .collect();
+        sites.sort_by(|a, b| a.name.cmp(&b.name));
+        sites
+    }
+
+    /// Returns the stops served at the given site: the site itself and, for a station,
+    /// its platforms
+    fn site_stops(&self, site_id: &str) -> Vec<&GtfsStop> {
+        self.stops
+            .values()
+            .filter(|stop| {
+                stop.stop_id == site_id || stop.parent_station.as_deref() == Some(site_id)
+            })
+            .collect()
+    }
+
+    /// Returns the departures of the site within the next two hours of the given instant,
+    /// with the realtime updates applied. Cancelled departures are kept and marked as such.
+    /// The times of the departures are converted from the agency timezone to Stockholm
+    /// wall-clock times, as given by SL.
+    pub fn departures(
+        &self,
+        site_id: &str,
+        now: DateTime<Utc>,
+        realtime: &RealtimeUpdates,
+    ) -> Vec<Departure> {
+        let mut departures: Vec<Departure> = vec![];
+        let today = now.with_timezone(&self.timezone).date_naive();
+
+        // Trips of the previous service day may run past midnight
+        for service_day in [today - Duration::days(1), today] {
+            let Some(day_start) = self.service_day_start(service_day) else {
+                continue;
+            };
+
+            for stop in self.site_stops(site_id) {
+                for stop_time in self.stop_times.get(&stop.stop_id).into_iter().flatten() {
+                    let scheduled = day_start + Duration::seconds(stop_time.departure as i64);
+                    if scheduled + DEPARTURE_WINDOW < now || scheduled > now + DEPARTURE_WINDOW {
+                        continue;
+                    }
+                    let Some(trip) = self.trips.get(&stop_time.trip_id) else {
+                        continue;
+                    };
+                    if !self.runs_on(&trip.service_id, service_day) {
+                        continue;
+                    }
+                    let Some(route) = self.routes.get(&trip.route_id) else {
+                        continue;
+                    };
+
+                    let status =
+                        realtime.status(&trip.trip_id, stop_time.stop_sequence, &stop.stop_id);
+                    let expected = match status {
+                        Some(StopStatus::At(timestamp)) => DateTime::from_timestamp(timestamp, 0),
+                        Some(StopStatus::Delayed(delay)) => {
+                            Some(scheduled + Duration::seconds(delay as i64))
+                        }
+                        _ => None,
+                    };
+                    if expected.unwrap_or(scheduled) < now {
+                        continue;
+                    }
+
+                    departures.push(Departure {
+                        destination: stop_time
+                            .stop_headsign
+                            .clone()
+                            .or(trip.trip_headsign.clone())
+                            .unwrap_or(route.route_long_name.clone()),
+                        direction_code: trip.direction_id.map(|direction| direction + 1),
+                        display: expected
+                            .unwrap_or(scheduled)
+                            .with_timezone(&self.timezone)
+                            .format("%H:%M")
+                            .to_string(),
+                        state: if status == Some(StopStatus::Cancelled) {
+                            DepartureState::Cancelled
+                        } else {
+                            DepartureState::Expected
+                        },
+                        scheduled: Some(wall_clock(scheduled)),
+                        expected: Some(wall_clock(expected.unwrap_or(scheduled))),
+                        line: Line {
+                            id: line_id(route),
+                            designation: if route.route_short_name.is_empty() {
+                                route.route_long_name.clone()
+                            } else {
+                                route.route_short_name.clone()
+                            },
+                            transport_mode: transport_mode(route.route_type).to_string(),
+                            group_of_lines: None,
+                        },
+                        stop_point: Some(StopPoint {
+                            id: stop.stop_id.parse().unwrap_or_default(),
+                            name: stop.stop_name.clone(),
+                            designation: stop.platform_code.clone(),
+                        }),
+                        journey: None,
+                        deviations: vec![],
+                        walking_minutes: None,
+                    });
+                }
+            }
+        }
+
+        departures.sort_by_key(|departure| departure.departure_time());
+        departures
+    }
+}
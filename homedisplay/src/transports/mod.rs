@@ -1,2 +1,7 @@
 pub mod api;
+pub mod catalogue;
 pub mod database;
+pub mod filters;
+pub mod gtfs;
+pub mod provider;
+pub mod resrobot;
@@ -0,0 +1,63 @@
+/// The sources of sites and departures the transports can be fetched from
+use async_trait::async_trait;
+
+use crate::models::transports::{Departure, Site};
+use crate::settings::{BusStop, Transit, TransitProvider};
+use crate::traits::Api;
+
+use super::gtfs::GtfsProvider;
+use super::resrobot::ResRobot;
+
+#[async_trait]
+/// A transit data source, producing the sites and departures of a network
+pub trait Provider: Send + Sync {
+    /// Returns the sites of the network. Providers able to list the whole network return
+    /// every site, those only offering a search return the matches of the stops' names
+    async fn sites(&self, stops: &[BusStop]) -> Result<Vec<Site>, String>;
+
+    /// Returns the upcoming departures of the given site
+    async fn departures(&self, site_id: &str) -> Result<Vec<Departure>, String>;
+}
+
+/// Storstockholms Lokaltrafik, through the `transport.integration.sl.se` API
+pub struct Sl;
+
+#[async_trait]
+impl Provider for Sl {
+    async fn sites(&self, _stops: &[BusStop]) -> Result<Vec<Site>, String> {
+        Site::api_get(()).await
+    }
+
+    async fn departures(&self, site_id: &str) -> Result<Vec<Departure>, String> {
+        Departure::api_get(site_id.to_string()).await
+    }
+}
+
+/// Builds the provider configured in the transit settings
+pub fn from_settings(transit: &Transit) -> Result<Box<dyn Provider>, String> {
+    match transit.provider {
+        TransitProvider::Sl => Ok(Box::new(Sl)),
+        TransitProvider::Gtfs => {
+            let path = transit
+                .gtfs_static
+                .as_ref()
+                .ok_or("The GTFS provider requires a `gtfs_static` feed".to_string())?;
+            Ok(Box::new(GtfsProvider::load(
+                path,
+                transit.gtfs_realtime.clone(),
+            )?))
+        }
+        TransitProvider::ResRobot => {
+            if transit.api_key.is_empty() {
+                return Err("The ResRobot provider requires an `api_key`".to_string());
+            }
+            Ok(Box::new(ResRobot::new(transit.api_key.expose())))
+        }
+    }
+}
+
+/// Returns whether the configured provider lists the whole network, rather than only the
+/// sites matching the stops
+pub fn lists_all_sites(transit: &Transit) -> bool {
+    transit.provider != TransitProvider::ResRobot
+}
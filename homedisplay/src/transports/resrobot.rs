@@ -0,0 +1,423 @@
+/// Trafiklab ResRobot, the national Swedish timetables, covering the regional networks
+/// outside of SL (UL, Västtrafik, Skånetrafiken, ...)
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use log::{debug, info, warn};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+
+use crate::models::transports::{Coordinates, Departure, DepartureState, Line, Site, StopPoint};
+use crate::settings::BusStop;
+
+use super::provider::Provider;
+
+const RESROBOT_URL: &str = "https://api.resrobot.se/v2.1";
+
+/// How far ahead departures are requested, in minutes
+const DEPARTURE_WINDOW: u32 = 120;
+
+#[derive(Deserialize, Debug)]
+struct LocationResponse {
+    #[serde(default, rename = "stopLocationOrCoordLocation")]
+    locations: Vec<LocationAPI>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LocationAPI {
+    #[serde(rename = "StopLocation")]
+    stop: Option<StopLocationAPI>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StopLocationAPI {
+    #[serde(rename = "extId")]
+    ext_id: String,
+    name: String,
+    #[serde(default)]
+    lat: Option<f32>,
+    #[serde(default)]
+    lon: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DepartureBoardResponse {
+    #[serde(default, rename = "Departure")]
+    departures: Vec<DepartureAPI>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ProductAPI {
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "displayNumber")]
+    display_number: Option<String>,
+    #[serde(default)]
+    line: Option<String>,
+    #[serde(default, rename = "catCode")]
+    cat_code: Option<String>,
+    #[serde(default, rename = "catOutL")]
+    category: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DepartureAPI {
+    #[serde(rename = "ProductAtStop")]
+    product: ProductAPI,
+    stop: String,
+    #[serde(rename = "stopExtId")]
+    stop_ext_id: String,
+    time: String,
+    date: String,
+    #[serde(default, rename = "rtTime")]
+    rt_time: Option<String>,
+    #[serde(default, rename = "rtDate")]
+    rt_date: Option<String>,
+    #[serde(default)]
+    track: Option<String>,
+    #[serde(default, rename = "rtTrack")]
+    rt_track: Option<String>,
+    direction: String,
+    #[serde(default, rename = "directionFlag")]
+    direction_flag: Option<String>,
+    #[serde(default)]
+    cancelled: bool,
+}
+
+/// Parses a ResRobot date (`YYYY-MM-DD`) and time (`HH:MM:SS`)
+fn parse_datetime(date: &str, time: &str) -> Option<NaiveDateTime> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S").ok()?;
+    Some(date.and_time(time))
+}
+
+/// Returns the id of a line from its designation: the number itself when numeric,
+/// otherwise a stable hash of the designation (e.g. `Röd`, `T14X` or `N3`)
+fn line_id(designation: &str) -> i32 {
+    designation.parse().unwrap_or_else(|_| {
+        debug!("Line {designation} is not numeric, identified by a hash of its name");
+        Line::hashed_id(designation)
+    })
+}
+
+/// Returns the transport mode of a ResRobot product category, named as by SL
+fn transport_mode(cat_code: Option<&str>) -> &'static str {
+    match cat_code {
+        Some("1") | Some("2") | Some("4") => "TRAIN",
+        Some("5") => "METRO",
+        Some("6") => "TRAM",
+        Some("8") => "SHIP",
+        _ => "BUS",
+    }
+}
+
+impl From<DepartureAPI> for Departure {
+    fn from(departure: DepartureAPI) -> Self {
+        let scheduled = parse_datetime(&departure.date, &departure.time);
+        let expected = match (&departure.rt_date, &departure.rt_time) {
+            (Some(date), Some(time)) => parse_datetime(date, time),
+            (None, Some(time)) => parse_datetime(&departure.date, time),
+            _ => None,
+        }
+        .or(scheduled);
+        let designation = departure
+            .product
+            .display_number
+            .or(departure.product.line)
+            .unwrap_or(departure.product.name);
+
+        Departure {
+            destination: departure.direction,
+            direction_code: departure.direction_flag.and_then(|flag| flag.parse().ok()),
+            display: expected
+                .map(|time| time.format("%H:%M").to_string())
+                .unwrap_or(departure.time),
+            state: if departure.cancelled {
+                DepartureState::Cancelled
+            } else {
+                DepartureState::Expected
+            },
+            scheduled,
+            expected,
+            line: Line {
+                id: line_id(&designation),
+                designation,
+                transport_mode: transport_mode(departure.product.cat_code.as_deref()).to_string(),
+                group_of_lines: departure.product.category,
+            },
+            stop_point: Some(StopPoint {
+                id: departure.stop_ext_id.parse().unwrap_or_default(),
+                name: departure.stop,
+                designation: departure.rt_track.or(departure.track),
+            }),
+            journey: None,
+            deviations: vec![],
+            walking_minutes: None,
+        }
+    }
+}
+
+/// The ResRobot timetables, authenticated with a Trafiklab access id
+pub struct ResRobot {
+    api_key: String,
+}
+
+impl ResRobot {
+    pub fn new(api_key: &str) -> ResRobot {
+        ResRobot {
+            api_key: api_key.to_string(),
+        }
+    }
+
+    /// Queries an endpoint of the API, the access id and the format being added to the
+    /// given parameters
+    async fn get<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, String> {
+        let url = Url::parse_with_params(
+            format!("{RESROBOT_URL}/{endpoint}").as_str(),
+            params
+                .iter()
+                .map(|(key, value)| (*key, value.as_str()))
+                .chain([("format", "json"), ("accessId", self.api_key.as_str())]),
+        )
+        .map_err(|e| format!("Could not parse URL: {e}"))?;
+
+        let result = reqwest::get(url)
+            .await
+            .map_err(|e| format!("Unable to reach ResRobot ({endpoint}): {e}"))?;
+        match result.status() {
+            reqwest::StatusCode::OK => result
+                .json::<T>()
+                .await
+                .map_err(|e| format!("Invalid ResRobot response ({endpoint}): {e}")),
+            status => Err(format!(
+                "Unexpected error ({status}) while querying ResRobot ({endpoint})"
+            )),
+        }
+    }
+
+    /// Searches the stops matching the given name
+    pub async fn lookup(&self, name: &str) -> Result<Vec<Site>, String> {
+        let response: LocationResponse = self
+            .get(
+                "location.name",
+                &[("input", name.to_string()), ("type", "S".to_string())],
+            )
+            .await?;
+
+        Ok(response
+            .locations
+            .into_iter()
+            .filter_map(|location| location.stop)
+            .map(|stop| Site {
+                name: stop.name,
+                id: stop.ext_id,
+                coord: Coordinates {
+                    latitude: stop.lat.unwrap_or(0.0),
+                    longitude: stop.lon.unwrap_or(0.0),
+                },
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Provider for ResRobot {
+    /// ResRobot can't list the whole country, the stops are looked up by name instead
+    async fn sites(&self, stops: &[BusStop]) -> Result<Vec<Site>, String> {
+        let mut sites: Vec<Site> = vec![];
+        for stop in stops.iter() {
+            match self.lookup(&stop.name).await {
+                Ok(found) => {
+                    info!("ResRobot: {} stops match {}", found.len(), stop.name);
+                    for site in found {
+                        if !sites.iter().any(|known| known.id == site.id) {
+                            sites.push(site);
+                        }
+                    }
+                }
+                Err(e) => warn!("ResRobot: unable to look up {}: {e}", stop.name),
+            }
+        }
+        Ok(sites)
+    }
+
+    async fn departures(&self, site_id: &str) -> Result<Vec<Departure>, String> {
+        let response: DepartureBoardResponse = self
+            .get(
+                "departureBoard",
+                &[
+                    ("id", site_id.to_string()),
+                    ("duration", DEPARTURE_WINDOW.to_string()),
+                ],
+            )
+            .await?;
+
+        Ok(response
+            .departures
+            .into_iter()
+            .map(Departure::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Departure board of Uppsala Centralstation, as returned by ResRobot
+    const DEPARTURE_BOARD: &str = r#"{
+        "Departure": [
+            {
+                "ProductAtStop": {
+                    "name": "Länstrafik - Buss 3",
+                    "internalName": "Länstrafik - Buss 3",
+                    "displayNumber": "3",
+                    "num": "40310",
+                    "line": "3",
+                    "lineId": "1276000003",
+                    "catOut": "BLT",
+                    "catIn": "BLT",
+                    "catCode": "7",
+                    "cls": "128",
+                    "catOutS": "BLT",
+                    "catOutL": "Länstrafik - Buss",
+                    "operatorCode": "279",
+                    "operator": "UL",
+                    "admin": "279"
+                },
+                "name": "Länstrafik - Buss 3",
+                "type": "ST",
+                "stop": "Uppsala Centralstation",
+                "stopid": "A=1@O=Uppsala Centralstation@X=17646009@Y=59858535@U=1@L=740000005@",
+                "stopExtId": "740000005",
+                "lon": 17.646009,
+                "lat": 59.858535,
+                "time": "23:58:00",
+                "date": "2024-03-12",
+                "rtTime": "00:03:00",
+                "rtDate": "2024-03-13",
+                "reachable": true,
+                "direction": "Gränby centrum (Uppsala kn)",
+                "directionFlag": "2",
+                "track": "B3",
+                "rtTrack": "B4"
+            },
+            {
+                "ProductAtStop": {
+                    "name": "Länstrafik - Tåg Röd",
+                    "displayNumber": "Röd",
+                    "line": "Röd",
+                    "catCode": "4",
+                    "catOutL": "Länstrafik - Tåg"
+                },
+                "stop": "Uppsala Centralstation",
+                "stopExtId": "740000005",
+                "time": "14:05:00",
+                "date": "2024-03-12",
+                "direction": "Gävle Centralstation",
+                "directionFlag": "1",
+                "track": "5"
+            },
+            {
+                "ProductAtStop": {
+                    "name": "Länstrafik - Buss N3",
+                    "catCode": "7",
+                    "catOutL": "Länstrafik - Buss"
+                },
+                "stop": "Uppsala Centralstation",
+                "stopExtId": "740000005",
+                "time": "14:10:00",
+                "date": "2024-03-12",
+                "rtTime": "14:10:00",
+                "direction": "Flogsta",
+                "cancelled": true
+            }
+        ]
+    }"#;
+
+    fn departures() -> Vec<Departure> {
+        serde_json::from_str::<DepartureBoardResponse>(DEPARTURE_BOARD)
+            .unwrap()
+            .departures
+            .into_iter()
+            .map(Departure::from)
+            .collect()
+    }
+
+    fn datetime(date: &str, time: &str) -> Option<NaiveDateTime> {
+        parse_datetime(date, time)
+    }
+
+    #[test]
+    fn maps_a_realtime_departure() {
+        let departure = &departures()[0];
+
+        assert_eq!(departure.line.id, 3);
+        assert_eq!(departure.line.designation, "3");
+        assert_eq!(departure.line.transport_mode, "BUS");
+        assert_eq!(
+            departure.line.group_of_lines.as_deref(),
+            Some("Länstrafik - Buss")
+        );
+        assert_eq!(departure.destination, "Gränby centrum (Uppsala kn)");
+        assert_eq!(departure.direction_code, Some(2));
+        assert_eq!(departure.scheduled, datetime("2024-03-12", "23:58:00"));
+        // Delayed past midnight, on the next day
+        assert_eq!(departure.expected, datetime("2024-03-13", "00:03:00"));
+        assert_eq!(departure.display, "00:03");
+        assert_eq!(departure.state, DepartureState::Expected);
+
+        let stop_point = departure.stop_point.as_ref().unwrap();
+        assert_eq!(stop_point.id, 740000005);
+        assert_eq!(stop_point.name, "Uppsala Centralstation");
+        assert_eq!(stop_point.designation.as_deref(), Some("B4"));
+    }
+
+    #[test]
+    fn maps_a_scheduled_departure() {
+        let departure = &departures()[1];
+
+        assert_eq!(departure.line.designation, "Röd");
+        assert_eq!(departure.line.transport_mode, "TRAIN");
+        assert_eq!(departure.direction_code, Some(1));
+        assert_eq!(departure.expected, departure.scheduled);
+        assert_eq!(departure.display, "14:05");
+        assert_eq!(
+            departure
+                .stop_point
+                .as_ref()
+                .unwrap()
+                .designation
+                .as_deref(),
+            Some("5")
+        );
+    }
+
+    #[test]
+    fn maps_a_cancelled_departure_named_after_its_product() {
+        let departure = &departures()[2];
+
+        assert_eq!(departure.line.designation, "Länstrafik - Buss N3");
+        assert_eq!(departure.direction_code, None);
+        assert_eq!(departure.expected, datetime("2024-03-12", "14:10:00"));
+        assert_eq!(departure.state, DepartureState::Cancelled);
+    }
+
+    #[test]
+    fn identifies_the_lines_that_are_not_numbered() {
+        let departures = departures();
+
+        assert_ne!(departures[1].line.id, 0);
+        assert_ne!(departures[2].line.id, 0);
+        assert_ne!(departures[1].line.id, departures[2].line.id);
+        // The ids are stable across refreshes
+        assert_eq!(departures[1].line.id, self::departures()[1].line.id);
+        assert_eq!(line_id("Röd"), departures[1].line.id);
+        assert_ne!(line_id("T14X"), line_id("N3"));
+        assert_eq!(line_id("43"), 43);
+    }
+}
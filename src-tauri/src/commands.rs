@@ -3,14 +3,15 @@ use log::trace;
 use std::sync::Mutex;
 use tauri::State;
 
-use homedisplay::models::currency::Conversion;
-use homedisplay::models::transports::{Departure, Site};
+use homedisplay::models::currency::{Conversion, ConversionHistory, TriggeredAlert};
+use homedisplay::models::quotes::QuoteTable;
+use homedisplay::models::transports::{Departure, RoutePlan, Site, TrafficDeviation};
 use homedisplay::models::weather::WeatherInfo;
 use homedisplay::settings::Settings;
 
 #[tauri::command]
-/// Get the current currency conversion from the database.
-pub async fn get_currency(settings: State<'_, Mutex<Settings>>) -> Result<Conversion, String> {
+/// Get the current currency conversions from the database.
+pub async fn get_currency(settings: State<'_, Mutex<Settings>>) -> Result<Vec<Conversion>, String> {
     trace!("Currency tauri command invoked");
     let (currency_settings, redis_data) = {
         let settings = match settings.lock() {
@@ -20,7 +21,57 @@ pub async fn get_currency(settings: State<'_, Mutex<Settings>>) -> Result<Conver
         (settings.currency.clone(), settings.redis.clone())
     };
 
-    homedisplay::currency::database::fetch_current_conversion(currency_settings, &redis_data).await
+    homedisplay::currency::database::fetch_current_conversions(currency_settings, &redis_data).await
+}
+
+#[tauri::command]
+/// Get the daily history of the configured currency conversions from the database.
+pub async fn get_currency_history(
+    settings: State<'_, Mutex<Settings>>,
+) -> Result<Vec<ConversionHistory>, String> {
+    trace!("Currency history tauri command invoked");
+    let (currency_settings, redis_data) = {
+        let settings = match settings.lock() {
+            Ok(s) => s,
+            Err(e) => return Err(e.to_string()),
+        };
+        (settings.currency.clone(), settings.redis.clone())
+    };
+
+    homedisplay::currency::database::fetch_conversion_histories(currency_settings, &redis_data)
+        .await
+}
+
+#[tauri::command]
+/// Evaluate the currency alert rules and return the triggered alerts.
+pub async fn get_currency_alerts(
+    settings: State<'_, Mutex<Settings>>,
+) -> Result<Vec<TriggeredAlert>, String> {
+    trace!("Currency alerts tauri command invoked");
+    let (currency_settings, redis_data) = {
+        let settings = match settings.lock() {
+            Ok(s) => s,
+            Err(e) => return Err(e.to_string()),
+        };
+        (settings.currency.clone(), settings.redis.clone())
+    };
+
+    homedisplay::currency::alerts::check_alerts(currency_settings, &redis_data).await
+}
+
+#[tauri::command]
+/// Get the cryptocurrency and commodity quotes of the configured symbols from the database.
+pub async fn get_quotes(settings: State<'_, Mutex<Settings>>) -> Result<QuoteTable, String> {
+    trace!("Quotes tauri command invoked");
+    let (quotes_settings, redis_data) = {
+        let settings = match settings.lock() {
+            Ok(s) => s,
+            Err(e) => return Err(e.to_string()),
+        };
+        (settings.quotes.clone(), settings.redis.clone())
+    };
+
+    homedisplay::quotes::database::fetch_quotes(quotes_settings, &redis_data).await
 }
 
 #[tauri::command]
@@ -28,32 +79,88 @@ pub async fn get_currency(settings: State<'_, Mutex<Settings>>) -> Result<Conver
 /// `SL_PLACE_BUS_STOPS` environment variable.
 pub async fn get_sites(settings: State<'_, Mutex<Settings>>) -> Result<Vec<Site>, String> {
     trace!("Sites tauri command invoked");
-    let (stops, redis_data) = {
+    let (stops, home, transit, redis_data) = {
         let settings = match settings.lock() {
             Ok(s) => s,
             Err(e) => return Err(e.to_string()),
         };
-        (settings.transports.clone(), settings.redis.clone())
+        (
+            settings.transports.clone(),
+            settings.weather.clone(),
+            settings.transit.clone(),
+            settings.redis.clone(),
+        )
     };
-    homedisplay::transports::database::get_sites(&stops, &redis_data).await
+    let stops =
+        homedisplay::transports::database::resolve_stops(&stops, &home, &transit, &redis_data)
+            .await?;
+    homedisplay::transports::database::get_sites(&stops, &transit, &redis_data).await
 }
 
 #[tauri::command]
-/// Returns the sites from the database. The list is filtered using elements in the
-/// `SL_PLACE_BUS_STOPS` environment variable.
+/// Returns the departures of a site, filtered using the lines, transport modes,
+/// destinations and direction of the matching bus stop of the settings.
 pub async fn get_departures(
     settings: State<'_, Mutex<Settings>>,
     site_id: String,
 ) -> Result<Vec<Departure>, String> {
     trace!("Departures tauri command invoked");
-    let redis_data = {
+    let (stops, home, transit, redis_data) = {
+        let settings = match settings.lock() {
+            Ok(s) => s,
+            Err(e) => return Err(e.to_string()),
+        };
+        (
+            settings.transports.clone(),
+            settings.weather.clone(),
+            settings.transit.clone(),
+            settings.redis.clone(),
+        )
+    };
+    let stops =
+        homedisplay::transports::database::resolve_stops(&stops, &home, &transit, &redis_data)
+            .await?;
+    homedisplay::transports::database::get_stop_departures(site_id, &stops, &transit, &redis_data)
+        .await
+}
+
+#[tauri::command]
+/// Returns the traffic deviations affecting the sites or lines of the bus stops of the settings.
+pub async fn get_deviations(
+    settings: State<'_, Mutex<Settings>>,
+) -> Result<Vec<TrafficDeviation>, String> {
+    trace!("Deviations tauri command invoked");
+    let (stops, home, transit, redis_data) = {
+        let settings = match settings.lock() {
+            Ok(s) => s,
+            Err(e) => return Err(e.to_string()),
+        };
+        (
+            settings.transports.clone(),
+            settings.weather.clone(),
+            settings.transit.clone(),
+            settings.redis.clone(),
+        )
+    };
+    let stops =
+        homedisplay::transports::database::resolve_stops(&stops, &home, &transit, &redis_data)
+            .await?;
+    let sites = homedisplay::transports::database::get_sites(&stops, &transit, &redis_data).await?;
+    homedisplay::transports::database::get_deviations(&sites, &stops, &transit, &redis_data).await
+}
+
+#[tauri::command]
+/// Returns the next itineraries of the routes of the settings.
+pub async fn get_routes(settings: State<'_, Mutex<Settings>>) -> Result<Vec<RoutePlan>, String> {
+    trace!("Routes tauri command invoked");
+    let (routes, redis_data) = {
         let settings = match settings.lock() {
             Ok(s) => s,
             Err(e) => return Err(e.to_string()),
         };
-        settings.redis.clone()
+        (settings.routes.clone(), settings.redis.clone())
     };
-    homedisplay::transports::database::get_departures(site_id, &redis_data).await
+    homedisplay::transports::database::get_route_plans(&routes, &redis_data).await
 }
 
 #[tauri::command]
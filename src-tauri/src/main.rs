@@ -4,9 +4,13 @@
 use std::sync::Mutex;
 
 use clap::Parser;
+use homedisplay::settings::loader::SettingsLoader;
+use homedisplay::settings::migration;
+use homedisplay::settings::validation::Severity;
+use homedisplay::settings::watcher::SettingsWatcher;
 use homedisplay::settings::Settings;
-use log::error;
-use tauri::{Builder, Manager};
+use log::{error, warn};
+use tauri::{Builder, Emitter, Manager};
 
 mod commands;
 
@@ -21,13 +25,38 @@ struct Args {
     #[arg(short = 's', long, default_value = "settings.toml")]
     /// Path to the settings file
     settings: String,
+
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    /// Overrides a setting, e.g. `--set weather.latitude=59.3`. Can be repeated
+    overrides: Vec<String>,
+
+    #[arg(long)]
+    /// Validates the settings file, prints the problems found and exits
+    check_settings: bool,
 }
 
 fn main() {
     env_logger::init();
     let args = Args::parse();
 
-    let settings = match Settings::load_from_file(&args.settings) {
+    if !args.check_settings {
+        if let Err(e) = migration::upgrade_file(&args.settings) {
+            error!("Unable to upgrade the settings file: {}", e);
+        }
+    }
+
+    let loader = match SettingsLoader::new()
+        .file(&args.settings)
+        .env()
+        .overrides(&args.overrides)
+    {
+        Ok(loader) => loader,
+        Err(err) => {
+            error!("Failed to load settings: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let settings = match loader.load() {
         Ok(settings) => settings,
         Err(err) => {
             error!("Failed to load settings: {}", err);
@@ -35,16 +64,84 @@ fn main() {
         }
     };
 
+    let report = settings.validate();
+    if args.check_settings {
+        if report.is_empty() {
+            println!("{}: no problem found", args.settings);
+        } else {
+            print!("{report}");
+        }
+        std::process::exit(if report.has_errors() { 1 } else { 0 });
+    }
+    for issue in report.issues {
+        match issue.severity {
+            Severity::Error => error!("Invalid settings: {}", issue),
+            Severity::Warning => warn!("Suspicious settings: {}", issue),
+        }
+    }
+
+    if let Err(errors) = tauri::async_runtime::block_on(
+        homedisplay::currency::database::validate_currencies(&settings.currency, &settings.redis),
+    ) {
+        for e in errors {
+            warn!("Invalid currency settings: {}", e);
+        }
+    }
+
+    if let Err(errors) =
+        tauri::async_runtime::block_on(homedisplay::transports::database::validate_stops(
+            &settings.transports,
+            &settings.transit,
+            &settings.redis,
+        ))
+    {
+        for e in errors {
+            warn!("Ambiguous stop settings: {}", e);
+        }
+    }
+
     Builder::default()
-        .setup(|app| {
-            app.manage(Mutex::new(settings));
+        .setup(move |app| {
+            // Managed before the watcher starts, as a reload may happen right away
+            app.manage(Mutex::new(settings.clone()));
+
+            // Swaps the settings of the commands when the file changes, the frontend then
+            // reloads its panels
+            let handle = app.handle().clone();
+            match SettingsWatcher::watch(
+                &args.settings,
+                loader,
+                settings,
+                move |settings, changes| {
+                    match handle.state::<Mutex<Settings>>().lock() {
+                        Ok(mut current) => *current = settings,
+                        Err(e) => {
+                            error!("Unable to apply the reloaded settings: {}", e);
+                            return;
+                        }
+                    }
+                    if let Err(e) = handle.emit("settings-changed", changes) {
+                        warn!("Unable to notify the frontend of the new settings: {}", e);
+                    }
+                },
+            ) {
+                Ok(watcher) => {
+                    app.manage(Mutex::new(watcher));
+                }
+                Err(e) => warn!("Settings changes won't be applied live: {}", e),
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_currency,
+            commands::get_currency_history,
+            commands::get_currency_alerts,
+            commands::get_quotes,
             commands::get_weather,
             commands::get_sites,
             commands::get_departures,
+            commands::get_deviations,
+            commands::get_routes,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
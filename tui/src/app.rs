@@ -6,16 +6,21 @@ use std::time::{Duration, SystemTime};
 
 use ratatui::{
     crossterm::event::{self, poll, Event, KeyCode, KeyEvent, KeyEventKind},
-    layout::{Constraint, Layout},
+    layout::{Constraint, Layout, Rect},
     Frame,
 };
 
+use homedisplay::settings::loader::SettingsLoader;
+use homedisplay::settings::validation::Severity;
+use homedisplay::settings::watcher::{SettingsChanges, SettingsWatcher};
 use homedisplay::settings::Settings;
 
-use crate::async_manager::{AsyncDataManager, DataUpdate, RefreshConfig};
-use crate::currency::CurrencyComponent;
+use crate::async_manager::{AsyncDataManager, DataUpdate, RefreshConfig, Task};
+use crate::currency::{AlertBanner, CurrencyComponent};
 use crate::datetime::DateTimeComponent;
 use crate::error::TuiError;
+use crate::quotes::QuotesComponent;
+use crate::stops::{StopAction, StopManager};
 use crate::transports::{Departures, TransportComponent};
 use crate::tui::Tui;
 use crate::weather::WeatherComponent;
@@ -23,14 +28,19 @@ use crate::weather::WeatherComponent;
 #[derive(Debug)]
 /// Main application state containing all UI components
 pub struct App {
-    pub exit: bool,                                        // Flag to exit the application
-    pub settings: Settings,                                // Application configuration
-    pub weather: WeatherComponent,                         // Weather display component
-    pub datetime: DateTimeComponent,                       // Date/time display component
-    pub currency: CurrencyComponent,                       // Currency conversion component
-    pub transports: TransportComponent,                    // Transport departure component
-    pub data_receiver: Option<mpsc::Receiver<DataUpdate>>, // Channel for async data updates
-    pub async_manager: Option<AsyncDataManager>,           // Async data manager (kept alive)
+    pub exit: bool,                                         // Flag to exit the application
+    pub settings: Settings,                                 // Application configuration
+    pub settings_file: String,                              // File the settings are saved to
+    pub weather: WeatherComponent,                          // Weather display component
+    pub datetime: DateTimeComponent,                        // Date/time display component
+    pub currency: CurrencyComponent,                        // Currency conversion component
+    pub quotes: QuotesComponent,                            // Crypto & commodity ticker
+    pub transports: TransportComponent,                     // Transport departure component
+    pub stop_manager: Option<StopManager>,                  // Bus stop editor, when open
+    pub data_receiver: Option<mpsc::Receiver<DataUpdate>>,  // Channel for async data updates
+    pub async_manager: Option<AsyncDataManager>,            // Async data manager (kept alive)
+    pub settings_watcher: Option<SettingsWatcher>,          // Watches the settings file
+    pub settings_updates: Option<mpsc::Receiver<Settings>>, // Reloaded settings
 }
 
 impl Default for App {
@@ -38,27 +48,32 @@ impl Default for App {
         Self {
             exit: false,
             settings: Settings::default(),
+            settings_file: "settings.toml".to_string(),
             weather: WeatherComponent::default(),
             datetime: DateTimeComponent::default(),
             currency: CurrencyComponent::default(),
+            quotes: QuotesComponent::default(),
             transports: TransportComponent::default(),
+            stop_manager: None,
             data_receiver: None,
             async_manager: None,
+            settings_watcher: None,
+            settings_updates: None,
         }
     }
 }
 
 impl App {
-    /// Loads settings from file and configures the application
-    pub fn with_settings(mut self, settings_file: &str) -> Self {
-        let settings = Settings::load_from_file(settings_file).unwrap_or_else(|e| {
-            log::error!(
-                "Unable to load settings from file: {}. Using default value",
-                e
-            );
-            Settings::default()
-        });
+    /// Configures the application with the given settings, read from the given file
+    pub fn with_settings(mut self, settings: Settings, settings_file: &str) -> Self {
+        for issue in settings.validate().issues {
+            match issue.severity {
+                Severity::Error => log::error!("Invalid settings: {}", issue),
+                Severity::Warning => log::warn!("Suspicious settings: {}", issue),
+            }
+        }
         self.settings = settings;
+        self.settings_file = settings_file.to_string();
         for timezone in self.settings.timezones.iter() {
             self.datetime.push_timezone(timezone.clone());
         }
@@ -66,6 +81,48 @@ impl App {
         self
     }
 
+    /// Reloads the settings with the given loader whenever the settings file changes
+    pub fn watch_settings(mut self, loader: SettingsLoader) -> Self {
+        let (tx, rx) = mpsc::channel();
+        match SettingsWatcher::watch(
+            &self.settings_file,
+            loader,
+            self.settings.clone(),
+            move |settings, _| {
+                let _ = tx.send(settings);
+            },
+        ) {
+            Ok(watcher) => {
+                self.settings_watcher = Some(watcher);
+                self.settings_updates = Some(rx);
+            }
+            Err(e) => log::warn!("Settings changes won't be applied live: {}", e),
+        }
+        self
+    }
+
+    /// Applies the reloaded settings, restarting the background tasks reading the sections
+    /// that changed
+    fn process_settings_updates(&mut self) {
+        let Some(receiver) = &self.settings_updates else {
+            return;
+        };
+        let Some(settings) = receiver.try_iter().last() else {
+            return;
+        };
+        // Several reloads may have been received, compare with the applied settings
+        let changes = SettingsChanges::between(&self.settings, &settings);
+        log::info!("Applying the reloaded settings: {:?}", changes);
+        self.settings = settings;
+
+        if changes.timezones {
+            self.datetime.set_timezones(self.settings.timezones.clone());
+        }
+        if let Some(manager) = &mut self.async_manager {
+            manager.restart_tasks(&Task::affected_by(&changes), &self.settings);
+        }
+    }
+
     /// Starts the async data manager and begins background data fetching
     pub fn start_async_manager(&mut self) -> Result<(), TuiError> {
         let mut manager = AsyncDataManager::new()?;
@@ -108,9 +165,24 @@ impl App {
         Ok(())
     }
 
-    /// Renders all components to the terminal frame in a three-column layout
+    /// Renders all components to the terminal frame in a three-column layout,
+    /// below a banner listing the triggered currency alerts and above the quotes ticker
     fn render_frame(&self, frame: &mut Frame) {
-        let chunks = Layout::horizontal([Constraint::Ratio(1, 3); 3]).split(frame.area());
+        let rows = Layout::vertical([
+            Constraint::Length(self.currency.alerts.len() as u16),
+            Constraint::Min(0),
+            Constraint::Length(self.quotes.height()),
+        ])
+        .split(frame.area());
+
+        frame.render_widget(
+            AlertBanner {
+                alerts: &self.currency.alerts,
+            },
+            rows[0],
+        );
+
+        let chunks = Layout::horizontal([Constraint::Ratio(1, 3); 3]).split(rows[1]);
 
         let middle_split =
             Layout::vertical([Constraint::Ratio(4, 5), Constraint::Ratio(1, 5)]).split(chunks[1]);
@@ -119,10 +191,98 @@ impl App {
         frame.render_widget(&self.datetime, middle_split[0]);
         frame.render_widget(&self.currency, middle_split[1]);
         frame.render_widget(&self.transports, chunks[2]);
+        frame.render_widget(&self.quotes, rows[2]);
+
+        if let Some(stop_manager) = &self.stop_manager {
+            let area = frame.area();
+            let modal = Rect::new(
+                area.width / 10,
+                area.height / 10,
+                area.width - area.width / 5,
+                area.height - area.height / 5,
+            );
+            frame.render_widget(stop_manager, modal);
+        }
+    }
+
+    /// Carries out the work requested by the stop manager
+    fn run_stop_action(&mut self, action: StopAction) {
+        match action {
+            StopAction::None => {}
+            StopAction::Search(query) => {
+                if let Some(manager) = &self.async_manager {
+                    manager.search_sites(
+                        query,
+                        self.settings.transit.clone(),
+                        self.settings.redis.clone(),
+                    );
+                }
+            }
+            StopAction::Preview(site_id) => {
+                if let Some(manager) = &self.async_manager {
+                    manager.preview_departures(
+                        site_id,
+                        self.settings.transit.clone(),
+                        self.settings.redis.clone(),
+                    );
+                }
+            }
+            StopAction::Save => self.save_stops(),
+            StopAction::SaveAndClose => {
+                self.save_stops();
+                // Left open with the error when the stops could not be saved
+                if self
+                    .stop_manager
+                    .as_ref()
+                    .is_some_and(|stop_manager| !stop_manager.modified)
+                {
+                    self.stop_manager = None;
+                }
+            }
+            StopAction::Close => self.stop_manager = None,
+        }
+    }
+
+    /// Writes the stops of the stop manager to the settings file, and restarts the transport
+    /// task so that the departures of the new stops are fetched
+    fn save_stops(&mut self) {
+        let Some(stop_manager) = &mut self.stop_manager else {
+            return;
+        };
+        self.settings.transports = stop_manager.stops.clone();
+
+        // Only the stops of the file are rewritten, so that the values coming from the
+        // environment or the command line (e.g. api keys) are not written to disk
+        let saved = Settings::save_transports(&self.settings_file, &stop_manager.stops);
+        match saved {
+            Ok(_) => {
+                log::info!(
+                    "Saved {} stops to {}",
+                    stop_manager.stops.len(),
+                    self.settings_file
+                );
+                stop_manager.status = Some(format!(
+                    "Saved {} stops to {}",
+                    stop_manager.stops.len(),
+                    self.settings_file
+                ));
+                stop_manager.modified = false;
+            }
+            Err(e) => {
+                log::error!("Unable to save the settings: {}", e);
+                stop_manager.status = Some(format!("Unable to save: {e}"));
+                return;
+            }
+        }
+
+        if let Some(manager) = &mut self.async_manager {
+            manager.restart_tasks(&[Task::Transport], &self.settings);
+        }
     }
 
     /// Processes any pending async data updates
     fn process_async_updates(&mut self) {
+        let mut pending: Vec<StopAction> = Vec::new();
         if let Some(ref receiver) = self.data_receiver {
             // Process all available updates without blocking
             while let Ok(update) = receiver.try_recv() {
@@ -131,13 +291,40 @@ impl App {
                         self.weather = WeatherComponent::new(result);
                     }
                     DataUpdate::Currency(result) => {
+                        let history = std::mem::take(&mut self.currency.history);
+                        let alerts = std::mem::take(&mut self.currency.alerts);
                         self.currency = CurrencyComponent::new(result);
+                        self.currency.history = history;
+                        self.currency.alerts = alerts;
+                    }
+                    DataUpdate::CurrencyHistory(result) => match result {
+                        Ok(history) => self.currency.history = history,
+                        Err(e) => log::warn!("Currency history unavailable: {}", e),
+                    },
+                    DataUpdate::CurrencyAlerts(alerts) => {
+                        self.currency.alerts = alerts;
+                    }
+                    DataUpdate::Quotes(result) => {
+                        self.quotes = QuotesComponent::new(result, self.quotes.started);
+                    }
+                    DataUpdate::SiteSearch(result) => {
+                        if let Some(stop_manager) = &mut self.stop_manager {
+                            pending.push(stop_manager.set_results(result));
+                        }
+                    }
+                    DataUpdate::StopPreview(site_id, result) => {
+                        if let Some(stop_manager) = &mut self.stop_manager {
+                            stop_manager.set_preview(site_id, result);
+                        }
                     }
                     DataUpdate::Transport(transport_update) => {
                         let departures = Departures {
                             sites: transport_update.sites,
                             departures: transport_update.departures,
                             site_errors: transport_update.site_errors,
+                            deviations: transport_update.deviations,
+                            routes: transport_update.routes,
+                            route_error: transport_update.route_error,
                             error: transport_update.error,
                         };
                         self.transports = TransportComponent::new(departures);
@@ -145,14 +332,19 @@ impl App {
                 }
             }
         }
+
+        for action in pending {
+            self.run_stop_action(action);
+        }
     }
 
     /// Updates the state of the application every frame
     fn update_state(&mut self) -> io::Result<()> {
-        // Process any new data from async tasks
+        // Apply the settings file changes, then process any new data from async tasks
+        self.process_settings_updates();
         self.process_async_updates();
 
-        // Handle UI-specific updates (forecast cycling, timezone cycling)
+        // Handle UI-specific updates (forecast cycling, conversion cycling, timezone cycling)
 
         match SystemTime::now().duration_since(self.weather.last_forecast_change) {
             Ok(duration) => {
@@ -166,6 +358,17 @@ impl App {
             }
         }
 
+        match SystemTime::now().duration_since(self.currency.last_conversion_change) {
+            Ok(duration) => {
+                if duration.as_secs() > 5 {
+                    self.currency.advance_conversion();
+                }
+            }
+            Err(e) => {
+                error!("Error: {}", e.to_string());
+            }
+        }
+
         match SystemTime::now().duration_since(self.datetime.last_offset_change) {
             Ok(duration) => {
                 if duration.as_secs() > 5 {
@@ -190,7 +393,17 @@ impl App {
 
     /// Handles keyboard input events
     fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if let Some(stop_manager) = &mut self.stop_manager {
+            let action = stop_manager.handle_key(key_event);
+            self.run_stop_action(action);
+            return;
+        }
+
         match key_event.code {
+            KeyCode::Char('s') => {
+                log::info!("User pressed 's', opening the stop manager");
+                self.stop_manager = Some(StopManager::new(self.settings.transports.clone()));
+            }
             KeyCode::Char('q') => {
                 log::info!("User pressed 'q', exiting application");
                 self.exit = true;
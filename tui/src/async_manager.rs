@@ -1,19 +1,23 @@
-use log::{error, info};
+use log::{error, info, warn};
 /// Async data manager for handling background data fetching
 ///
 /// This module provides a single Tokio runtime with background tasks that fetch
 /// weather, currency, and transport data concurrently. It bridges async operations
 /// with the synchronous TUI event loop using channels.
+use std::collections::HashMap;
 use std::sync::mpsc;
 use std::time::Duration;
 use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 
 use homedisplay::models::{
-    currency::Conversion,
-    transports::{Departure, Site},
+    currency::{Conversion, ConversionHistory, TriggeredAlert},
+    quotes::QuoteTable,
+    transports::{Departure, RoutePlan, Site, TrafficDeviation},
     weather::WeatherInfo,
 };
-use homedisplay::settings::{BusStop, Currency, Redis, Settings, Weather};
+use homedisplay::settings::watcher::SettingsChanges;
+use homedisplay::settings::{BusStop, Currency, Quotes, Redis, Route, Settings, Transit, Weather};
 
 use crate::error::{TuiError, TuiResult};
 
@@ -21,8 +25,13 @@ use crate::error::{TuiError, TuiResult};
 #[derive(Debug, Clone)]
 pub enum DataUpdate {
     Weather(Result<WeatherInfo, TuiError>),
-    Currency(Result<Conversion, TuiError>),
+    Currency(Result<Vec<Conversion>, TuiError>),
+    CurrencyHistory(Result<Vec<ConversionHistory>, TuiError>),
+    CurrencyAlerts(Vec<TriggeredAlert>),
+    Quotes(Result<QuoteTable, TuiError>),
     Transport(TransportUpdate),
+    SiteSearch(Result<Vec<Site>, TuiError>),
+    StopPreview(String, Result<Vec<Departure>, TuiError>),
 }
 
 /// Transport-specific update containing sites and their departures
@@ -31,6 +40,9 @@ pub struct TransportUpdate {
     pub sites: Vec<Site>,
     pub departures: std::collections::HashMap<String, Vec<Departure>>,
     pub site_errors: std::collections::HashMap<String, TuiError>,
+    pub deviations: Vec<TrafficDeviation>,
+    pub routes: Vec<RoutePlan>,
+    pub route_error: Option<TuiError>,
     pub error: Option<TuiError>,
 }
 
@@ -39,6 +51,7 @@ pub struct TransportUpdate {
 pub struct RefreshConfig {
     pub weather_interval: Duration,
     pub currency_interval: Duration,
+    pub quotes_interval: Duration,
     pub transport_interval: Duration,
 }
 
@@ -47,15 +60,49 @@ impl Default for RefreshConfig {
         Self {
             weather_interval: Duration::from_secs(30 * 60), // 30 minutes
             currency_interval: Duration::from_secs(60 * 60), // 60 minutes
+            quotes_interval: Duration::from_secs(5 * 60),   // 5 minutes
             transport_interval: Duration::from_secs(60),    // 1 minute
         }
     }
 }
 
+/// The background tasks, one per panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Task {
+    Weather,
+    Currency,
+    Quotes,
+    Transport,
+}
+
+impl Task {
+    const ALL: [Task; 4] = [Task::Weather, Task::Currency, Task::Quotes, Task::Transport];
+
+    /// Returns the tasks reading the changed sections of the settings
+    pub fn affected_by(changes: &SettingsChanges) -> Vec<Task> {
+        Task::ALL
+            .into_iter()
+            .filter(|task| {
+                changes.redis
+                    || match task {
+                        Task::Weather => changes.weather,
+                        Task::Currency => changes.currency,
+                        Task::Quotes => changes.quotes,
+                        // The weather location is the home of the closest stops
+                        Task::Transport => changes.transports || changes.weather,
+                    }
+            })
+            .collect()
+    }
+}
+
 /// Async data manager that runs background tasks and communicates with UI thread
 pub struct AsyncDataManager {
     runtime: tokio::runtime::Runtime,
     shutdown_tx: Option<oneshot::Sender<()>>,
+    tx: Option<mpsc::Sender<DataUpdate>>, // Sender for the tasks and the on-demand requests
+    config: RefreshConfig,
+    tasks: HashMap<Task, JoinHandle<()>>,
 }
 
 impl AsyncDataManager {
@@ -69,12 +116,15 @@ impl AsyncDataManager {
         Ok(Self {
             runtime,
             shutdown_tx: None,
+            tx: None,
+            config: RefreshConfig::default(),
+            tasks: HashMap::new(),
         })
     }
 
     /// Starts background data fetching tasks and returns a receiver for updates
     ///
-    /// This method spawns three background tasks that continuously fetch data
+    /// This method spawns a background task per panel that continuously fetch data
     /// and send updates through the returned channel.
     pub fn start_background_tasks(
         &mut self,
@@ -92,54 +142,12 @@ impl AsyncDataManager {
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
         self.shutdown_tx = Some(shutdown_tx);
+        self.tx = Some(tx);
+        self.config = config;
 
-        // Clone settings for each task
-        let weather_settings = settings.weather.clone();
-        let currency_settings = settings.currency.clone();
-        let transport_settings = settings.transports.clone();
-        let redis_settings = settings.redis.clone();
-
-        // Spawn weather task
-        info!("Spawning weather background task");
-        let weather_tx = tx.clone();
-        let weather_redis = redis_settings.clone();
-        self.runtime.spawn(async move {
-            Self::weather_task(
-                weather_settings,
-                weather_redis,
-                weather_tx,
-                config.weather_interval,
-            )
-            .await;
-        });
-
-        // Spawn currency task
-        info!("Spawning currency background task");
-        let currency_tx = tx.clone();
-        let currency_redis = redis_settings.clone();
-        self.runtime.spawn(async move {
-            Self::currency_task(
-                currency_settings,
-                currency_redis,
-                currency_tx,
-                config.currency_interval,
-            )
-            .await;
-        });
-
-        // Spawn transport task
-        info!("Spawning transport background task");
-        let transport_tx = tx.clone();
-        let transport_redis = redis_settings;
-        self.runtime.spawn(async move {
-            Self::transport_task(
-                transport_settings,
-                transport_redis,
-                transport_tx,
-                config.transport_interval,
-            )
-            .await;
-        });
+        for task in Task::ALL {
+            self.spawn_task(task, &settings);
+        }
 
         // Spawn shutdown monitoring task
         info!("Spawning shutdown monitoring task");
@@ -152,6 +160,74 @@ impl AsyncDataManager {
         Ok(rx)
     }
 
+    /// Stops the given tasks and starts them again with the new settings
+    pub fn restart_tasks(&mut self, tasks: &[Task], settings: &Settings) {
+        for task in tasks {
+            if let Some(handle) = self.tasks.remove(task) {
+                handle.abort();
+            }
+            info!("Restarting the {:?} background task", task);
+            self.spawn_task(*task, settings);
+        }
+    }
+
+    /// Spawns the background task fetching the data of a panel
+    fn spawn_task(&mut self, task: Task, settings: &Settings) {
+        let Some(tx) = self.tx.clone() else {
+            return;
+        };
+        let config = self.config.clone();
+        let redis = settings.redis.clone();
+
+        let handle = match task {
+            Task::Weather => {
+                info!("Spawning weather background task");
+                let weather_settings = settings.weather.clone();
+                self.runtime.spawn(async move {
+                    Self::weather_task(weather_settings, redis, tx, config.weather_interval).await;
+                })
+            }
+            Task::Currency => {
+                info!("Spawning currency background task");
+                let currency_settings = settings.currency.clone();
+                self.runtime.spawn(async move {
+                    Self::currency_task(currency_settings, redis, tx, config.currency_interval)
+                        .await;
+                })
+            }
+            Task::Quotes => {
+                if settings.quotes.symbols.is_empty() {
+                    return;
+                }
+                info!("Spawning quotes background task");
+                let quotes_settings = settings.quotes.clone();
+                self.runtime.spawn(async move {
+                    Self::quotes_task(quotes_settings, redis, tx, config.quotes_interval).await;
+                })
+            }
+            Task::Transport => {
+                info!("Spawning transport background task");
+                let transport_settings = settings.transports.clone();
+                let transit_settings = settings.transit.clone();
+                let route_settings = settings.routes.clone();
+                let home_settings = settings.weather.clone();
+                self.runtime.spawn(async move {
+                    Self::transport_task(
+                        transport_settings,
+                        transit_settings,
+                        route_settings,
+                        home_settings,
+                        redis,
+                        tx,
+                        config.transport_interval,
+                    )
+                    .await;
+                })
+            }
+        };
+        self.tasks.insert(task, handle);
+    }
+
     /// Background task for fetching weather data
     async fn weather_task(
         settings: Weather,
@@ -208,25 +284,35 @@ impl AsyncDataManager {
             "Currency task started with interval: {}s",
             interval.as_secs()
         );
+        if let Err(errors) =
+            homedisplay::currency::database::validate_currencies(&settings, &redis).await
+        {
+            for e in errors {
+                warn!("Currency task: Invalid currency settings: {}", e);
+            }
+        }
+
         let mut interval_timer = tokio::time::interval(interval);
 
         loop {
             info!("Currency task: Starting data fetch");
-            let result = match homedisplay::currency::database::fetch_current_conversion(
+            let result = match homedisplay::currency::database::fetch_current_conversions(
                 settings.clone(),
                 &redis,
             )
             .await
             {
-                Ok(currency) => {
-                    info!(
-                        "Currency task: Data fetched successfully - {} {} = {} {}",
-                        currency.from_currency_amount,
-                        currency.from_currency,
-                        currency.to_currency_amount,
-                        currency.to_currency
-                    );
-                    Ok(currency)
+                Ok(conversions) => {
+                    for currency in conversions.iter() {
+                        info!(
+                            "Currency task: Data fetched successfully - {} {} = {} {}",
+                            currency.from_currency_amount,
+                            currency.from_currency,
+                            currency.to_currency_amount,
+                            currency.to_currency
+                        );
+                    }
+                    Ok(conversions)
                 }
                 Err(e) => {
                     error!("Currency task: Failed to fetch data: {}", e);
@@ -241,13 +327,77 @@ impl AsyncDataManager {
                 info!("Currency task: Update sent to UI thread successfully");
             }
 
+            let history = homedisplay::currency::database::fetch_conversion_histories(
+                settings.clone(),
+                &redis,
+            )
+            .await
+            .map_err(|e| {
+                warn!("Currency task: Failed to fetch history: {}", e);
+                TuiError::CurrencyFetch(e)
+            });
+
+            if let Err(e) = tx.send(DataUpdate::CurrencyHistory(history)) {
+                error!("Currency task: Failed to send history to UI thread: {}", e);
+                break;
+            }
+
+            match homedisplay::currency::alerts::check_alerts(settings.clone(), &redis).await {
+                Ok(alerts) => {
+                    if let Err(e) = tx.send(DataUpdate::CurrencyAlerts(alerts)) {
+                        error!("Currency task: Failed to send alerts to UI thread: {}", e);
+                        break;
+                    }
+                }
+                Err(e) => warn!("Currency task: Failed to check alerts: {}", e),
+            }
+
+            interval_timer.tick().await;
+        }
+    }
+
+    /// Background task for fetching cryptocurrency and commodity quotes
+    async fn quotes_task(
+        settings: Quotes,
+        redis: Redis,
+        tx: mpsc::Sender<DataUpdate>,
+        interval: Duration,
+    ) {
+        info!("Quotes task started with interval: {}s", interval.as_secs());
+        let mut interval_timer = tokio::time::interval(interval);
+
+        loop {
+            info!("Quotes task: Starting data fetch");
+            let result =
+                match homedisplay::quotes::database::fetch_quotes(settings.clone(), &redis).await {
+                    Ok(quotes) => {
+                        info!(
+                            "Quotes task: Data fetched successfully - {} quotes",
+                            quotes.quotes.len()
+                        );
+                        Ok(quotes)
+                    }
+                    Err(e) => {
+                        error!("Quotes task: Failed to fetch data: {}", e);
+                        Err(TuiError::QuotesFetch(e))
+                    }
+                };
+
+            if let Err(e) = tx.send(DataUpdate::Quotes(result)) {
+                error!("Quotes task: Failed to send update to UI thread: {}", e);
+                break;
+            }
+
             interval_timer.tick().await;
         }
     }
 
     /// Background task for fetching transport data
     async fn transport_task(
-        stops: Vec<BusStop>,
+        configured_stops: Vec<BusStop>,
+        transit: Transit,
+        routes: Vec<Route>,
+        home: Weather,
         redis: Redis,
         tx: mpsc::Sender<DataUpdate>,
         interval: Duration,
@@ -255,11 +405,35 @@ impl AsyncDataManager {
         info!(
             "Transport task started with interval: {}s, monitoring {} stops",
             interval.as_secs(),
-            stops.len()
+            configured_stops.len()
         );
+        if let Err(errors) =
+            homedisplay::transports::database::validate_stops(&configured_stops, &transit, &redis)
+                .await
+        {
+            for e in errors {
+                warn!("Transport task: Ambiguous stop settings: {}", e);
+            }
+        }
+
         let mut interval_timer = tokio::time::interval(interval);
 
         loop {
+            // Stops selecting the closest sites are resolved against the current catalogue
+            let stops = match homedisplay::transports::database::resolve_stops(
+                &configured_stops,
+                &home,
+                &transit,
+                &redis,
+            )
+            .await
+            {
+                Ok(stops) => stops,
+                Err(e) => {
+                    warn!("Transport task: Failed to resolve the closest stops: {}", e);
+                    configured_stops.clone()
+                }
+            };
             info!(
                 "Transport task: Starting data fetch for {} stops",
                 stops.len()
@@ -268,11 +442,16 @@ impl AsyncDataManager {
                 sites: Vec::new(),
                 departures: std::collections::HashMap::new(),
                 site_errors: std::collections::HashMap::new(),
+                deviations: Vec::new(),
+                routes: Vec::new(),
+                route_error: None,
                 error: None,
             };
 
             // Fetch sites
-            let sites = match homedisplay::transports::database::get_sites(&stops, &redis).await {
+            let sites = match homedisplay::transports::database::get_sites(&stops, &transit, &redis)
+                .await
+            {
                 Ok(sites) => {
                     info!("Transport task: Fetched {} sites successfully", sites.len());
                     sites
@@ -303,34 +482,21 @@ impl AsyncDataManager {
                     "Transport task: Fetching departures for site {} ({})",
                     site.id, site.name
                 );
-                let departures = match homedisplay::transports::database::get_departures(
+                let departures = match homedisplay::transports::database::get_stop_departures(
                     site.id.clone(),
+                    &stops,
+                    &transit,
                     &redis,
                 )
                 .await
                 {
                     Ok(departures) => {
-                        // Apply line filtering if configured
-                        let filtered_departures =
-                            if let Some(stop) = stops.iter().find(|s| s.name == site.name) {
-                                if let Some(preferred_lines) = &stop.preffered_lines {
-                                    departures
-                                        .into_iter()
-                                        .filter(|d| preferred_lines.contains(&d.line.id))
-                                        .collect()
-                                } else {
-                                    departures
-                                }
-                            } else {
-                                departures
-                            };
-
                         info!(
                             "Transport task: Site {} - {} departures after filtering",
                             site.id,
-                            filtered_departures.len()
+                            departures.len()
                         );
-                        filtered_departures
+                        departures
                     }
                     Err(e) => {
                         error!(
@@ -350,6 +516,37 @@ impl AsyncDataManager {
                     .insert(site.id.clone(), departures);
             }
 
+            match homedisplay::transports::database::get_deviations(
+                &sites, &stops, &transit, &redis,
+            )
+            .await
+            {
+                Ok(deviations) => {
+                    info!(
+                        "Transport task: {} deviations affect the sites",
+                        deviations.len()
+                    );
+                    transport_update.deviations = deviations;
+                }
+                Err(e) => warn!("Transport task: Failed to fetch deviations: {}", e),
+            }
+
+            if !routes.is_empty() {
+                match homedisplay::transports::database::get_route_plans(&routes, &redis).await {
+                    Ok(plans) => {
+                        info!("Transport task: Planned {} routes", plans.len());
+                        transport_update.routes = plans;
+                    }
+                    Err(e) => {
+                        error!("Transport task: Failed to plan routes: {}", e);
+                        transport_update.route_error = Some(TuiError::TransportFetch(format!(
+                            "Failed to plan routes: {}",
+                            e
+                        )));
+                    }
+                }
+            }
+
             // Filter out sites with no departures
             let original_count = sites.len();
             transport_update.sites = sites
@@ -379,6 +576,43 @@ impl AsyncDataManager {
         }
     }
 
+    /// Searches the site catalogue in the background, the matching sites are sent as a
+    /// `DataUpdate::SiteSearch`
+    pub fn search_sites(&self, query: String, transit: Transit, redis: Redis) {
+        let Some(tx) = self.tx.clone() else {
+            return;
+        };
+        self.runtime.spawn(async move {
+            let result =
+                homedisplay::transports::database::search_sites(&query, 20, &transit, &redis)
+                    .await
+                    .map_err(TuiError::TransportFetch);
+            if let Err(e) = tx.send(DataUpdate::SiteSearch(result)) {
+                error!("Site search: Failed to send update to UI thread: {}", e);
+            }
+        });
+    }
+
+    /// Fetches the unfiltered departures of a site in the background, they are sent as a
+    /// `DataUpdate::StopPreview`
+    pub fn preview_departures(&self, site_id: String, transit: Transit, redis: Redis) {
+        let Some(tx) = self.tx.clone() else {
+            return;
+        };
+        self.runtime.spawn(async move {
+            let result = homedisplay::transports::database::get_departures(
+                site_id.clone(),
+                &transit,
+                &redis,
+            )
+            .await
+            .map_err(TuiError::TransportFetch);
+            if let Err(e) = tx.send(DataUpdate::StopPreview(site_id, result)) {
+                error!("Stop preview: Failed to send update to UI thread: {}", e);
+            }
+        });
+    }
+
     /// Shuts down the async data manager and all background tasks
     pub fn shutdown(mut self) {
         if let Some(shutdown_tx) = self.shutdown_tx.take() {
@@ -394,6 +628,7 @@ impl std::fmt::Debug for AsyncDataManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AsyncDataManager")
             .field("shutdown_tx", &self.shutdown_tx.is_some())
+            .field("tasks", &self.tasks.keys().collect::<Vec<&Task>>())
             .finish_non_exhaustive()
     }
 }
@@ -4,12 +4,12 @@ use std::time::{Duration, SystemTime};
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::Stylize,
-    text::{Line, Text},
-    widgets::{Block, Paragraph, Widget},
+    style::{Color, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Paragraph, Sparkline, Widget},
 };
 
-use homedisplay::models::currency::Conversion;
+use homedisplay::models::currency::{Conversion, ConversionHistory, TriggeredAlert};
 
 use crate::error::TuiError;
 use crate::utilities::fit_into;
@@ -18,30 +18,92 @@ use crate::utilities::fit_into;
 /// Currency conversion display component
 pub struct CurrencyComponent {
     pub last_refresh: SystemTime, // Last time conversion data was refreshed
-    pub conversion: Result<Conversion, TuiError>, // Current conversion data or error
+    pub conversions: Result<Vec<Conversion>, TuiError>, // Current conversion data or error
     pub cooldown: Duration,       // Time between refresh attempts
+    pub current_conversion: usize, // Currently displayed conversion
+    pub last_conversion_change: SystemTime, // Last time conversion display rotated
+    pub history: Vec<ConversionHistory>, // Daily rates of each conversion
+    pub alerts: Vec<TriggeredAlert>, // Currently triggered currency alerts
 }
 
 impl Default for CurrencyComponent {
     fn default() -> CurrencyComponent {
         CurrencyComponent {
             last_refresh: SystemTime::now(),
-            conversion: Err(TuiError::CurrencyFetch(
+            conversions: Err(TuiError::CurrencyFetch(
                 "No conversion was fetched yet".to_string(),
             )),
             cooldown: Duration::from_secs(60 * 60), // Once per hour
+            current_conversion: 0,
+            last_conversion_change: SystemTime::now(),
+            history: Vec::new(),
+            alerts: Vec::new(),
         }
     }
 }
 
 impl CurrencyComponent {
     /// Creates a new currency component with the given conversion data
-    pub fn new(conversion: Result<Conversion, TuiError>) -> CurrencyComponent {
+    pub fn new(conversions: Result<Vec<Conversion>, TuiError>) -> CurrencyComponent {
         let mut w = CurrencyComponent::default();
         w.last_refresh = SystemTime::now();
-        w.conversion = conversion;
+        w.conversions = conversions;
         w
     }
+
+    /// Cycles to the next conversion to display
+    pub fn advance_conversion(&mut self) {
+        if let Ok(conversions) = &self.conversions {
+            if !conversions.is_empty() {
+                self.current_conversion = (self.current_conversion + 1) % conversions.len();
+            }
+        }
+        self.last_conversion_change = SystemTime::now();
+    }
+
+    /// Returns the history of the given conversion, if enough points are known to draw it
+    fn history_of(&self, conversion: &Conversion) -> Option<&ConversionHistory> {
+        self.history.iter().find(|history| {
+            history.from_currency == conversion.from_currency
+                && history.to_currency == conversion.to_currency
+                && history.points.len() > 1
+        })
+    }
+}
+
+/// Builds a line with the 7, 30 and 90 days change of the conversion
+fn change_line(history: &ConversionHistory) -> Line<'static> {
+    let mut spans: Vec<Span> = Vec::new();
+    for days in [7, 30, 90] {
+        if let Some(change) = history.change(days) {
+            let value = format!("{change:+.1}%");
+            spans.push(format!(" {days}d ").gray());
+            spans.push(if change >= 0.0 {
+                value.green()
+            } else {
+                value.red()
+            });
+        }
+    }
+    Line::from(spans).centered()
+}
+
+/// Scales the rates of the history to integers for the sparkline, keeping the last `width` points
+fn sparkline_data(history: &ConversionHistory, width: usize) -> Vec<u64> {
+    let rates = history.rates();
+    let rates = &rates[rates.len().saturating_sub(width)..];
+    let min = rates.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = rates.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    rates
+        .iter()
+        .map(|rate| {
+            if max > min {
+                (1.0 + (rate - min) / (max - min) * 99.0) as u64
+            } else {
+                50
+            }
+        })
+        .collect()
 }
 
 impl Widget for &CurrencyComponent {
@@ -61,8 +123,17 @@ impl Widget for &CurrencyComponent {
 
         let currency_block = Block::new().title_bottom(last_refreshed.centered());
 
-        let currency_text: Text = match &self.conversion {
-            Ok(conversion) => {
+        let mut sparkline: Option<(Rect, Vec<u64>)> = None;
+        let currency_text: Text = match &self
+            .conversions
+            .as_ref()
+            .map(|conversions| (conversions.len(), conversions.get(self.current_conversion)))
+        {
+            Ok((count, Some(conversion))) => {
+                let history = self.history_of(conversion).filter(|_| area.height >= 8);
+                // Change line and two rows of sparkline
+                let history_height = if history.is_some() { 3 } else { 0 };
+
                 let refresh_date = {
                     let date_fetched = DateTime::from_timestamp(conversion.timestamp, 0)
                         .unwrap()
@@ -73,7 +144,7 @@ impl Widget for &CurrencyComponent {
                 };
 
                 let mut lines: Vec<Line> = Vec::new();
-                for _ in 1..(area.height - 2) / 2 {
+                for _ in 1..(area.height - 2 - history_height) / 2 {
                     lines.push(Line::from(""))
                 }
                 lines.push(
@@ -86,10 +157,34 @@ impl Widget for &CurrencyComponent {
                     ])
                     .centered(),
                 );
-                lines.push(Line::from(refresh_date.gray()).centered());
+                if *count > 1 {
+                    lines.push(
+                        Line::from(
+                            format!("{refresh_date} ({}/{count})", self.current_conversion + 1)
+                                .gray(),
+                        )
+                        .centered(),
+                    );
+                } else {
+                    lines.push(Line::from(refresh_date.gray()).centered());
+                }
+
+                if let Some(history) = history {
+                    lines.push(change_line(history));
+
+                    let width = history.points.len().min(area.width as usize - 2) as u16;
+                    let sparkline_area = Rect {
+                        x: area.x + (area.width - width) / 2,
+                        y: area.y + lines.len() as u16,
+                        width,
+                        height: history_height - 1,
+                    };
+                    sparkline = Some((sparkline_area, sparkline_data(history, width as usize)));
+                }
 
                 Text::from(lines)
             }
+            Ok((_, None)) => Text::from(Line::from("No currency pair configured").centered()),
             Err(e) => {
                 log::warn!("Currency component displaying error: {}", e);
                 let user_message = e.user_message();
@@ -118,5 +213,37 @@ impl Widget for &CurrencyComponent {
         Paragraph::new(currency_text)
             .block(currency_block)
             .render(area, buf);
+
+        if let Some((sparkline_area, data)) = sparkline {
+            Sparkline::default()
+                .data(&data)
+                .style(Style::default().fg(Color::Cyan))
+                .render(sparkline_area.intersection(area), buf);
+        }
+    }
+}
+
+/// Banner listing the triggered currency alerts, one per line
+pub struct AlertBanner<'a> {
+    pub alerts: &'a [TriggeredAlert],
+}
+
+impl Widget for AlertBanner<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let lines: Vec<Line> = self
+            .alerts
+            .iter()
+            .map(|alert| {
+                Line::from(format!("⚠ {} ⚠", alert.message))
+                    .bold()
+                    .black()
+                    .on_yellow()
+                    .centered()
+            })
+            .collect();
+
+        Paragraph::new(Text::from(lines))
+            .style(Style::default().bg(Color::Yellow))
+            .render(area, buf);
     }
 }
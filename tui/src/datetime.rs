@@ -1,5 +1,5 @@
 use chrono::prelude::Local;
-use chrono::{FixedOffset, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
 use homedisplay::settings::TimezoneData;
 
 use crate::error::{TuiError, TuiResult};
@@ -20,7 +20,7 @@ use ratatui::{
 #[derive(Debug)]
 /// This component displays local time as well as optional offseted timezones
 pub struct DateTimeComponent {
-    pub timezones: Vec<TimezoneData>, // Direction (E, W), offset (in hours) and Name
+    pub timezones: Vec<TimezoneData>, // IANA zone or direction (E, W) and offset, and Name
     pub currently_displayed_offset: u32,
     pub last_offset_change: SystemTime,
 }
@@ -48,9 +48,19 @@ impl DateTimeComponent {
         self.timezones.push(timezone);
     }
 
+    /// Replaces the displayed timezones, e.g. after the settings were reloaded
+    pub fn set_timezones(&mut self, timezones: Vec<TimezoneData>) {
+        self.timezones = timezones;
+        self.currently_displayed_offset = 0;
+        self.last_offset_change = SystemTime::now();
+    }
+
+    /// Displays the next timezone, if any is configured
     pub fn advance_timezone(&mut self) {
-        self.currently_displayed_offset =
-            (self.currently_displayed_offset + 1) % self.timezones.len() as u32;
+        self.currently_displayed_offset = match self.timezones.len() as u32 {
+            0 => 0,
+            count => (self.currently_displayed_offset + 1) % count,
+        };
         self.last_offset_change = SystemTime::now();
     }
 }
@@ -86,47 +96,19 @@ impl DateTimeComponent {
 
     /// Renders the currently displayed timezone. Different timezones are stored in the `timezones` field.
     fn render_current_timezone(&self, frame: Rect, buf: &mut Buffer) -> TuiResult<()> {
-        let (offseted_timezone, timezone_name) =
-            if let Some(timezone) = self.timezones.get(self.currently_displayed_offset as usize) {
-                match timezone.direction.to_uppercase().as_str() {
-                    "E" => (
-                        FixedOffset::east_opt((timezone.offset * 3600.0) as i32).ok_or(
-                            TuiError::TimezoneInvalid(format!(
-                                "Unable to build valid east offset with {} seconds",
-                                timezone.offset
-                            )),
-                        )?,
-                        &timezone.name,
-                    ),
-                    "W" => (
-                        FixedOffset::west_opt(timezone.offset as i32).ok_or(
-                            TuiError::TimezoneInvalid(format!(
-                                "Unable to build valid west offset with {} seconds",
-                                timezone.offset
-                            )),
-                        )?,
-                        &timezone.name,
-                    ),
-                    other => {
-                        error!("Incorrect timezone offset: {other}");
-                        return Err(TuiError::TimezoneInvalid(format!(
-                            "Incorrect timezone direction '{}'. Expected 'E' or 'W'",
-                            other
-                        )));
-                    }
-                }
-            } else {
-                // No timezone to display
-                return Ok(());
-            };
+        let Some(timezone) = self.timezones.get(self.currently_displayed_offset as usize) else {
+            // No timezone to display
+            return Ok(());
+        };
+        let (today, abbreviation) = timezone.at(Utc::now()).map_err(TuiError::TimezoneInvalid)?;
         let datetime_block = Block::new()
             .borders(Borders::BOTTOM)
             .border_set(border::THICK);
 
-        let today = Utc::now().with_timezone(&offseted_timezone);
         let current_day: String = today.format("%A").to_string();
         let current_date: String = today.format("%d/%m/%Y").to_string();
-        let current_time: String = today.format("%H:%M").to_string();
+        let current_time = time_label(today, &abbreviation, Local::now().date_naive());
+        let timezone_name = &timezone.name;
 
         let mut datetime_text = vec![];
 
@@ -152,6 +134,24 @@ impl DateTimeComponent {
     }
 }
 
+/// Returns the number of days the date of a timezone is ahead of the local date
+fn day_offset(date: NaiveDate, local: NaiveDate) -> i64 {
+    (date - local).num_days()
+}
+
+/// Returns the time of a timezone with its abbreviation, followed by the days it is ahead
+/// of or behind the local date, e.g. `05:00 UTC+05:30 (+1 day)`
+fn time_label(time: DateTime<FixedOffset>, abbreviation: &str, local: NaiveDate) -> String {
+    match day_offset(time.date_naive(), local) {
+        0 => format!("{} {abbreviation}", time.format("%H:%M")),
+        days => format!(
+            "{} {abbreviation} ({days:+} day{})",
+            time.format("%H:%M"),
+            if days.abs() > 1 { "s" } else { "" }
+        ),
+    }
+}
+
 impl Widget for &DateTimeComponent {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let splitted = Layout::default()
@@ -165,3 +165,102 @@ impl Widget for &DateTimeComponent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(direction: &str, offset: f32) -> TimezoneData {
+        TimezoneData {
+            zone: None,
+            direction: direction.to_string(),
+            offset,
+            name: String::new(),
+        }
+    }
+
+    fn zone(zone: &str) -> TimezoneData {
+        TimezoneData {
+            zone: Some(zone.to_string()),
+            ..fixed("", 0.0)
+        }
+    }
+
+    /// Returns the label of the timezone at the given UTC time, seen from the given local date
+    fn label(timezone: &TimezoneData, utc: &str, local: &str) -> String {
+        let instant = utc.parse::<DateTime<Utc>>().unwrap();
+        let (time, abbreviation) = timezone.at(instant).unwrap();
+        time_label(time, &abbreviation, local.parse().unwrap())
+    }
+
+    #[test]
+    fn shows_the_days_ahead_of_the_local_date() {
+        assert_eq!(
+            label(&fixed("E", 5.5), "2024-03-12T23:30:00Z", "2024-03-12"),
+            "05:00 UTC+05:30 (+1 day)"
+        );
+        assert_eq!(
+            label(&zone("Asia/Tokyo"), "2024-03-12T20:00:00Z", "2024-03-12"),
+            "05:00 JST (+1 day)"
+        );
+    }
+
+    #[test]
+    fn shows_the_days_behind_the_local_date() {
+        assert_eq!(
+            label(&fixed("W", 10.0), "2024-03-12T05:00:00Z", "2024-03-12"),
+            "19:00 UTC-10:00 (-1 day)"
+        );
+        assert_eq!(
+            label(
+                &zone("Pacific/Honolulu"),
+                "2024-03-12T05:00:00Z",
+                "2024-03-12"
+            ),
+            "19:00 HST (-1 day)"
+        );
+    }
+
+    #[test]
+    fn shows_no_offset_on_the_same_day() {
+        assert_eq!(
+            label(&fixed("e", 1.0), "2024-03-12T12:00:00Z", "2024-03-12"),
+            "13:00 UTC+01:00"
+        );
+        assert_eq!(
+            label(&fixed("w", 5.0), "2024-03-12T12:00:00Z", "2024-03-12"),
+            "07:00 UTC-05:00"
+        );
+    }
+
+    #[test]
+    fn pluralises_the_offsets_of_several_days() {
+        assert_eq!(
+            day_offset("2024-03-14".parse().unwrap(), "2024-03-12".parse().unwrap()),
+            2
+        );
+        assert_eq!(
+            label(&fixed("E", 1.0), "2024-03-12T12:00:00Z", "2024-03-10"),
+            "13:00 UTC+01:00 (+2 days)"
+        );
+    }
+
+    #[test]
+    fn follows_daylight_saving_time() {
+        let new_york = zone("America/New_York");
+        // Clocks go forward at 2:00 local time on the 10th of March 2024
+        assert_eq!(
+            label(&new_york, "2024-03-10T06:59:00Z", "2024-03-10"),
+            "01:59 EST"
+        );
+        assert_eq!(
+            label(&new_york, "2024-03-10T07:00:00Z", "2024-03-10"),
+            "03:00 EDT"
+        );
+        // The offset of the day is that of the zone, not of the host
+        assert_eq!(
+            label(&new_york, "2024-03-10T03:30:00Z", "2024-03-10"),
+            "22:30 EST (-1 day)"
+        );
+    }
+}
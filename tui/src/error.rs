@@ -4,6 +4,7 @@ use std::fmt;
 pub enum TuiError {
     WeatherFetch(String),
     CurrencyFetch(String),
+    QuotesFetch(String),
     TransportFetch(String),
     TokioRuntime(String),
     TerminalTooSmall { width: u16, height: u16 },
@@ -19,6 +20,7 @@ impl fmt::Display for TuiError {
         match self {
             TuiError::WeatherFetch(msg) => write!(f, "Weather fetch failed: {}", msg),
             TuiError::CurrencyFetch(msg) => write!(f, "Currency fetch failed: {}", msg),
+            TuiError::QuotesFetch(msg) => write!(f, "Quotes fetch failed: {}", msg),
             TuiError::TransportFetch(msg) => write!(f, "Transport fetch failed: {}", msg),
             TuiError::TokioRuntime(msg) => write!(f, "Runtime error: {}", msg),
             TuiError::TerminalTooSmall { width, height } => {
@@ -55,6 +57,7 @@ impl TuiError {
             self,
             TuiError::WeatherFetch(_)
                 | TuiError::CurrencyFetch(_)
+                | TuiError::QuotesFetch(_)
                 | TuiError::TransportFetch(_)
                 | TuiError::TerminalTooSmall { .. }
         )
@@ -64,6 +67,7 @@ impl TuiError {
         match self {
             TuiError::WeatherFetch(_) => "Weather data unavailable",
             TuiError::CurrencyFetch(_) => "Currency data unavailable",
+            TuiError::QuotesFetch(_) => "Quotes unavailable",
             TuiError::TransportFetch(_) => "Transport data unavailable",
             TuiError::TokioRuntime(_) => "System error",
             TuiError::TerminalTooSmall { .. } => "Terminal too small",
@@ -2,10 +2,12 @@ use std::default::Default;
 use std::fs::File;
 use std::io;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use log;
 use simplelog::{CombinedLogger, Config, LevelFilter, WriteLogger};
 
+use homedisplay::settings::loader::SettingsLoader;
+use homedisplay::settings::migration;
 use homedisplay::settings::Settings;
 
 mod app;
@@ -13,6 +15,8 @@ mod async_manager;
 mod currency;
 mod datetime;
 mod error;
+mod quotes;
+mod stops;
 mod transports;
 mod tui;
 mod utilities;
@@ -47,9 +51,51 @@ struct Args {
     #[arg(short = 'D', long)]
     dump_default_settings: bool,
 
-    #[arg(short = 'o', long, default_value = "settings.toml")]
-    /// Output files for the settings dump.
-    dump_file: String,
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    /// Overrides a setting, e.g. `--set weather.latitude=59.3`. Can be repeated
+    overrides: Vec<String>,
+
+    #[arg(long)]
+    /// Validates the settings file, prints the problems found and exits
+    check_settings: bool,
+
+    #[arg(short = 'o', long)]
+    /// Output file for the settings dump. Defaults to the standard output
+    dump_file: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Lists the stops near the location of the weather settings, closest first
+    NearbyStops {
+        #[arg(short = 'r', long, default_value_t = 500.0)]
+        /// Search radius in meters
+        radius: f64,
+
+        #[arg(short = 'n', long, default_value_t = 20)]
+        /// Maximum number of stops to list
+        limit: usize,
+    },
+}
+
+/// Returns the loader of the settings from the file, the environment and the command line
+/// overrides
+fn settings_loader(settings_file: &str, overrides: &[String]) -> io::Result<SettingsLoader> {
+    SettingsLoader::new()
+        .file(settings_file)
+        .env()
+        .overrides(overrides)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+/// Loads the settings from the file, the environment and the command line overrides
+fn load_settings(settings_file: &str, overrides: &[String]) -> io::Result<Settings> {
+    settings_loader(settings_file, overrides)?
+        .load()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
 }
 
 fn main() -> io::Result<()> {
@@ -79,24 +125,37 @@ fn main() -> io::Result<()> {
         let settings = if args.dump_default_settings {
             Settings::default()
         } else {
-            Settings::load_from_file(&args.settings)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+            load_settings(&args.settings, &args.overrides)?
         };
 
-        let settings_str = settings
-            .to_string()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        std::fs::write(&args.dump_file, settings_str).unwrap();
-        log::info!("Settings dumped to {}", args.dump_file);
-        return Ok(());
+        return utilities::dump_settings(&settings, args.dump_file.as_deref(), &args.settings);
+    }
+
+    if args.check_settings {
+        let settings = load_settings(&args.settings, &args.overrides)?;
+        return utilities::print_settings_report(&settings, &args.settings);
+    }
+
+    if let Some(Command::NearbyStops { radius, limit }) = args.command {
+        let settings = load_settings(&args.settings, &args.overrides)?;
+        return utilities::print_nearby_stops(&settings, radius, limit);
     }
 
     log::info!("Starting HomeDisplay TUI application");
     log::info!("Loading settings from: {}", args.settings);
 
+    if let Err(e) = migration::upgrade_file(&args.settings) {
+        log::error!("Unable to upgrade the settings file: {}", e);
+    }
+    let settings = load_settings(&args.settings, &args.overrides).unwrap_or_else(|e| {
+        log::error!("Unable to load the settings: {}. Using default value", e);
+        Settings::default()
+    });
+
     let mut terminal = tui::init()?;
     let app_result = App::default()
-        .with_settings(&args.settings)
+        .with_settings(settings, &args.settings)
+        .watch_settings(settings_loader(&args.settings, &args.overrides)?)
         .run(&mut terminal);
 
     log::info!("Application finished, restoring terminal");
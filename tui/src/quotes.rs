@@ -0,0 +1,111 @@
+use std::time::{Duration, SystemTime};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Paragraph, Widget},
+};
+
+use homedisplay::models::quotes::{Quote, QuoteTable};
+
+use crate::error::TuiError;
+
+/// Time for the ticker to scroll by one column
+const SCROLL_STEP: Duration = Duration::from_millis(250);
+
+#[derive(Debug)]
+/// Ticker component scrolling the cryptocurrency and commodity quotes
+pub struct QuotesComponent {
+    pub last_refresh: SystemTime, // Last time quotes were refreshed
+    pub quotes: Result<QuoteTable, TuiError>, // Current quotes or error
+    pub started: SystemTime,      // Start of the scrolling animation
+}
+
+impl Default for QuotesComponent {
+    fn default() -> QuotesComponent {
+        QuotesComponent {
+            last_refresh: SystemTime::now(),
+            quotes: Ok(QuoteTable::default()),
+            started: SystemTime::now(),
+        }
+    }
+}
+
+impl QuotesComponent {
+    /// Creates a new quotes component with the given quotes, keeping the scrolling position
+    pub fn new(quotes: Result<QuoteTable, TuiError>, started: SystemTime) -> QuotesComponent {
+        QuotesComponent {
+            last_refresh: SystemTime::now(),
+            quotes,
+            started,
+        }
+    }
+
+    /// Returns the height the ticker needs, zero when there is nothing to display
+    pub fn height(&self) -> u16 {
+        match &self.quotes {
+            Ok(table) if table.quotes.is_empty() => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// Formats a quote as ticker spans, e.g. `BTC 67000.00 USD ▲1.20%`
+fn quote_spans(quote: &Quote) -> Vec<Span<'static>> {
+    let mut spans = vec![
+        Span::from(format!("{} ", quote.symbol)).bold(),
+        Span::from(format!("{:.2} {}", quote.price, quote.currency)),
+    ];
+    if let Some(change) = quote.change_24h {
+        let (arrow, color) = if change >= 0.0 {
+            ("▲", Color::Green)
+        } else {
+            ("▼", Color::Red)
+        };
+        spans.push(Span::styled(
+            format!(" {arrow}{:.2}%", change.abs()),
+            Style::default().fg(color),
+        ));
+    }
+    spans.push(Span::from("   •   ").dark_gray());
+    spans
+}
+
+impl Widget for &QuotesComponent {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let table = match &self.quotes {
+            Ok(table) => table,
+            Err(e) => {
+                Paragraph::new(Line::from(e.user_message()).red()).render(area, buf);
+                return;
+            }
+        };
+
+        let spans: Vec<Span> = table.quotes.iter().flat_map(quote_spans).collect();
+        let width: usize = spans.iter().map(|span| span.width()).sum();
+        if width == 0 {
+            return;
+        }
+
+        // Repeat the quotes until the line is wide enough to scroll without gaps
+        let repeat = (area.width as usize).div_ceil(width) + 1;
+        let line = Line::from(
+            spans
+                .iter()
+                .cycle()
+                .take(spans.len() * repeat)
+                .cloned()
+                .collect::<Vec<Span>>(),
+        );
+        let elapsed = SystemTime::now()
+            .duration_since(self.started)
+            .unwrap_or_default();
+        let offset = (elapsed.as_millis() / SCROLL_STEP.as_millis()) as usize % width;
+
+        Paragraph::new(line)
+            .scroll((0, offset as u16))
+            .render(area, buf);
+    }
+}
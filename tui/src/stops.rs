@@ -0,0 +1,452 @@
+use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    symbols::border,
+    text::{Line, Text},
+    widgets::{Block, Borders, Clear, Paragraph, Widget},
+};
+
+use homedisplay::models::transports::{stockholm_now, Departure, Site};
+use homedisplay::settings::{BusStop, StopMatch};
+
+use crate::error::TuiError;
+
+/// Number of departures shown in the preview
+const PREVIEW_DEPARTURES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Part of the stop manager receiving the key presses
+pub enum Focus {
+    Stops,   // The configured stops
+    Search,  // The search field
+    Results, // The sites matching the search
+    Lines,   // The preferred lines of the selected stop
+    Close,   // The prompt to save or discard the changes before closing
+}
+
+#[derive(Debug, PartialEq)]
+/// Work the application has to carry out after a key press in the stop manager
+pub enum StopAction {
+    None,
+    Search(String),  // Search the site catalogue for the query
+    Preview(String), // Fetch the live departures of the site
+    Save,            // Write the stops to the settings file
+    SaveAndClose,    // Write the stops to the settings file, then close once saved
+    Close,
+}
+
+#[derive(Debug)]
+/// The live departures of the site selected in the stop manager
+pub struct Preview {
+    pub site_id: String,
+    pub departures: Option<Result<Vec<Departure>, TuiError>>, // None while loading
+}
+
+#[derive(Debug)]
+/// Modal screen to search the site catalogue and edit the stops of the settings
+pub struct StopManager {
+    pub stops: Vec<BusStop>, // Working copy of the stops of the settings
+    pub selected: usize,     // Selected stop
+    pub focus: Focus,        // Part receiving the key presses
+    pub query: String,       // Search field content
+    pub results: Result<Vec<Site>, TuiError>, // Sites matching the last search
+    pub searching: bool,     // Whether a search is running
+    pub result_selected: usize, // Selected search result
+    pub preview: Option<Preview>, // Previewed site
+    pub lines_input: String, // Preferred lines being edited
+    pub status: Option<String>, // Outcome of the last action
+    pub modified: bool,      // Whether the stops differ from the settings file
+}
+
+impl StopManager {
+    pub fn new(stops: Vec<BusStop>) -> StopManager {
+        StopManager {
+            stops,
+            selected: 0,
+            focus: Focus::Stops,
+            query: String::new(),
+            results: Ok(Vec::new()),
+            searching: false,
+            result_selected: 0,
+            preview: None,
+            lines_input: String::new(),
+            status: None,
+            modified: false,
+        }
+    }
+
+    /// Stores the sites matching the search, and previews the first one
+    pub fn set_results(&mut self, results: Result<Vec<Site>, TuiError>) -> StopAction {
+        self.searching = false;
+        self.result_selected = 0;
+        self.results = results;
+        match self.results.as_ref().ok().and_then(|sites| sites.first()) {
+            Some(site) => self.preview(site.id.clone()),
+            None => StopAction::None,
+        }
+    }
+
+    /// Stores the departures of a previewed site, unless another site was selected since
+    pub fn set_preview(&mut self, site_id: String, departures: Result<Vec<Departure>, TuiError>) {
+        if let Some(preview) = &mut self.preview {
+            if preview.site_id == site_id {
+                preview.departures = Some(departures);
+            }
+        }
+    }
+
+    fn preview(&mut self, site_id: String) -> StopAction {
+        if self.preview.as_ref().map(|preview| &preview.site_id) == Some(&site_id) {
+            return StopAction::None;
+        }
+        self.preview = Some(Preview {
+            site_id: site_id.clone(),
+            departures: None,
+        });
+        StopAction::Preview(site_id)
+    }
+
+    /// Previews the site the selected stop is pinned to, if any
+    fn preview_selected_stop(&mut self) -> StopAction {
+        match self
+            .stops
+            .get(self.selected)
+            .and_then(|stop| stop.site_id.clone())
+        {
+            Some(site_id) => self.preview(site_id),
+            None => StopAction::None,
+        }
+    }
+
+    fn results_len(&self) -> usize {
+        self.results.as_ref().map(Vec::len).unwrap_or(0)
+    }
+
+    /// Handles a key press, returning the work left to the application
+    pub fn handle_key(&mut self, key: KeyEvent) -> StopAction {
+        match self.focus {
+            Focus::Stops => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') if self.modified => {
+                    self.focus = Focus::Close;
+                    StopAction::None
+                }
+                KeyCode::Esc | KeyCode::Char('q') => StopAction::Close,
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.selected = self.selected.saturating_sub(1);
+                    self.preview_selected_stop()
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    if self.selected + 1 < self.stops.len() {
+                        self.selected += 1;
+                    }
+                    self.preview_selected_stop()
+                }
+                KeyCode::Char('/') | KeyCode::Char('a') => {
+                    self.focus = Focus::Search;
+                    StopAction::None
+                }
+                KeyCode::Char('d') | KeyCode::Delete => {
+                    if self.selected < self.stops.len() {
+                        let stop = self.stops.remove(self.selected);
+                        self.status = Some(format!("Removed {}", stop.name));
+                        self.selected = self.selected.min(self.stops.len().saturating_sub(1));
+                        self.modified = true;
+                    }
+                    StopAction::None
+                }
+                KeyCode::Char('l') => {
+                    if let Some(stop) = self.stops.get(self.selected) {
+                        self.lines_input = stop
+                            .preferred_lines
+                            .iter()
+                            .flatten()
+                            .map(|line| line.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ");
+                        self.focus = Focus::Lines;
+                    }
+                    StopAction::None
+                }
+                KeyCode::Char('w') => StopAction::Save,
+                _ => StopAction::None,
+            },
+            Focus::Search => match key.code {
+                KeyCode::Esc => {
+                    self.focus = Focus::Stops;
+                    StopAction::None
+                }
+                KeyCode::Enter if !self.query.trim().is_empty() => {
+                    self.searching = true;
+                    self.focus = Focus::Results;
+                    StopAction::Search(self.query.trim().to_string())
+                }
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    StopAction::None
+                }
+                KeyCode::Char(c) => {
+                    self.query.push(c);
+                    StopAction::None
+                }
+                _ => StopAction::None,
+            },
+            Focus::Results => match key.code {
+                KeyCode::Esc | KeyCode::Char('/') => {
+                    self.focus = Focus::Search;
+                    StopAction::None
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.result_selected = self.result_selected.saturating_sub(1);
+                    self.preview_selected_result()
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    if self.result_selected + 1 < self.results_len() {
+                        self.result_selected += 1;
+                    }
+                    self.preview_selected_result()
+                }
+                KeyCode::Enter => {
+                    let site = match &self.results {
+                        Ok(sites) => sites.get(self.result_selected).cloned(),
+                        Err(_) => None,
+                    };
+                    if let Some(site) = site {
+                        self.status = Some(format!("Added {} ({})", site.name, site.id));
+                        self.stops.push(BusStop {
+                            name: site.name,
+                            site_id: Some(site.id),
+                            match_mode: StopMatch::Id,
+                            ..Default::default()
+                        });
+                        self.selected = self.stops.len() - 1;
+                        self.focus = Focus::Stops;
+                        self.modified = true;
+                    }
+                    StopAction::None
+                }
+                _ => StopAction::None,
+            },
+            Focus::Lines => match key.code {
+                KeyCode::Esc => {
+                    self.focus = Focus::Stops;
+                    StopAction::None
+                }
+                KeyCode::Enter => {
+                    let lines: Result<Vec<i32>, _> = self
+                        .lines_input
+                        .split([',', ' '])
+                        .filter(|line| !line.is_empty())
+                        .map(str::parse::<i32>)
+                        .collect();
+                    match (lines, self.stops.get_mut(self.selected)) {
+                        (Ok(lines), Some(stop)) => {
+                            stop.preferred_lines = (!lines.is_empty()).then_some(lines);
+                            self.status = Some(format!("Updated the lines of {}", stop.name));
+                            self.focus = Focus::Stops;
+                            self.modified = true;
+                        }
+                        (Err(e), _) => self.status = Some(format!("Invalid line: {e}")),
+                        _ => self.focus = Focus::Stops,
+                    }
+                    StopAction::None
+                }
+                KeyCode::Backspace => {
+                    self.lines_input.pop();
+                    StopAction::None
+                }
+                KeyCode::Char(c) if c.is_ascii_digit() || c == ',' || c == ' ' => {
+                    self.lines_input.push(c);
+                    StopAction::None
+                }
+                _ => StopAction::None,
+            },
+            Focus::Close => match key.code {
+                KeyCode::Char('w') | KeyCode::Char('y') => {
+                    self.focus = Focus::Stops;
+                    StopAction::SaveAndClose
+                }
+                KeyCode::Char('d') | KeyCode::Char('n') => StopAction::Close,
+                KeyCode::Esc => {
+                    self.focus = Focus::Stops;
+                    StopAction::None
+                }
+                _ => StopAction::None,
+            },
+        }
+    }
+
+    fn preview_selected_result(&mut self) -> StopAction {
+        let site_id = match &self.results {
+            Ok(sites) => sites.get(self.result_selected).map(|site| site.id.clone()),
+            Err(_) => None,
+        };
+        match site_id {
+            Some(site_id) => self.preview(site_id),
+            None => StopAction::None,
+        }
+    }
+
+    fn stop_lines(&self) -> Vec<Line<'static>> {
+        if self.stops.is_empty() {
+            return vec![Line::from(" No stops configured").dark_gray()];
+        }
+        self.stops
+            .iter()
+            .enumerate()
+            .map(|(position, stop)| {
+                let mut text = format!(" {}", stop.name);
+                if let Some(site_id) = &stop.site_id {
+                    text.push_str(&format!(" ({site_id})"));
+                }
+                if let Some(lines) = &stop.preferred_lines {
+                    let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+                    text.push_str(&format!(" [{}]", lines.join(", ")));
+                }
+                if position == self.selected {
+                    Line::from(text).reversed()
+                } else {
+                    Line::from(text)
+                }
+            })
+            .collect()
+    }
+
+    fn result_lines(&self) -> Vec<Line<'static>> {
+        if self.searching {
+            return vec![Line::from(" Searching...").dark_gray()];
+        }
+        match &self.results {
+            Err(e) => vec![Line::from(format!(" {}", e.user_message())).red()],
+            Ok(sites) if sites.is_empty() && !self.query.is_empty() => {
+                vec![Line::from(" No matching stop").dark_gray()]
+            }
+            Ok(sites) => sites
+                .iter()
+                .enumerate()
+                .map(|(position, site)| {
+                    let line = Line::from(format!(" {} ({})", site.name, site.id));
+                    if self.focus == Focus::Results && position == self.result_selected {
+                        line.reversed()
+                    } else {
+                        line
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn preview_lines(&self) -> Vec<Line<'static>> {
+        let now = stockholm_now();
+        match self.preview.as_ref().map(|preview| &preview.departures) {
+            None => vec![Line::from(" Select a stop to see its departures").dark_gray()],
+            Some(None) => vec![Line::from(" Loading...").dark_gray()],
+            Some(Some(Err(e))) => vec![Line::from(format!(" {}", e.user_message())).red()],
+            Some(Some(Ok(departures))) => {
+                let mut lines_here: Vec<String> = vec![];
+                for departure in departures {
+                    let line = format!("{} ({})", departure.line.name(), departure.line.id);
+                    if !lines_here.contains(&line) {
+                        lines_here.push(line);
+                    }
+                }
+                let mut lines = vec![Line::from(format!(" Lines: {}", lines_here.join(", ")))];
+                lines.extend(departures.iter().take(PREVIEW_DEPARTURES).map(|departure| {
+                    Line::from(format!(
+                        "   {:6} {} {}",
+                        departure.countdown(now),
+                        departure.line.name(),
+                        departure.destination
+                    ))
+                }));
+                lines
+            }
+        }
+    }
+
+    fn help(&self) -> &'static str {
+        match self.focus {
+            Focus::Stops => "↑↓ select · / search · l lines · d remove · w save · Esc close",
+            Focus::Search => "type a stop name · Enter search · Esc back",
+            Focus::Results => "↑↓ preview · Enter add · / edit search · Esc back",
+            Focus::Lines => {
+                "line ids separated by commas, empty for all · Enter apply · Esc cancel"
+            }
+            Focus::Close => "w save and close · d discard the changes · Esc keep editing",
+        }
+    }
+}
+
+/// Returns a block titled with the name of a pane, highlighted when it has the focus
+fn pane(title: &'static str, focused: bool) -> Block<'static> {
+    let block = Block::new().borders(Borders::ALL).title(title);
+    if focused {
+        block.border_style(Style::new().yellow())
+    } else {
+        block
+    }
+}
+
+impl Widget for &StopManager {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+        let title = if self.modified {
+            " Bus stops (unsaved) "
+        } else {
+            " Bus stops "
+        };
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .title(Line::from(title).bold().centered())
+            .title_bottom(Line::from(self.help()).centered())
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let rows = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).split(inner);
+        let columns = Layout::horizontal([Constraint::Ratio(1, 2); 2]).split(rows[0]);
+        let right = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Ratio(1, 2),
+            Constraint::Ratio(1, 2),
+        ])
+        .split(columns[1]);
+
+        let stops = pane(" Stops ", matches!(self.focus, Focus::Stops | Focus::Lines));
+        let mut stop_lines = self.stop_lines();
+        if self.focus == Focus::Lines {
+            stop_lines.push(Line::from(""));
+            stop_lines.push(Line::from(format!(" Lines: {}▏", self.lines_input)).yellow());
+        }
+        Paragraph::new(Text::from(stop_lines))
+            .block(stops)
+            .render(columns[0], buf);
+
+        let cursor = if self.focus == Focus::Search {
+            "▏"
+        } else {
+            ""
+        };
+        Paragraph::new(format!(" {}{cursor}", self.query))
+            .block(pane(" Search ", self.focus == Focus::Search))
+            .render(right[0], buf);
+        Paragraph::new(Text::from(self.result_lines()))
+            .block(pane(" Matching stops ", self.focus == Focus::Results))
+            .render(right[1], buf);
+        Paragraph::new(Text::from(self.preview_lines()))
+            .block(pane(" Live departures ", false))
+            .render(right[2], buf);
+
+        if self.focus == Focus::Close {
+            Paragraph::new(
+                Line::from(" The stops have unsaved changes, save them before closing?")
+                    .yellow()
+                    .bold(),
+            )
+            .render(rows[1], buf);
+        } else if let Some(status) = &self.status {
+            Paragraph::new(Line::from(format!(" {status}")).italic()).render(rows[1], buf);
+        }
+    }
+}
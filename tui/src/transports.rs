@@ -1,16 +1,20 @@
+use chrono::NaiveDateTime;
 use std::collections::HashMap;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use ratatui::{
     buffer::Buffer,
-    layout::Rect,
+    layout::{Constraint, Layout, Rect},
     style::Stylize,
     symbols::border,
-    text::{Line, Text},
+    text::{Line, Span, Text},
     widgets::{Block, Borders, Paragraph, Widget},
 };
 
-use homedisplay::models::transports::{Departure, Site};
+use homedisplay::models::transports::{
+    leave_headline, next_catchable, stockholm_now, Departure, Reachability, RoutePlan, Site,
+    TrafficDeviation, DEPARTURES_STALE_AFTER,
+};
 
 use crate::error::TuiError;
 use crate::utilities::fit_into;
@@ -21,6 +25,9 @@ pub struct Departures {
     pub sites: Vec<Site>,                            // List of transport sites
     pub departures: HashMap<String, Vec<Departure>>, // Departures grouped by site ID
     pub site_errors: HashMap<String, TuiError>,      // Per-site error messages
+    pub deviations: Vec<TrafficDeviation>,           // Disruptions affecting the sites
+    pub routes: Vec<RoutePlan>,                      // Next itineraries of the routes
+    pub route_error: Option<TuiError>,               // Error while planning the routes
     pub error: Option<TuiError>,                     // General error for all sites
 }
 
@@ -30,6 +37,9 @@ impl Default for Departures {
             sites: Vec::new(),
             departures: HashMap::new(),
             site_errors: HashMap::new(),
+            deviations: Vec::new(),
+            routes: Vec::new(),
+            route_error: None,
             error: Some(TuiError::TransportFetch(
                 "No departures were fetched yet".to_string(),
             )),
@@ -65,20 +75,49 @@ impl Default for TransportComponent {
     }
 }
 
+/// Formats a departure as its countdown, line, destination and platform, with its
+/// delay or cancellation highlighted, and whether one has to run to catch it
+fn departure_line(departure: &Departure, now: NaiveDateTime) -> Line<'static> {
+    let mut spans: Vec<Span> = vec![
+        format!("   {:6}", departure.countdown(now)).into(),
+        format!(" - {}", departure.line.name()).bold(),
+        format!(" {}", departure.destination).into(),
+    ];
+    if let Some(platform) = departure.platform() {
+        spans.push(format!(" [{platform}]").dark_gray());
+    }
+
+    if departure.is_cancelled() {
+        spans = spans.into_iter().map(|span| span.crossed_out()).collect();
+        spans.push(" cancelled".red().bold());
+    } else if let Some(delay) = departure.delay_minutes().filter(|delay| *delay > 0) {
+        spans.push(format!(" +{delay}'").yellow());
+    }
+
+    if departure.reachability(now) == Some(Reachability::Run) {
+        spans.push(" run!".yellow().bold());
+    }
+
+    Line::from(spans)
+}
+
 impl Widget for &TransportComponent {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let last_refreshed =
-            Line::from(match SystemTime::now().duration_since(self.last_refresh) {
-                Ok(duration) => {
-                    let seconds = duration.as_secs();
-                    format!(
-                        "{} second{} ago",
-                        seconds,
-                        if seconds > 1 { "s" } else { "" }
-                    )
-                }
-                Err(e) => format!("Err: {}", e.to_string()),
-            });
+        let last_refreshed = match SystemTime::now().duration_since(self.last_refresh) {
+            Ok(duration) if duration.as_secs() > DEPARTURES_STALE_AFTER => {
+                let seconds = duration.as_secs();
+                Line::from(format!("stale, {} seconds ago", seconds)).yellow()
+            }
+            Ok(duration) => {
+                let seconds = duration.as_secs();
+                Line::from(format!(
+                    "{} second{} ago",
+                    seconds,
+                    if seconds > 1 { "s" } else { "" }
+                ))
+            }
+            Err(e) => Line::from(format!("Err: {}", e.to_string())),
+        };
 
         let weather_block = Block::new()
             .borders(Borders::LEFT)
@@ -107,10 +146,18 @@ impl Widget for &TransportComponent {
 
             Text::from(lines)
         } else {
-            let mut lines: Vec<Line> = vec![
-                Line::from("Departures").bold().centered().underlined(),
-                Line::from(""),
-            ];
+            let mut lines: Vec<Line> = vec![];
+            if !self.departures.routes.is_empty() || self.departures.route_error.is_some() {
+                lines.push(Line::from("Routes").bold().centered().underlined());
+                lines.push(Line::from(""));
+                lines.extend(route_lines(
+                    &self.departures,
+                    stockholm_now(),
+                    area.width.saturating_sub(4) as usize,
+                ));
+            }
+            lines.push(Line::from("Departures").bold().centered().underlined());
+            lines.push(Line::from(""));
 
             for site in &self.departures.sites {
                 lines.push(Line::from(vec![
@@ -129,12 +176,22 @@ impl Widget for &TransportComponent {
                     lines.push(Line::from(format!("Error: {}", error_msg)).red())
                 }
 
-                for departure in &self.departures.departures[&site.id] {
-                    lines.push(Line::from(vec![
-                        format!("   {:6}", departure.display).into(),
-                        format!(" - {}", departure.line.id).bold(),
-                        format!(" {}", departure.destination).into(),
-                    ]));
+                let now = stockholm_now();
+                let upcoming: Vec<&Departure> = self.departures.departures[&site.id]
+                    .iter()
+                    .filter(|departure| {
+                        !departure.has_departed(now)
+                            && departure.reachability(now) != Some(Reachability::Missed)
+                    })
+                    .collect();
+
+                if let Some(headline) = next_catchable(upcoming.iter().copied(), now)
+                    .and_then(|departure| leave_headline(departure, now))
+                {
+                    lines.push(Line::from(format!("   {headline}")).green().bold());
+                }
+                for departure in upcoming {
+                    lines.push(departure_line(departure, now));
                 }
                 lines.push(Line::from(""));
             }
@@ -142,8 +199,81 @@ impl Widget for &TransportComponent {
             Text::from(lines)
         };
 
-        Paragraph::new(counter_text)
-            .block(weather_block)
-            .render(area, buf);
+        let inner = weather_block.inner(area);
+        weather_block.render(area, buf);
+
+        let deviations_height = if self.departures.deviations.is_empty() {
+            0
+        } else {
+            (inner.height / 3).min(8)
+        };
+        let rows = Layout::vertical([Constraint::Min(0), Constraint::Length(deviations_height)])
+            .split(inner);
+
+        Paragraph::new(counter_text).render(rows[0], buf);
+        render_deviations(&self.departures.deviations, rows[1], buf);
+    }
+}
+
+/// Formats the routes as their headline followed by their next itineraries, each with its
+/// times and transfers and, below, its legs
+fn route_lines(departures: &Departures, now: NaiveDateTime, width: usize) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = vec![];
+    if let Some(e) = &departures.route_error {
+        let error_msg = if log::log_enabled!(log::Level::Debug) {
+            e.to_string()
+        } else {
+            e.user_message().to_string()
+        };
+        lines.push(Line::from(format!(" Error: {}", error_msg)).red());
+        lines.push(Line::from(""));
+    }
+
+    for plan in departures.routes.iter() {
+        for line in fit_into(plan.headline(now), width) {
+            lines.push(Line::from(format!(" {line}")).cyan().bold());
+        }
+        for itinerary in plan.upcoming(now) {
+            lines.push(Line::from(format!("   {}", itinerary.summary())));
+            for legs in fit_into(itinerary.legs_summary(), width.saturating_sub(4)) {
+                lines.push(Line::from(format!("     {legs}")).dark_gray());
+            }
+        }
+        lines.push(Line::from(""));
     }
+    lines
+}
+
+/// Renders the deviations as a list of messages below a title. When the messages do not
+/// fit in the area, the list scrolls by one line every two seconds.
+fn render_deviations(deviations: &[TrafficDeviation], area: Rect, buf: &mut Buffer) {
+    if deviations.is_empty() || area.height < 2 {
+        return;
+    }
+
+    let messages: Vec<Line> = deviations
+        .iter()
+        .flat_map(|deviation| {
+            fit_into(
+                format!("⚠ {}", deviation.summary()),
+                area.width.saturating_sub(1) as usize,
+            )
+        })
+        .map(|line| Line::from(line).yellow())
+        .collect();
+
+    let visible = (area.height - 1) as usize;
+    let offset = if messages.len() > visible {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        (seconds / 2) as usize % messages.len()
+    } else {
+        0
+    };
+
+    let mut lines: Vec<Line> = vec![Line::from("Disruptions").red().bold().centered()];
+    lines.extend(messages.iter().cycle().skip(offset).take(visible).cloned());
+    Paragraph::new(Text::from(lines)).render(area, buf);
 }
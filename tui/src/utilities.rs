@@ -1,4 +1,5 @@
 use log::{info, trace};
+use std::io;
 use std::time::SystemTime;
 
 use crate::currency::CurrencyComponent;
@@ -6,10 +7,10 @@ use crate::error::TuiError;
 use crate::transports::TransportComponent;
 use crate::weather::WeatherComponent;
 
-use homedisplay::currency::database::fetch_current_conversion;
+use homedisplay::currency::database::fetch_current_conversions;
 use homedisplay::models::transports::Departure;
 use homedisplay::settings;
-use homedisplay::transports::database::{get_departures, get_sites};
+use homedisplay::transports::database::{get_sites, get_stop_departures, nearest_sites};
 use homedisplay::weather::database::fetch_current_weather;
 
 /// Refreshes weather data by creating a tokio runtime and fetching from the database
@@ -64,7 +65,7 @@ pub fn refresh_conversion(
         Ok(rt) => rt,
         Err(e) => {
             let mut conversion: CurrencyComponent = CurrencyComponent::default();
-            conversion.conversion = Err(TuiError::TokioRuntime(format!(
+            conversion.conversions = Err(TuiError::TokioRuntime(format!(
                 "Unable to build tokio runtime for currency: {}",
                 e
             )));
@@ -72,7 +73,7 @@ pub fn refresh_conversion(
         }
     };
 
-    match rt.block_on(fetch_current_conversion(currency_settings, redis_data)) {
+    match rt.block_on(fetch_current_conversions(currency_settings, redis_data)) {
         Ok(currency) => CurrencyComponent::new(Ok(currency)),
         Err(e) => CurrencyComponent::new(Err(TuiError::CurrencyFetch(e))),
     }
@@ -89,6 +90,7 @@ pub fn refresh_conversion(
 pub fn refresh_sites(
     component: &mut TransportComponent,
     stops: Vec<settings::BusStop>,
+    transit: settings::Transit,
     redis_data: &settings::Redis,
 ) {
     let rt = match tokio::runtime::Builder::new_current_thread()
@@ -109,7 +111,7 @@ pub fn refresh_sites(
     component.departures.error = None;
     component.departures.site_errors.clear();
 
-    let sites = match rt.block_on(get_sites(&stops, redis_data)) {
+    let sites = match rt.block_on(get_sites(&stops, &transit, redis_data)) {
         Ok(site) => site,
         Err(e) => {
             component.departures.error = Some(TuiError::TransportFetch(format!(
@@ -123,39 +125,25 @@ pub fn refresh_sites(
     let mut empty_sites = vec![];
 
     for site in sites.iter() {
-        let mut filter_on = vec![];
         info!("Refreshing site {} ({})", site.id, site.name);
-        if let Some(stop_index) = stops.iter().position(|stop| stop.name == site.name) {
-            info!(
-                "Finding out whether to filter lines for stop: {}",
-                &stops[stop_index].name
-            );
-            if let Some(lines) = &stops[stop_index].preffered_lines {
-                filter_on = lines.iter().map(|el| *el).collect();
-                info!(
-                    "Filtering stop {} on lines {:?}",
-                    stops[stop_index].name, filter_on
+        let departures: Vec<Departure> = match rt.block_on(get_stop_departures(
+            site.id.clone(),
+            &stops,
+            &transit,
+            redis_data,
+        )) {
+            Ok(departures) => departures,
+            Err(e) => {
+                component.departures.site_errors.insert(
+                    site.id.clone(),
+                    TuiError::TransportFetch(format!(
+                        "Unable to fetch departures for site {}: {}",
+                        site.id, e
+                    )),
                 );
+                continue;
             }
-        }
-
-        let departures: Vec<Departure> =
-            match rt.block_on(get_departures(site.id.clone(), redis_data)) {
-                Ok(departures) => departures
-                    .into_iter()
-                    .filter(|s| (filter_on.is_empty() || filter_on.contains(&s.line.id)))
-                    .collect(),
-                Err(e) => {
-                    component.departures.site_errors.insert(
-                        site.id.clone(),
-                        TuiError::TransportFetch(format!(
-                            "Unable to fetch departures for site {}: {}",
-                            site.id, e
-                        )),
-                    );
-                    continue;
-                }
-            };
+        };
 
         if departures.is_empty() {
             empty_sites.push(site.id.clone());
@@ -174,6 +162,90 @@ pub fn refresh_sites(
     component.last_refresh = SystemTime::now();
 }
 
+/// Prints the sites within the radius (in meters) of the weather location, closest first
+pub fn print_nearby_stops(
+    settings: &settings::Settings,
+    radius: f64,
+    limit: usize,
+) -> io::Result<()> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    let sites = rt
+        .block_on(nearest_sites(
+            settings.weather.latitude,
+            settings.weather.longitude,
+            radius,
+            &settings.transit,
+            &settings.redis,
+        ))
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    if sites.is_empty() {
+        println!(
+            "No stops within {radius:.0} m of {}, {}",
+            settings.weather.latitude, settings.weather.longitude
+        );
+    }
+    for (site, distance) in sites.iter().take(limit) {
+        println!("{distance:>6.0} m  {} ({})", site.name, site.id);
+    }
+    Ok(())
+}
+
+/// Prints the problems found in the settings, exiting with an error code when some
+/// values can't work
+pub fn print_settings_report(settings: &settings::Settings, settings_file: &str) -> io::Result<()> {
+    let report = settings.validate();
+    if report.is_empty() {
+        println!("{settings_file}: no problem found");
+    } else {
+        print!("{report}");
+    }
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Writes the settings to the dump file, or to the standard output when none is given.
+/// The inline secrets being redacted in the dump, the settings file itself is never
+/// overwritten.
+pub fn dump_settings(
+    settings: &settings::Settings,
+    dump_file: Option<&str>,
+    settings_file: &str,
+) -> io::Result<()> {
+    let settings_str = settings
+        .to_string()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let Some(dump_file) = dump_file else {
+        print!("{settings_str}");
+        return Ok(());
+    };
+
+    if is_same_file(dump_file, settings_file) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Refusing to overwrite the settings file {settings_file} with a dump of its redacted values"),
+        ));
+    }
+    std::fs::write(dump_file, settings_str)
+        .map_err(|e| io::Error::new(e.kind(), format!("{dump_file}: {e}")))?;
+    info!("Settings dumped to {}", dump_file);
+    Ok(())
+}
+
+/// Returns whether both paths lead to the same file, comparing the paths themselves
+/// when one of the files does not exist
+fn is_same_file(a: &str, b: &str) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => std::path::Path::new(a) == std::path::Path::new(b),
+    }
+}
+
 /// Splits the content by spaces, and re-arranges it into an iterator
 /// where each element fits the size.
 pub fn fit_into(content: String, size: usize) -> Vec<String> {